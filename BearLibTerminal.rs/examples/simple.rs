@@ -30,7 +30,7 @@ fn main() {
 				terminal::print_xy(0, 0, &*&format!("Width: {}\nHeight: {}", width, height));
 				terminal::refresh();
 			},
-			Event::Close | Event::KeyPressed{key: KeyCode::Escape, ctrl: _, shift: _} => break,
+			Event::Close | Event::KeyPressed{key: KeyCode::Escape, ..} => break,
 			_                                                                         => (),
 		}
 	}
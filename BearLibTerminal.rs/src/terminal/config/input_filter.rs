@@ -12,6 +12,21 @@ pub enum InputFilter {
 	Alnum{keys: String, both: bool},
 }
 
+/// A whole `input.filter`, built up typed, ie to let through keys, text input, mouse buttons (presses and releases),
+/// mouse moves and wheel scrolls, closing and resizing:
+///
+/// ```
+/// use bear_lib_terminal::terminal::config::{ConfigPart, InputFilterSet};
+/// assert_eq!(InputFilterSet::empty().keyboard(false).mouse(true).system().to_config_str(),
+///            "input.filter = ['keyboard', 'mouse+', 'system'];");
+/// ```
+///
+/// See [`terminal::set()`](../fn.set.html).
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct InputFilterSet {
+	filters: Vec<InputFilter>,
+}
+
 /// Filter event group, from [here](http://foo.wyrd.name/en:bearlibterminal:reference:input#inputfilter).
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub enum InputFilterGroup {
@@ -150,6 +165,91 @@ pub enum InputFilterEvent {
 }
 
 
+impl InputFilterSet {
+	/// Construct a filter letting nothing through.
+	pub fn empty() -> InputFilterSet {
+		InputFilterSet{
+			filters: Vec::new(),
+		}
+	}
+
+	/// Everything: all keys (presses only, with their text), all mouse events (with button releases), closing and resizing.
+	pub fn all() -> InputFilterSet {
+		InputFilterSet::empty().keyboard(false).mouse(true).system()
+	}
+
+	/// All keyboard keys, with their releases if `releases`.
+	pub fn keyboard(self, releases: bool) -> Self {
+		self.group(InputFilterGroup::Keyboard, releases)
+	}
+
+	/// Mouse buttons (with their releases if `releases`), moves and wheel scrolls.
+	pub fn mouse(self, releases: bool) -> Self {
+		self.group(InputFilterGroup::Mouse, releases)
+	}
+
+	/// Closing and resizing the window.
+	pub fn system(self) -> Self {
+		self.group(InputFilterGroup::System, false)
+	}
+
+	/// Just the arrow keys.
+	pub fn arrows(self, releases: bool) -> Self {
+		self.group(InputFilterGroup::Arrow, releases)
+	}
+
+	/// Just the numpad keys.
+	pub fn keypad(self, releases: bool) -> Self {
+		self.group(InputFilterGroup::Keypad, releases)
+	}
+
+	/// A single event (ie `InputFilterEvent::MouseMove`).
+	pub fn event(mut self, name: InputFilterEvent, releases: bool) -> Self {
+		self.push(InputFilter::Event{name: name, both: releases});
+		self
+	}
+
+	/// The keys for the given letters and digits (ie `"wasd"`).
+	pub fn keys(mut self, keys: &str, releases: bool) -> Self {
+		self.push(InputFilter::Alnum{keys: keys.to_string(), both: releases});
+		self
+	}
+
+	/// A whole group of events.
+	pub fn group(mut self, group: InputFilterGroup, releases: bool) -> Self {
+		self.push(InputFilter::Group{group: group, both: releases});
+		self
+	}
+
+	/// The filters so far.
+	pub fn filters(&self) -> &[InputFilter] {
+		&self.filters
+	}
+
+	// (with releases wins, if given twice)
+	fn push(&mut self, filter: InputFilter) {
+		let same = |a: &InputFilter, b: &InputFilter| match (a, b) {
+			(&InputFilter::Event{name: ref x, ..}, &InputFilter::Event{name: ref y, ..}) => x == y,
+			(&InputFilter::Group{group: ref x, ..}, &InputFilter::Group{group: ref y, ..}) => x == y,
+			(&InputFilter::Alnum{keys: ref x, ..}, &InputFilter::Alnum{keys: ref y, ..}) => x == y,
+			_ => false,
+		};
+		let both = |f: &InputFilter| match f {
+			&InputFilter::Event{both, ..} | &InputFilter::Group{both, ..} | &InputFilter::Alnum{both, ..} => both,
+		};
+		match self.filters.iter().position(|f| same(f, &filter)) {
+			Some(i) => if both(&filter) { self.filters[i] = filter; },
+			None    => self.filters.push(filter),
+		}
+	}
+}
+
+impl ConfigPart for InputFilterSet {
+	fn to_config_str(&self) -> String {
+		self.filters.to_config_str()
+	}
+}
+
 impl ConfigPart for Vec<InputFilter> {
 	fn to_config_str(&self) -> String {
 		format!("input.filter = [{}];", {
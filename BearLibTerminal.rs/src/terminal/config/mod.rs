@@ -25,6 +25,9 @@ mod input_filter;
 
 pub mod font;
 
+use std::error::Error;
+use std::fmt;
+
 pub use self::section::*;
 pub use self::input_filter::*;
 
@@ -37,6 +40,24 @@ pub trait ConfigPart {
 }
 
 
+/// A configuration BLT refused, returned by [`terminal::try_set()`](../fn.try_set.html).
+///
+/// BLT only logs why, so all that's known is which config string failed.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ConfigError {
+	/// The refused config string.
+	pub config: String,
+}
+
+impl fmt::Display for ConfigError {
+	fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		write!(formatter, "configuration refused: {}", self.config)
+	}
+}
+
+impl Error for ConfigError {}
+
+
 /// Escapes `'`s and wraps the strings with `'`s, as per [this](http://foo.wyrd.name/en:bearlibterminal:reference:configuration#configuration_string_format).
 ///
 /// # Examples
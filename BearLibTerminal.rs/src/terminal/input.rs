@@ -148,7 +148,7 @@ pub enum KeyCode {
 pub enum Event {
 	/// Terminal window closed.
 	Close,
-	/// Terminal window resized. Needs to have `window.resizeable = true` to occur.
+	/// Terminal window resized. Needs to have `window.resizeable = true` to occur (and the `system` input group).
 	///
 	/// Note, that, as of [`40e6253`](https://bitbucket.org/cfyzium/bearlibterminal/commits/40e625311f0cccc43b94633add4dec0d6b77c2b7),
 	/// the terminal window is cleared when resized.
@@ -166,7 +166,11 @@ pub enum Event {
 		/// `0`-based cell index from the left to which the mouse cursor moved.
 		x: i32,
 		/// `0`-based cell index from the top to which the mouse cursor moved.
-		y: i32
+		y: i32,
+		/// `0`-based pixel index from the left of the window to which the mouse cursor moved.
+		pixel_x: i32,
+		/// `0`-based pixel index from the top of the window to which the mouse cursor moved.
+		pixel_y: i32,
 	},
 	/// Mouse wheel moved.
 	MouseScroll{
@@ -175,9 +179,63 @@ pub enum Event {
 		/// Positive when scrolled "down"/"backwards".
 		///
 		/// Negative when scrolled "up"/"forwards"/"away".
-		delta: i32
+		delta: i32,
+		/// `0`-based cell index from the left of the mouse cursor.
+		x: i32,
+		/// `0`-based cell index from the top of the mouse cursor.
+		y: i32,
+		/// `0`-based pixel index from the left of the window of the mouse cursor.
+		pixel_x: i32,
+		/// `0`-based pixel index from the top of the window of the mouse cursor.
+		pixel_y: i32,
+		/// Whether the Control key is pressed.
+		ctrl: bool,
+		/// Whether the Shift key is pressed.
+		shift: bool,
+		/// Whether the Alt key is pressed.
+		alt: bool,
+	},
+	/// A mouse button pressed.
+	MousePressed{
+		/// The button pressed, one of the `KeyCode::Mouse*`s.
+		key: KeyCode,
+		/// `0`-based cell index from the left of the mouse cursor.
+		x: i32,
+		/// `0`-based cell index from the top of the mouse cursor.
+		y: i32,
+		/// `0`-based pixel index from the left of the window of the mouse cursor.
+		pixel_x: i32,
+		/// `0`-based pixel index from the top of the window of the mouse cursor.
+		pixel_y: i32,
+		/// Amount of fast consecutive clicks so far, this one included (ie `2` for a double click).
+		clicks: i32,
+		/// Whether the Control key is pressed.
+		ctrl: bool,
+		/// Whether the Shift key is pressed.
+		shift: bool,
+		/// Whether the Alt key is pressed.
+		alt: bool,
+	},
+	/// A mouse button released.
+	MouseReleased{
+		/// The button released, one of the `KeyCode::Mouse*`s.
+		key: KeyCode,
+		/// `0`-based cell index from the left of the mouse cursor.
+		x: i32,
+		/// `0`-based cell index from the top of the mouse cursor.
+		y: i32,
+		/// `0`-based pixel index from the left of the window of the mouse cursor.
+		pixel_x: i32,
+		/// `0`-based pixel index from the top of the window of the mouse cursor.
+		pixel_y: i32,
+		/// Whether the Control key is pressed.
+		ctrl: bool,
+		/// Whether the Shift key is pressed.
+		shift: bool,
+		/// Whether the Alt key is pressed.
+		alt: bool,
 	},
-	/// A keyboard or mouse button pressed (might repeat, if set in OS).
+	/// A keyboard key pressed (might repeat, if set in OS).
 	KeyPressed{
 		/// The key pressed.
 		key: KeyCode,
@@ -187,8 +245,10 @@ pub enum Event {
 		shift: bool,
 		/// Whether the Alt key is pressed.
 		alt: bool,
+		/// The character typed, if any, as per the keyboard layout and modifiers (ie `'Ä'`).
+		text: Option<char>,
 	},
-	/// A keyboard or mouse button released.
+	/// A keyboard key released.
 	KeyReleased{
 		/// The key released.
 		key: KeyCode,
@@ -8,7 +8,7 @@ pub mod state;
 use std::char;
 use colors::Color;
 use geometry::{Rect, Point, Size};
-use self::config::{ConfigPart, Window};
+use self::config::{ConfigError, ConfigPart, Window};
 use bear_lib_terminal_sys as ffi;
 use bear_lib_terminal_sys::ColorT;
 
@@ -41,6 +41,29 @@ pub fn set<T: ConfigPart>(cfg: T) -> bool {
 	ffi::set(&*&cfg.to_config_str())
 }
 
+/// Like [`set()`](fn.set.html), but a refused config string is returned as a [`ConfigError`](config/struct.ConfigError.html).
+///
+/// Besides malformed strings BLT refuses ones it can't carry out, i.e. fonts or tilesets that fail to load.
+///
+/// # Examples
+/// ```no_run
+/// use bear_lib_terminal::terminal::{self, config::font};
+/// use bear_lib_terminal::geometry::Size;
+/// terminal::open("Example", 80, 30);
+/// if let Err(err) = terminal::try_set(font::true_type(font::Origin::Root, "missing.ttf", Size::new(0, 12))) {
+/// 	assert_eq!(err.config, "font: 'missing.ttf', size=12;");
+/// 	println!("{}", err);
+/// }
+/// ```
+pub fn try_set<T: ConfigPart>(cfg: T) -> Result<(), ConfigError> {
+	let config = cfg.to_config_str();
+	if ffi::set(&*&config) {
+		Ok(())
+	} else {
+		Err(ConfigError{config: config})
+	}
+}
+
 /// Flushes all changes made to the screen; also shows the window after the [`open()`](fn.open.html) call
 ///
 /// Equivalent to the [`terminal_refresh()` C API function](http://foo.wyrd.name/en:bearlibterminal:reference#refresh).
@@ -422,17 +445,79 @@ fn to_keycode(code: i32) -> Option<KeyCode> {
 	}
 }
 
+fn is_mouse_button(key: KeyCode) -> bool {
+	match key {
+		KeyCode::MouseLeft | KeyCode::MouseRight | KeyCode::MouseMiddle | KeyCode::MouseFourth | KeyCode::MouseFifth => true,
+		_ => false,
+	}
+}
+
+/// The terminal state an event comes with, read all at once so that
+/// `event_from()` needn't touch the terminal (and can be tested without one).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct EventState {
+	width: i32,
+	height: i32,
+	mouse_x: i32,
+	mouse_y: i32,
+	mouse_pixel_x: i32,
+	mouse_pixel_y: i32,
+	wheel: i32,
+	clicks: i32,
+	wchar: i32,
+	ctrl: bool,
+	shift: bool,
+	alt: bool,
+}
+
+impl EventState {
+	fn read() -> EventState {
+		EventState{
+			width: ffi::state(ffi::TK_WIDTH),
+			height: ffi::state(ffi::TK_HEIGHT),
+			mouse_x: ffi::state(ffi::TK_MOUSE_X),
+			mouse_y: ffi::state(ffi::TK_MOUSE_Y),
+			mouse_pixel_x: ffi::state(ffi::TK_MOUSE_PIXEL_X),
+			mouse_pixel_y: ffi::state(ffi::TK_MOUSE_PIXEL_Y),
+			wheel: ffi::state(ffi::TK_MOUSE_WHEEL),
+			clicks: ffi::state(ffi::TK_MOUSE_CLICKS),
+			wchar: ffi::state(ffi::TK_WCHAR),
+			ctrl: ffi::check(ffi::TK_CONTROL),
+			shift: ffi::check(ffi::TK_SHIFT),
+			alt: ffi::check(ffi::TK_ALT),
+		}
+	}
+}
+
 fn to_event(code: i32) -> Option<Event> {
+	event_from(code, &EventState::read())
+}
+
+fn event_from(code: i32, state: &EventState) -> Option<Event> {
 	match code {
 		ffi::TK_CLOSE        => Some(Event::Close),
-		ffi::TK_RESIZED      => Some(get_window_resize()),
-		ffi::TK_MOUSE_MOVE   => Some(get_mouse_move()),
-		ffi::TK_MOUSE_SCROLL => Some(get_mouse_scroll()),
-		_                    => to_key_event(code),
+		ffi::TK_RESIZED      => Some(Event::Resize{width: state.width, height: state.height}),
+		ffi::TK_MOUSE_MOVE   => Some(Event::MouseMove{
+			x: state.mouse_x,
+			y: state.mouse_y,
+			pixel_x: state.mouse_pixel_x,
+			pixel_y: state.mouse_pixel_y,
+		}),
+		ffi::TK_MOUSE_SCROLL => Some(Event::MouseScroll{
+			delta: state.wheel,
+			x: state.mouse_x,
+			y: state.mouse_y,
+			pixel_x: state.mouse_pixel_x,
+			pixel_y: state.mouse_pixel_y,
+			ctrl: state.ctrl,
+			shift: state.shift,
+			alt: state.alt,
+		}),
+		_                    => to_key_event(code, state),
 	}
 }
 
-fn to_key_event(code: i32) -> Option<Event> {
+fn to_key_event(code: i32, state: &EventState) -> Option<Event> {
 	let key      = code & !ffi::TK_KEY_RELEASED;
 	let released = (code & ffi::TK_KEY_RELEASED) == ffi::TK_KEY_RELEASED;
 
@@ -441,52 +526,88 @@ fn to_key_event(code: i32) -> Option<Event> {
 		ffi::TK_CONTROL => Some(if released {Event::ControlReleased} else {Event::ControlPressed}),
 		ffi::TK_ALT     => Some(if released {Event::AltReleased}   else {Event::AltPressed}),
 		key             => {
-			let ctrl  = ffi::check(ffi::TK_CONTROL);
-			let shift = ffi::check(ffi::TK_SHIFT);
-			let alt   = ffi::check(ffi::TK_ALT);
-
 			match to_keycode(key) {
-				Some(converted) => Some(get_key(released, converted, ctrl, shift, alt)),
+				Some(converted) if is_mouse_button(converted) => Some(get_mouse_button(released, converted, state)),
+				Some(converted) => Some(get_key(released, converted, state)),
 				None            => None,
 			}
 		}
 	}
 }
 
-fn get_window_resize() -> Event {
-	Event::Resize{
-		width: ffi::state(ffi::TK_WIDTH),
-		height: ffi::state(ffi::TK_HEIGHT),
-	}
-}
-
-fn get_mouse_move() -> Event {
-	Event::MouseMove{
-		x: ffi::state(ffi::TK_MOUSE_X),
-		y: ffi::state(ffi::TK_MOUSE_Y),
-	}
-}
-
-fn get_mouse_scroll() -> Event {
-	Event::MouseScroll{
-		delta: ffi::state(ffi::TK_MOUSE_WHEEL),
+fn get_mouse_button(released: bool, key: KeyCode, state: &EventState) -> Event {
+	let &EventState{mouse_x: x, mouse_y: y, mouse_pixel_x: pixel_x, mouse_pixel_y: pixel_y, ctrl, shift, alt, ..} = state;
+	if released {
+		Event::MouseReleased{key, x, y, pixel_x, pixel_y, ctrl, shift, alt}
+	} else {
+		Event::MousePressed{key, x, y, pixel_x, pixel_y, clicks: state.clicks, ctrl, shift, alt}
 	}
 }
 
-fn get_key(released: bool, key: KeyCode, ctrl: bool, shift: bool, alt: bool) -> Event {
+fn get_key(released: bool, key: KeyCode, state: &EventState) -> Event {
 	if released {
 		Event::KeyReleased{
 			key: key,
-			ctrl: ctrl,
-			shift: shift,
-			alt: alt,
+			ctrl: state.ctrl,
+			shift: state.shift,
+			alt: state.alt,
 		}
 	} else {
 		Event::KeyPressed{
 			key: key,
-			ctrl: ctrl,
-			shift: shift,
-			alt: alt,
+			ctrl: state.ctrl,
+			shift: state.shift,
+			alt: state.alt,
+			text: typed_char(state.wchar),
 		}
 	}
 }
+
+/// The printable character for a `TK_WCHAR` state, if any.
+fn typed_char(wchar: i32) -> Option<char> {
+	match wchar {
+		0  => None,
+		ch => char::from_u32(ch as u32).filter(|ch| !ch.is_control()),
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::{event_from, typed_char, EventState};
+	use super::input::{Event, KeyCode};
+	use bear_lib_terminal_sys as ffi;
+
+	fn state() -> EventState {
+		EventState{mouse_x: 3, mouse_y: 4, mouse_pixel_x: 30, mouse_pixel_y: 64, wheel: -2, clicks: 2, wchar: 'a' as i32,
+		           shift: true, ..EventState::default()}
+	}
+
+	#[test]
+	fn mouse_events() {
+		let st = state();
+		assert_eq!(event_from(ffi::TK_MOUSE_SCROLL, &st),
+		           Some(Event::MouseScroll{delta: -2, x: 3, y: 4, pixel_x: 30, pixel_y: 64, ctrl: false, shift: true, alt: false}));
+		assert_eq!(event_from(ffi::TK_MOUSE_MOVE, &st), Some(Event::MouseMove{x: 3, y: 4, pixel_x: 30, pixel_y: 64}));
+		assert_eq!(event_from(ffi::TK_MOUSE_RIGHT, &st),
+		           Some(Event::MousePressed{key: KeyCode::MouseRight, x: 3, y: 4, pixel_x: 30, pixel_y: 64, clicks: 2,
+		                                    ctrl: false, shift: true, alt: false}));
+		assert_eq!(event_from(ffi::TK_MOUSE_X1 | ffi::TK_KEY_RELEASED, &st),
+		           Some(Event::MouseReleased{key: KeyCode::MouseFourth, x: 3, y: 4, pixel_x: 30, pixel_y: 64,
+		                                     ctrl: false, shift: true, alt: false}));
+	}
+
+	#[test]
+	fn key_events() {
+		let st = state();
+		assert_eq!(event_from(ffi::TK_A, &st), Some(Event::KeyPressed{key: KeyCode::A, ctrl: false, shift: true, alt: false, text: Some('a')}));
+		assert_eq!(event_from(ffi::TK_A | ffi::TK_KEY_RELEASED, &st), Some(Event::KeyReleased{key: KeyCode::A, ctrl: false, shift: true, alt: false}));
+		assert_eq!(event_from(ffi::TK_SHIFT | ffi::TK_KEY_RELEASED, &st), Some(Event::ShiftReleased));
+		assert_eq!(event_from(ffi::TK_RESIZED, &EventState{width: 80, height: 25, ..st}), Some(Event::Resize{width: 80, height: 25}));
+		assert_eq!(event_from(ffi::TK_CLOSE, &st), Some(Event::Close));
+		assert_eq!(typed_char(0), None);
+		assert_eq!(typed_char(0x1B), None);
+		assert_eq!(typed_char(0xC4), Some('Ä'));
+		assert_eq!(typed_char(0xD800), None);
+	}
+}
@@ -0,0 +1,504 @@
+////////////////////////////////////////////////////////////////////////////////
+
+// Renderer for plain (ie ssh) Unix terminals, using ANSI escapes.
+// Raw mode is set via stty (so no termios bindings are needed),
+// and the terminal size re-read (likewise) while polling, to notice resizes.
+
+use std::collections::{VecDeque};
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use bear_lib_terminal::terminal::{KeyCode};
+
+use crate::core_systems::*;
+use crate::grid::*;
+use crate::point2d::*;
+use crate::rect2d::*;
+use crate::renderer::{InputEvent, Renderer};
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum ColorMode {
+    TrueColor,
+    Palette256,
+}
+
+impl ColorMode {
+    // truecolor only if the terminal advertises it (via COLORTERM)
+    pub fn detect() -> ColorMode {
+        match std::env::var("COLORTERM") {
+            Ok(v) if v.contains("truecolor") || v.contains("24bit") => ColorMode::TrueColor,
+            _ => ColorMode::Palette256,
+        }
+    }
+}
+
+// nearest xterm 256-colour index, from the 6x6x6 cube or the grey ramp
+pub fn rgb_to_256(r:u8, g:u8, b:u8) -> u8 {
+    let levels : [i32; 6] = [0x00, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    let nearest = |v:i32| (0..6).min_by_key(|&i| (levels[i] - v).abs()).unwrap();
+    let (ri, gi, bi) = (nearest(r), nearest(g), nearest(b));
+    let grey_i = (((r + g + b)/3 - 8).max(0) / 10).min(23);
+    let grey = 8 + grey_i * 10;
+    let dist = |x:i32, y:i32, z:i32| (x - r).pow(2) + (y - g).pow(2) + (z - b).pow(2);
+    if dist(grey, grey, grey) < dist(levels[ri], levels[gi], levels[bi]) {
+        (232 + grey_i) as u8
+    } else {
+        (16 + 36*ri + 6*gi + bi) as u8
+    }
+}
+
+fn sgr(out:&mut Vec<u8>, c:Color, fg:bool, mode:ColorMode) {
+    let (r, g, b) = (c.r(), c.g(), c.b());
+    let _ = match mode {
+        ColorMode::TrueColor => write!(out, "\x1b[{};2;{};{};{}m", if fg {38} else {48}, r, g, b),
+        ColorMode::Palette256 => write!(out, "\x1b[{};5;{}m", if fg {38} else {48}, rgb_to_256(r, g, b)),
+    };
+}
+
+// escapes to draw the given cells, moving the cursor only where cells aren't
+// consecutive and setting colours only when they change
+pub fn cell_escapes<I:IntoIterator<Item=(Point2d,Glyph)>>(cells:I, mode:ColorMode) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut cursor : Option<Point2d> = None;
+    let mut colors : Option<(Color,Color)> = None;
+    for (p, g) in cells {
+        if cursor != Some(p) {
+            let _ = write!(buf, "\x1b[{};{}H", p.y+1, p.x+1);
+        }
+        if colors.map(|c| c.0) != Some(g.fg) { sgr(&mut buf, g.fg, true, mode); }
+        if colors.map(|c| c.1) != Some(g.bg) { sgr(&mut buf, g.bg, false, mode); }
+        colors = Some((g.fg, g.bg));
+        let ch = if g.ch < ' ' { ' ' } else { g.ch };
+        let _ = write!(buf, "{}", ch);
+        cursor = Some(p + Point2d::new(1, 0));
+    }
+    buf
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// decodes keyboard input bytes into key events
+#[derive(Debug,Default)]
+pub struct KeyParser {
+    buf: VecDeque<u8>,
+    pending: VecDeque<InputEvent>,  // text following its key
+}
+
+impl KeyParser {
+    pub fn new() -> Self {
+        KeyParser { buf:VecDeque::new(), pending:VecDeque::new() }
+    }
+
+    pub fn feed(&mut self, bytes:&[u8]) {
+        self.buf.extend(bytes);
+    }
+
+    // next complete key; an incomplete escape sequence is held back unless
+    // no more input is pending (ie a lone ESC is the Escape key)
+    pub fn next(&mut self, more_pending:bool) -> Option<InputEvent> {
+        if let Some(ev) = self.pending.pop_front() { return Some(ev); }
+        loop {
+            let b = *self.buf.front()?;
+            if b >= 0x80 {
+                // utf-8 text, not a key
+                let n = match b { 0xc0..=0xdf => 2, 0xe0..=0xef => 3, 0xf0..=0xf7 => 4, _ => 1 };
+                if self.buf.len() < n && more_pending { return None; }
+                let bytes : Vec<u8> = self.buf.iter().take(n).cloned().collect();
+                match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+                    Some(ch) => {
+                        self.buf.drain(..n);
+                        return Some(InputEvent::Text(ch));
+                    }
+                    None => { self.buf.pop_front(); continue; }
+                }
+            }
+            if b != 0x1b {
+                self.buf.pop_front();
+                if (0x20..0x7f).contains(&b) { self.pending.push_back(InputEvent::Text(b as char)); }
+                match plain_key(b) {
+                    Some(ev) => { return Some(ev); }
+                    None => { continue; }
+                }
+            }
+            let seq : Vec<u8> = self.buf.iter().cloned().collect();
+            match parse_escape(&seq) {
+                EscapeSeq::Key(n, ev) => {
+                    self.buf.drain(..n);
+                    return Some(ev);
+                }
+                EscapeSeq::Unknown(n) => {
+                    self.buf.drain(..n);
+                }
+                EscapeSeq::Incomplete if more_pending => { return None; }
+                EscapeSeq::Incomplete => {
+                    self.buf.pop_front();
+                    return Some(InputEvent::key(KeyCode::Escape));
+                }
+            }
+        }
+    }
+}
+
+fn key(key:KeyCode, ctrl:bool, shift:bool, alt:bool) -> InputEvent {
+    InputEvent::Key { key, ctrl, shift, alt }
+}
+
+fn plain_key(b:u8) -> Option<InputEvent> {
+    use KeyCode::*;
+    let letters = [A,B,C,D,E,F,G,H,I,J,K,L,M,N,O,P,Q,R,S,T,U,V,W,X,Y,Z];
+    let digits = [Row0,Row1,Row2,Row3,Row4,Row5,Row6,Row7,Row8,Row9];
+    let ev = match b {
+        b'\r' | b'\n' => key(Enter, false, false, false),
+        b'\t' => key(Tab, false, false, false),
+        0x7f | 0x08 => key(Backspace, false, false, false),
+        0x01 ..= 0x1a => key(letters[(b - 1) as usize], true, false, false),
+        b'a' ..= b'z' => key(letters[(b - b'a') as usize], false, false, false),
+        b'A' ..= b'Z' => key(letters[(b - b'A') as usize], false, true, false),
+        b'0' ..= b'9' => key(digits[(b - b'0') as usize], false, false, false),
+        b' ' => key(Space, false, false, false),
+        _ => {
+            // punctuation, as the unshifted key with shift where needed (US layout)
+            let (k, shift) = match b {
+                b'-' => (Minus, false), b'_' => (Minus, true),
+                b'=' => (Equals, false), b'+' => (Equals, true),
+                b'[' => (LeftBracket, false), b'{' => (LeftBracket, true),
+                b']' => (RightBracket, false), b'}' => (RightBracket, true),
+                b'\\' => (Backslash, false), b'|' => (Backslash, true),
+                b';' => (Semicolon, false), b':' => (Semicolon, true),
+                b'\'' => (Apostrophe, false), b'"' => (Apostrophe, true),
+                b'`' => (Grave, false), b'~' => (Grave, true),
+                b',' => (Comma, false), b'<' => (Comma, true),
+                b'.' => (Period, false), b'>' => (Period, true),
+                b'/' => (Slash, false), b'?' => (Slash, true),
+                b'!' => (Row1, true), b'@' => (Row2, true), b'#' => (Row3, true),
+                b'$' => (Row4, true), b'%' => (Row5, true), b'^' => (Row6, true),
+                b'&' => (Row7, true), b'*' => (Row8, true), b'(' => (Row9, true),
+                b')' => (Row0, true),
+                _ => { return None; }
+            };
+            key(k, false, shift, false)
+        }
+    };
+    Some(ev)
+}
+
+enum EscapeSeq {
+    Key(usize, InputEvent),     // bytes consumed
+    Unknown(usize),             // well-formed but unrecognised; skip bytes
+    Incomplete,
+}
+
+// seq starts with ESC
+fn parse_escape(seq:&[u8]) -> EscapeSeq {
+    use KeyCode::*;
+    match seq.get(1) {
+        None => EscapeSeq::Incomplete,
+        Some(b'[') | Some(b'O') => {
+            // CSI/SS3: parameters then a final byte in 0x40..=0x7e
+            let end = match seq[2..].iter().position(|&c| (0x40..=0x7e).contains(&c)) {
+                Some(i) => i + 2,
+                None => { return EscapeSeq::Incomplete; }
+            };
+            let params : Vec<i32> = std::str::from_utf8(&seq[2..end]).unwrap_or("")
+                .split(';').map(|s| s.parse().unwrap_or(0)).collect();
+            // xterm modifier parameter is 1 + (shift:1 | alt:2 | ctrl:4)
+            let m = params.get(1).cloned().unwrap_or(1).max(1) - 1;
+            let (shift, alt, ctrl) = (m & 1 != 0, m & 2 != 0, m & 4 != 0);
+            let k = match seq[end] {
+                b'A' => Some(Up),
+                b'B' => Some(Down),
+                b'C' => Some(Right),
+                b'D' => Some(Left),
+                b'H' => Some(Home),
+                b'F' => Some(End),
+                b'P' => Some(F1),
+                b'Q' => Some(F2),
+                b'R' => Some(F3),
+                b'S' => Some(F4),
+                b'Z' => { return EscapeSeq::Key(end+1, key(Tab, false, true, false)); }
+                b'~' => match params[0] {
+                    1 | 7 => Some(Home),
+                    2 => Some(Insert),
+                    3 => Some(Delete),
+                    4 | 8 => Some(End),
+                    5 => Some(PageUp),
+                    6 => Some(PageDown),
+                    11 => Some(F1), 12 => Some(F2), 13 => Some(F3), 14 => Some(F4),
+                    15 => Some(F5), 17 => Some(F6), 18 => Some(F7), 19 => Some(F8),
+                    20 => Some(F9), 21 => Some(F10), 23 => Some(F11), 24 => Some(F12),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match k {
+                Some(k) => EscapeSeq::Key(end+1, key(k, ctrl, shift, alt)),
+                None => EscapeSeq::Unknown(end+1),
+            }
+        }
+        Some(0x1b) => EscapeSeq::Key(1, InputEvent::key(Escape)),
+        Some(&b) => {
+            // alt+key
+            match plain_key(b) {
+                Some(InputEvent::Key { key:k, ctrl, shift, .. }) => EscapeSeq::Key(2, key(k, ctrl, shift, true)),
+                _ => EscapeSeq::Unknown(2),
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// stty against the controlling terminal
+fn stty(args:&[&str]) -> io::Result<String> {
+    let tty = std::fs::File::open("/dev/tty")?;
+    let out = Command::new("stty").args(args).stdin(Stdio::from(tty)).output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&out.stderr).into_owned()));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+// terminal size as (cols, rows)
+fn stty_size() -> Option<Point2d> {
+    let size : Vec<i32> = stty(&["size"]).ok()?.split_whitespace().filter_map(|s| s.parse().ok()).collect();
+    match size[..] {
+        [rows, cols] => Some(Point2d::new(cols, rows)),
+        _ => None,
+    }
+}
+
+// asked for the terminal size on each poll_event; None if unknown (or not checked)
+pub type SizeSource = Box<dyn FnMut() -> Option<Point2d>>;
+
+// stty_size, at most every 250ms, as each check runs a process
+fn stty_size_source() -> SizeSource {
+    let mut last = Instant::now();
+    Box::new(move || {
+        if last.elapsed() < Duration::from_millis(250) { return None; }
+        last = Instant::now();
+        stty_size()
+    })
+}
+
+pub struct AnsiRenderer<W:Write> {
+    out: W,
+    mode: ColorMode,
+    screen: Grid<Glyph>,
+    shown: Option<Grid<Glyph>>,     // what the terminal currently displays (None if unknown)
+    input: Option<Receiver<Vec<u8>>>,
+    parser: KeyParser,
+    saved_stty: Option<String>,     // to restore on drop, if we changed it
+    size_source: Option<SizeSource>,
+}
+
+impl AnsiRenderer<io::Stdout> {
+    // takes over the controlling terminal: raw mode, alternate screen, hidden cursor
+    pub fn open(mode:ColorMode) -> io::Result<Self> {
+        let size = stty_size().ok_or_else(|| io::Error::other("cannot get terminal size"))?;
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 64];
+            let mut stdin = io::stdin();
+            while let Ok(n) = stdin.read(&mut buf) {
+                if n == 0 || tx.send(buf[..n].to_vec()).is_err() { break; }
+            }
+        });
+        let mut res = AnsiRenderer::new(io::stdout(), size, mode).with_size_source(stty_size_source());
+        res.input = Some(rx);
+        res.saved_stty = Some(saved);
+        res.out.write_all(b"\x1b[?1049h\x1b[?25l\x1b[0m\x1b[2J")?;
+        res.out.flush()?;
+        Ok(res)
+    }
+}
+
+impl<W:Write> AnsiRenderer<W> {
+    // renders to any writer (ie a Vec<u8> in tests), with no input
+    pub fn new(out:W, size:Point2d, mode:ColorMode) -> Self {
+        let span = Rect2d::new(Point2d::new(0,0), size);
+        AnsiRenderer { out, mode, screen:Grid::new(span, Glyph::default()), shown:None,
+            input:None, parser:KeyParser::new(), saved_stty:None, size_source:None }
+    }
+
+    // where resizes are noticed from (see SizeSource)
+    pub fn with_size_source(mut self, source:SizeSource) -> Self {
+        self.size_source = Some(source);
+        self
+    }
+
+    pub fn writer(&self) -> &W {
+        &self.out
+    }
+
+    pub fn writer_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    // cells differing from what's shown, in screen order
+    fn diff(&self) -> Vec<(Point2d,Glyph)> {
+        self.screen.span().into_iter()
+            .map(|p| (p, *self.screen.get(p).unwrap()))
+            .filter(|(p, g)| self.shown.as_ref().is_none_or(|s| s.get(*p) != Some(g)))
+            .collect()
+    }
+}
+
+impl<W:Write> Renderer for AnsiRenderer<W> {
+    fn size(&self) -> Point2d {
+        self.screen.span().size()
+    }
+
+    fn put(&mut self, p:Point2d, g:Glyph) {
+        self.screen.set(p, g);
+    }
+
+    fn clear(&mut self) {
+        self.screen.fill(Glyph::default());
+    }
+
+    fn refresh(&mut self) {
+        let buf = cell_escapes(self.diff(), self.mode);
+        if !buf.is_empty() {
+            let _ = self.out.write_all(&buf).and_then(|_| self.out.flush());
+        }
+        self.shown = Some(self.screen.clone());
+    }
+
+    fn poll_event(&mut self) -> Option<InputEvent> {
+        if let Some(size) = self.size_source.as_mut().and_then(|f| f()) {
+            if size != self.size() && size.x > 0 && size.y > 0 {
+                self.screen.resize(size, Anchor::Min, Anchor::Min, Glyph::default());
+                // the terminal may have reflowed or cleared, so redraw it all
+                self.shown = None;
+                let _ = self.out.write_all(b"\x1b[0m\x1b[2J");
+                return Some(InputEvent::Resize(size));
+            }
+        }
+        let mut more = false;
+        if let Some(rx) = &self.input {
+            while let Ok(bytes) = rx.try_recv() {
+                self.parser.feed(&bytes);
+            }
+            // give a split escape sequence a moment to finish arriving
+            if let Ok(bytes) = rx.recv_timeout(std::time::Duration::from_millis(if self.parser.buf.front() == Some(&0x1b) {15} else {0})) {
+                self.parser.feed(&bytes);
+                more = true;
+            }
+        }
+        self.parser.next(more)
+    }
+}
+
+impl<W:Write> Drop for AnsiRenderer<W> {
+    fn drop(&mut self) {
+        if let Some(saved) = self.saved_stty.take() {
+            let _ = self.out.write_all(b"\x1b[0m\x1b[?25h\x1b[?1049l");
+            let _ = self.out.flush();
+            let _ = stty(&[&saved]);
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(bytes:&[u8]) -> Vec<InputEvent> {
+        let mut p = KeyParser::new();
+        p.feed(bytes);
+        std::iter::from_fn(|| p.next(false)).filter(|ev| !matches!(ev, InputEvent::Text(_))).collect()
+    }
+
+    fn text(bytes:&[u8]) -> String {
+        let mut p = KeyParser::new();
+        p.feed(bytes);
+        std::iter::from_fn(|| p.next(false)).filter_map(|ev| match ev { InputEvent::Text(ch) => Some(ch), _ => None }).collect()
+    }
+
+    #[test]
+    fn parses_keys() {
+        use KeyCode::*;
+        assert_eq!(keys(b"aQ\r"), vec![key(A,false,false,false), key(Q,false,true,false), key(Enter,false,false,false)]);
+        assert_eq!(keys(b"\x1b[A\x1bOB\x1b[5~\x1b[1;5C"), vec![
+            key(Up,false,false,false), key(Down,false,false,false),
+            key(PageUp,false,false,false), key(Right,true,false,false)]);
+        assert_eq!(keys(b"\x1b[15~\x1bx\x03?"), vec![
+            key(F5,false,false,false), key(X,false,false,true), key(C,true,false,false), key(Slash,false,true,false)]);
+        assert_eq!(keys(b"\x1b"), vec![InputEvent::key(Escape)]);
+        // unknown sequences are skipped, & non-ascii text comes as text only
+        assert_eq!(keys("\x1b[99xé1".as_bytes()), vec![key(Row1,false,false,false)]);
+        assert_eq!(text("\x1b[Aa ḫ\x01ā\r".as_bytes()), "a ḫā");
+        let mut p = KeyParser::new();
+        p.feed(&"ṣ".as_bytes()[..2]);
+        assert_eq!(p.next(true), None);
+        p.feed(&"ṣ".as_bytes()[2..]);
+        assert_eq!(p.next(false), Some(InputEvent::Text('ṣ')));
+        // a split sequence waits for the rest while more input is pending
+        let mut p = KeyParser::new();
+        p.feed(b"\x1b[");
+        assert_eq!(p.next(true), None);
+        p.feed(b"D");
+        assert_eq!(p.next(false), Some(InputEvent::key(Left)));
+    }
+
+    #[test]
+    fn palette_approximation() {
+        assert_eq!(rgb_to_256(0,0,0), 16);
+        assert_eq!(rgb_to_256(255,255,255), 231);
+        assert_eq!(rgb_to_256(255,0,0), 196);
+        assert_eq!(rgb_to_256(0x80,0x80,0x80), 244);
+    }
+
+    #[test]
+    fn emits_only_changed_cells() {
+        let mut r = AnsiRenderer::new(Vec::new(), Point2d::new(4,2), ColorMode::TrueColor);
+        r.print(Point2d::new(0,0), "ab", Color::white(), Color::black());
+        r.refresh();
+        assert!(r.writer().len() > 8*3);
+        r.writer_mut().clear();
+        r.refresh();
+        assert!(r.writer().is_empty());
+        r.put(Point2d::new(2,1), Glyph::new('z', Color::white(), Color::black()));
+        r.put(Point2d::new(3,1), Glyph::new('y', Color::white(), Color::black()));
+        r.refresh();
+        let out = String::from_utf8(r.writer().clone()).unwrap();
+        assert_eq!(out, "\x1b[2;3H\x1b[38;2;255;255;255m\x1b[48;2;0;0;0mzy");
+        let mut r = AnsiRenderer::new(Vec::new(), Point2d::new(1,1), ColorMode::Palette256);
+        r.put(Point2d::new(0,0), Glyph::new('#', Color::white(), Color::black()));
+        r.refresh();
+        assert_eq!(String::from_utf8(r.writer().clone()).unwrap(), "\x1b[1;1H\x1b[38;5;231m\x1b[48;5;16m#");
+    }
+
+    #[test]
+    fn notices_resizes() {
+        let sizes = std::rc::Rc::new(std::cell::RefCell::new(vec![Point2d::new(5,3), Point2d::new(4,2)]));
+        let source = sizes.clone();
+        let mut r = AnsiRenderer::new(Vec::new(), Point2d::new(4,2), ColorMode::TrueColor)
+            .with_size_source(Box::new(move || source.borrow_mut().pop()));
+        r.put(Point2d::new(0,0), Glyph::new('a', Color::white(), Color::black()));
+        r.refresh();
+        r.writer_mut().clear();
+        // unchanged, then changed once
+        assert_eq!(r.poll_event(), None);
+        assert_eq!(r.poll_event(), Some(InputEvent::Resize(Point2d::new(5,3))));
+        assert_eq!(r.poll_event(), None);
+        assert_eq!(r.size(), Point2d::new(5,3));
+        assert!(sizes.borrow().is_empty());
+        // the screen is cleared, and the next refresh redraws every cell
+        r.refresh();
+        let out = String::from_utf8(r.writer().clone()).unwrap();
+        assert!(out.starts_with("\x1b[0m\x1b[2J\x1b[1;1H"));
+        assert!(out.contains('a'));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
@@ -3,7 +3,7 @@
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Clone,Copy,Default,Eq,Hash,Ord,PartialEq,PartialOrd)]
+#[derive(Clone,Copy,Default,Eq,Hash,Ord,PartialEq,PartialOrd,serde::Serialize,serde::Deserialize)]
 pub struct Color(pub u32);
 
 #[allow(non_upper_case_globals)]
@@ -42,6 +42,32 @@ impl Color {
             (((self.a() as f64)*w).round().min(255.0).max(0.0) as u8))
     }
 
+    // self composited over below (alpha-weighted by self's alpha)
+    pub fn blend(&self, below:&Color, mode:BlendMode) -> Color {
+        let sa = self.a() as f64 / 255.0;
+        let da = below.a() as f64 / 255.0;
+        let ch = |s:u8, d:u8| -> u8 {
+            let (s, d) = (s as f64 / 255.0, d as f64 / 255.0);
+            let c = match mode {
+                BlendMode::Replace => { return (s*255.0).round() as u8; }
+                BlendMode::Normal => s,
+                BlendMode::Multiply => s*d,
+                BlendMode::Additive => (s + d).min(1.0),
+            };
+            ((c*sa + d*(1.0-sa))*255.0).round().clamp(0.0, 255.0) as u8
+        };
+        let a = if mode == BlendMode::Replace { self.a() } else { ((sa + da*(1.0-sa))*255.0).round() as u8 };
+        Color::rgba(ch(self.r(),below.r()), ch(self.g(),below.g()), ch(self.b(),below.b()), a)
+    }
+
+    #[inline]
+    pub fn transparent() -> Color { Color(0) }
+
+    #[inline]
+    pub fn with_alpha(&self, a:u8) -> Color {
+        Color::rgba(self.r(), self.g(), self.b(), a)
+    }
+
     #[inline]
     pub fn blt(&self) -> bear_lib_terminal::Color {
         bear_lib_terminal::Color::from_rgba(self.r(), self.g(), self.b(), self.a())
@@ -132,6 +158,103 @@ impl Color {
     #[inline] pub fn vodka() -> Color { Color::rgb(0xBF,0xC0,0xEE) }
     #[inline] pub fn white_chocolate() -> Color { Color::rgb(0xED,0xE6,0xD6) }
     #[inline] pub fn white() -> Color { Color::rgb(0xFF,0xFF,0xFF) }
+
+    // palette colour by name (ie "gold", "misty_rose"), or "#RRGGBB"
+    pub fn named(name:&str) -> Option<Color> {
+        if let Some(hex) = name.strip_prefix('#') {
+            if hex.len() != 6 { return None; }
+            let v = u32::from_str_radix(hex, 16).ok()?;
+            return Some(Color::rgb((v>>16) as u8, (v>>8) as u8, v as u8));
+        }
+        Some(match name {
+            "alice_blue" => Color::alice_blue,
+            "antique_white" => Color::antique_white(),
+            "azure_mist" => Color::azure_mist(),
+            "black" => Color::black(),
+            "blood" => Color::blood(),
+            "blue" => Color::blue(),
+            "blue_sapphire" => Color::blue_sapphire(),
+            "bronze_metallic" => Color::bronze_metallic(),
+            "brown" => Color::brown(),
+            "cadmium_blue" => Color::cadmium_blue(),
+            "carnation_pink" => Color::carnation_pink(),
+            "cerulean_frost" => Color::cerulean_frost(),
+            "classic_rose" => Color::classic_rose(),
+            "cobalt_blue" => Color::cobalt_blue(),
+            "dark_blue" => Color::dark_blue(),
+            "dark_brown" => Color::dark_brown(),
+            "dark_green" => Color::dark_green(),
+            "dark_grey" => Color::dark_grey(),
+            "dark_saffron" => Color::dark_saffron(),
+            "desert_sand" => Color::desert_sand(),
+            "emerald_green" => Color::emerald_green(),
+            "english_lavender" => Color::english_lavender(),
+            "forest_green" => Color::forest_green(),
+            "gold" => Color::gold(),
+            "granite_gray" => Color::granite_gray(),
+            "green" => Color::green(),
+            "grey" => Color::grey(),
+            "honolulu_blue" => Color::honolulu_blue(),
+            "iron" => Color::iron(),
+            "jade" => Color::jade(),
+            "lapis_lazuli" => Color::lapis_lazuli(),
+            "lavender_blue" => Color::lavender_blue(),
+            "lavender_blush" => Color::lavender_blush(),
+            "lavender_mist" => Color::lavender_mist(),
+            "lilac" => Color::lilac(),
+            "malachite" => Color::malachite(),
+            "mauve" => Color::mauve(),
+            "maximum_blue" => Color::maximum_blue(),
+            "maximum_green" => Color::maximum_green(),
+            "maximum_red" => Color::maximum_red(),
+            "milk" => Color::milk(),
+            "mint" => Color::mint(),
+            "misty_rose" => Color::misty_rose(),
+            "mistyrose" => Color::mistyrose(),
+            "navajo_white" => Color::navajo_white(),
+            "onyx" => Color::onyx(),
+            "opal" => Color::opal(),
+            "pale_lavender" => Color::pale_lavender(),
+            "pale_pink" => Color::pale_pink(),
+            "pale_plum" => Color::pale_plum(),
+            "papaya_whip" => Color::papaya_whip(),
+            "parakeet_blue" => Color::parakeet_blue(),
+            "pearl" => Color::pearl(),
+            "persian_blue" => Color::persian_blue(),
+            "pistachio" => Color::pistachio(),
+            "prussian_blue" => Color::prussian_blue(),
+            "pure_blue" => Color::pure_blue(),
+            "pure_green" => Color::pure_green(),
+            "pure_red" => Color::pure_red(),
+            "quartz" => Color::quartz(),
+            "rose_garnet" => Color::rose_garnet(),
+            "rose_quartz" => Color::rose_quartz(),
+            "royal_purple" => Color::royal_purple(),
+            "saffron" => Color::saffron(),
+            "sage" => Color::sage(),
+            "sandstorm" => Color::sandstorm(),
+            "sapphire" => Color::sapphire(),
+            "sea_blue" => Color::sea_blue(),
+            "smoke" => Color::smoke(),
+            "smokey_topaz" => Color::smokey_topaz(),
+            "smoky_topaz" => Color::smoky_topaz(),
+            "snow" => Color::snow(),
+            "tangelo" => Color::tangelo(),
+            "tea_rose" => Color::tea_rose(),
+            "tea_rose2" => Color::tea_rose2(),
+            "teal_blue" => Color::teal_blue(),
+            "thistle" => Color::thistle(),
+            "thulian_pink" => Color::thulian_pink(),
+            "titanium" => Color::titanium(),
+            "tuscan" => Color::tuscan(),
+            "twilight_lavender" => Color::twilight_lavender(),
+            "vanilla" => Color::vanilla(),
+            "vodka" => Color::vodka(),
+            "white" => Color::white(),
+            "white_chocolate" => Color::white_chocolate(),
+            _ => { return None; }
+        })
+    }
 }
 
 impl ::std::fmt::Debug for Color {
@@ -141,7 +264,7 @@ impl ::std::fmt::Debug for Color {
     }
 }
 
-#[derive(Clone,Copy,Default,Eq,PartialEq)]
+#[derive(Clone,Copy,Default,Eq,PartialEq,serde::Serialize,serde::Deserialize)]
 pub struct Glyph {
     pub ch: char,
     pub fg: Color,
@@ -152,6 +275,47 @@ impl Glyph {
     pub fn new(ch:char, fg:Color, bg:Color) -> Self {
         Glyph { ch, fg, bg }
     }
+
+    // passes whatever is beneath straight through when composited
+    pub fn transparent() -> Self {
+        Glyph { ch:'\0', fg:Color::transparent(), bg:Color::transparent() }
+    }
+
+    // tints whatever is beneath (eg fog, damage flash), keeping its character
+    pub fn tint(bg:Color) -> Self {
+        Glyph { ch:'\0', fg:Color::transparent(), bg }
+    }
+}
+
+////////////////////////////////////////
+
+// how a window's cells are combined onto its parent
+#[derive(Clone,Copy,Debug,Default,Eq,Hash,PartialEq,serde::Serialize,serde::Deserialize)]
+pub enum BlendMode {
+    #[default]
+    Replace,    // overwrite outright (ignoring alpha)
+    Normal,     // alpha "over"
+    Multiply,   // darken by multiplying colors
+    Additive,   // lighten by adding colors
+}
+
+// cells which windows can composite onto their parents
+pub trait Composite {
+    fn composite(&self, below:&Self, mode:BlendMode) -> Self;
+}
+
+impl Composite for Glyph {
+    // a '\0' (or fully transparent) character keeps the character beneath,
+    // seen through this cell's background
+    fn composite(&self, below:&Glyph, mode:BlendMode) -> Glyph {
+        if mode == BlendMode::Replace { return *self; }
+        let bg = self.bg.blend(&below.bg, mode);
+        if self.ch == '\0' || self.fg.a() == 0 {
+            Glyph { ch:below.ch, fg:self.bg.blend(&below.fg, mode), bg }
+        } else {
+            Glyph { ch:self.ch, fg:self.fg.blend(&bg, mode), bg }
+        }
+    }
 }
 
 impl ::std::fmt::Debug for Glyph {
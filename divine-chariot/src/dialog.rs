@@ -0,0 +1,441 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use std::rc::{Rc};
+
+use bear_lib_terminal::terminal::{KeyCode};
+
+use crate::core_systems::*;
+use crate::keymap::{Command, Context, KeyChord, Keymap};
+use crate::point2d::*;
+use crate::renderer::{InputEvent};
+use crate::text::{RichText};
+use crate::time_manager::{TimeManager};
+use crate::widgets::{Align, CompleteFn, EditEvent, Frame, History, LineEdit, ListMenu, MenuEvent, DOUBLE_BORDER, SINGLE_BORDER};
+use crate::window::{WindowHandle};
+
+////////////////////////////////////////////////////////////////////////////////
+
+// what a layer did with an event
+pub enum Response {
+    Pass,                   // not handled, offer it to the layer beneath
+    Consume,                // handled
+    Close,                  // handled, and this layer is done
+    Push(Box<dyn Layer>),   // handled, and open another layer above this one
+}
+
+// something taking over input while it's open (a dialog, inventory screen, targeting, ...)
+pub trait Layer {
+    // (raised above its siblings when pushed, demolished when popped)
+    fn window(&self) -> &WindowHandle<Glyph>;
+
+    fn handle(&mut self, ev:&InputEvent) -> Response;
+
+    // gaining focus (on being pushed, or uncovered by the layer above popping)
+    fn focus(&mut self) {}
+
+    // losing focus (covered by another layer, or popped)
+    fn blur(&mut self) {}
+
+    // once a frame, ie to keep up with what's beneath
+    fn update(&mut self) {}
+}
+
+// layers over the game, topmost getting input first; the game is paused
+// (through the TimeManager) while any are open
+#[derive(Default)]
+pub struct LayerStack {
+    layers: Vec<Box<dyn Layer>>,
+}
+
+impl LayerStack {
+    pub fn new() -> Self {
+        LayerStack { layers:Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    pub fn top(&self) -> Option<&dyn Layer> {
+        self.layers.last().map(|l| l.as_ref())
+    }
+
+    pub fn push(&mut self, mut layer:Box<dyn Layer>, time:&mut TimeManager) {
+        if let Some(top) = self.layers.last_mut() { top.blur(); }
+        layer.window().upper();
+        layer.focus();
+        time.pause();
+        self.layers.push(layer);
+    }
+
+    pub fn pop(&mut self, time:&mut TimeManager) -> Option<Box<dyn Layer>> {
+        let i = self.layers.len().checked_sub(1)?;
+        Some(self.remove(i, time))
+    }
+
+    pub fn clear(&mut self, time:&mut TimeManager) {
+        while self.pop(time).is_some() {}
+    }
+
+    fn remove(&mut self, i:usize, time:&mut TimeManager) -> Box<dyn Layer> {
+        let was_top = i + 1 == self.layers.len();
+        let mut layer = self.layers.remove(i);
+        if was_top { layer.blur(); }
+        layer.window().demolish();
+        time.unpause();
+        if let (true, Some(top)) = (was_top, self.layers.last_mut()) { top.focus(); }
+        layer
+    }
+
+    pub fn update(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.update();
+        }
+    }
+
+    // offers ev to each layer from the top down, until one doesn't pass it;
+    // returns false if all passed it (so it's for the game)
+    pub fn handle(&mut self, ev:&InputEvent, time:&mut TimeManager) -> bool {
+        for i in (0 .. self.layers.len()).rev() {
+            match self.layers[i].handle(ev) {
+                Response::Pass => { continue; }
+                Response::Consume => {}
+                Response::Close => { self.remove(i, time); }
+                Response::Push(layer) => { self.push(layer, time); }
+            }
+            return true;
+        }
+        false
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// framed box of the given inner size, centred in parent
+fn centered_frame(parent:&WindowHandle<Glyph>, id:&str, inner:Point2d, title:&str) -> Frame {
+    let size = inner + Point2d::new(2,2);
+    let offset = (parent.size() - size)/2;
+    let mut frame = Frame::new(parent, id, offset, size, title);
+    frame.set_border(DOUBLE_BORDER);
+    frame
+}
+
+// yes/no question, answered with y/n (or enter/escape); swallows all other input
+pub struct ConfirmDialog {
+    frame: Frame,
+    on_answer: Box<dyn FnMut(bool)>,
+}
+
+impl ConfirmDialog {
+    pub fn new(parent:&WindowHandle<Glyph>, title:&str, markup:&str, on_answer:Box<dyn FnMut(bool)>) -> Self {
+        let style = crate::widgets::Style::default();
+        let text = RichText::parse(markup, style.fg, style.bg);
+        let prompt = RichText::parse("[fg=gold]y[/fg]es / [fg=gold]n[/fg]o", style.fg, style.bg);
+        let w = text.width().max(prompt.width()).max(title.chars().count() as i32 + 4).min(parent.size().x - 4).max(1);
+        let lines = text.lines(w, None).len() as i32;
+        let frame = centered_frame(parent, "ConfirmDialog", Point2d::new(w + 2, lines + 2), title);
+        // (in a window of its own, so redrawing the frame's border leaves it be)
+        let inner = frame.inner();
+        let body = frame.window().new_child("Body".into(), inner.bl, inner - inner.bl);
+        body.fill(Glyph::new(' ', style.fg, style.bg));
+        text.draw(&body, Point2d::new(1,0), w, Align::Left, None);
+        prompt.draw(&body, Point2d::new(1, lines + 1), w, Align::Center, None);
+        ConfirmDialog { frame, on_answer }
+    }
+}
+
+impl Layer for ConfirmDialog {
+    fn window(&self) -> &WindowHandle<Glyph> {
+        self.frame.window()
+    }
+
+    fn handle(&mut self, ev:&InputEvent) -> Response {
+        let answer = match ev {
+            InputEvent::Key { key:KeyCode::Y, .. } | InputEvent::Key { key:KeyCode::Enter, .. } => true,
+            InputEvent::Key { key:KeyCode::N, .. } | InputEvent::Key { key:KeyCode::Escape, .. } => false,
+            // (closing & resizing aren't for a dialog to swallow)
+            InputEvent::Close | InputEvent::Resize(_) => { return Response::Pass; }
+            _ => { return Response::Consume; }
+        };
+        (self.on_answer)(answer);
+        Response::Close
+    }
+
+    fn focus(&mut self) {
+        self.frame.set_border(DOUBLE_BORDER);
+    }
+
+    fn blur(&mut self) {
+        self.frame.set_border(SINGLE_BORDER);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// pick one of a list (ie inventory), closing on a choice or escape
+pub struct MenuDialog {
+    frame: Frame,
+    menu: ListMenu,
+    on_choice: Box<dyn FnMut(usize)->Option<Box<dyn Layer>>>,
+    keymap: Rc<Keymap>,
+}
+
+impl MenuDialog {
+    // on_choice may open a follow-up layer (ie "really drop it?") over this one, or else it closes
+    pub fn new(parent:&WindowHandle<Glyph>, title:&str, items:Vec<String>,
+        on_choice:Box<dyn FnMut(usize)->Option<Box<dyn Layer>>>) -> Self
+    {
+        let w = items.iter().map(|s| s.chars().count() as i32 + 3).max().unwrap_or(0)
+            .max(title.chars().count() as i32 + 4).min(parent.size().x - 2).max(1);
+        let h = (items.len() as i32).min(parent.size().y - 2).max(1);
+        let frame = centered_frame(parent, "MenuDialog", Point2d::new(w, h), title);
+        let menu = ListMenu::new(frame.window(), "Menu", frame.inner().bl, Point2d::new(w, h), items, true);
+        MenuDialog { frame, menu, on_choice, keymap:Rc::new(Keymap::default()) }
+    }
+
+    // navigating by the keymap's menu bindings (rather than the defaults)
+    pub fn with_keymap(mut self, keymap:Rc<Keymap>) -> Self {
+        self.keymap = keymap;
+        self
+    }
+}
+
+impl Layer for MenuDialog {
+    fn window(&self) -> &WindowHandle<Glyph> {
+        self.frame.window()
+    }
+
+    fn handle(&mut self, ev:&InputEvent) -> Response {
+        let chord = match KeyChord::from_event(ev) {
+            Some(chord) => chord,
+            None if matches!(ev, InputEvent::Close | InputEvent::Resize(_)) => { return Response::Pass; }
+            None => { return Response::Consume; }
+        };
+        // (unbound keys may still be item hotkeys)
+        let res = match self.keymap.command(Context::Menu, chord) {
+            Some(cmd) => self.menu.handle_command(cmd),
+            None if !chord.ctrl && !chord.alt => self.menu.handle_key(chord.key),
+            None => MenuEvent::Ignored,
+        };
+        match res {
+            MenuEvent::Chosen(i) => {
+                match (self.on_choice)(i) {
+                    Some(next) => Response::Push(next),
+                    None => Response::Close,
+                }
+            }
+            MenuEvent::Cancelled => Response::Close,
+            _ => Response::Consume,
+        }
+    }
+
+    fn focus(&mut self) {
+        self.frame.set_border(DOUBLE_BORDER);
+    }
+
+    fn blur(&mut self) {
+        self.frame.set_border(SINGLE_BORDER);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// asks for a line of text (see LineEdit), closing on enter or escape
+pub struct PromptDialog {
+    frame: Frame,
+    edit: LineEdit,
+    on_submit: Box<dyn FnMut(String)>,
+}
+
+impl PromptDialog {
+    pub fn new(parent:&WindowHandle<Glyph>, title:&str, prompt:&str, width:i32, on_submit:Box<dyn FnMut(String)>) -> Self {
+        let w = width.max(title.chars().count() as i32 + 4).min(parent.size().x - 2).max(1);
+        let frame = centered_frame(parent, "PromptDialog", Point2d::new(w, 1), title);
+        let edit = LineEdit::new(frame.window(), "Edit", frame.inner().bl, w, prompt);
+        PromptDialog { frame, edit, on_submit }
+    }
+
+    // (ie shared with earlier prompts of the same kind)
+    pub fn with_history(mut self, history:History) -> Self {
+        self.edit.set_history(history);
+        self
+    }
+
+    pub fn with_completer(mut self, f:CompleteFn) -> Self {
+        self.edit.set_completer(f);
+        self
+    }
+}
+
+impl Layer for PromptDialog {
+    fn window(&self) -> &WindowHandle<Glyph> {
+        self.frame.window()
+    }
+
+    fn handle(&mut self, ev:&InputEvent) -> Response {
+        if let InputEvent::Close | InputEvent::Resize(_) = ev { return Response::Pass; }
+        match self.edit.handle(ev) {
+            EditEvent::Submitted(line) => {
+                (self.on_submit)(line);
+                Response::Close
+            }
+            EditEvent::Cancelled => Response::Close,
+            _ => Response::Consume,
+        }
+    }
+
+    fn focus(&mut self) {
+        self.frame.set_border(DOUBLE_BORDER);
+    }
+
+    fn blur(&mut self) {
+        self.frame.set_border(SINGLE_BORDER);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// lines of markup to read (ie the key bindings), scrolled by the keymap's menu
+// bindings, closing on select, cancel, space or q
+pub struct TextDialog {
+    frame: Frame,
+    body: WindowHandle<Glyph>,
+    lines: Vec<RichText>,
+    top: usize,
+    keymap: Rc<Keymap>,
+}
+
+impl TextDialog {
+    pub fn new(parent:&WindowHandle<Glyph>, title:&str, lines:&[String], keymap:Rc<Keymap>) -> Self {
+        let style = crate::widgets::Style::default();
+        let lines : Vec<RichText> = lines.iter().map(|l| RichText::parse(l, style.fg, style.bg)).collect();
+        let w = lines.iter().map(|l| l.width()).max().unwrap_or(0)
+            .max(title.chars().count() as i32 + 4).min(parent.size().x - 4).max(1);
+        let h = (lines.len() as i32).min(parent.size().y - 4).max(1);
+        let frame = centered_frame(parent, "TextDialog", Point2d::new(w + 2, h), title);
+        let inner = frame.inner();
+        let body = frame.window().new_child("Body".into(), inner.bl, inner - inner.bl);
+        let mut res = TextDialog { frame, body, lines, top:0, keymap };
+        res.render();
+        res
+    }
+
+    fn render(&mut self) {
+        let style = crate::widgets::Style::default();
+        let size = self.body.size();
+        let (w, h) = (size.x - 2, size.y.max(1) as usize);
+        self.top = self.top.min(self.lines.len().saturating_sub(h));
+        self.body.fill(Glyph::new(' ', style.fg, style.bg));
+        for (y, line) in self.lines.iter().skip(self.top).take(h).enumerate() {
+            line.draw(&self.body, Point2d::new(1, y as i32), w, Align::Left, Some(1));
+        }
+        if self.top > 0 {
+            self.body.set(Point2d::new(size.x-1, 0), Glyph::new('↑', style.dim_fg, style.bg));
+        }
+        if self.top + h < self.lines.len() {
+            self.body.set(Point2d::new(size.x-1, size.y-1), Glyph::new('↓', style.dim_fg, style.bg));
+        }
+    }
+}
+
+impl Layer for TextDialog {
+    fn window(&self) -> &WindowHandle<Glyph> {
+        self.frame.window()
+    }
+
+    fn handle(&mut self, ev:&InputEvent) -> Response {
+        let chord = match KeyChord::from_event(ev) {
+            Some(chord) => chord,
+            None if matches!(ev, InputEvent::Close | InputEvent::Resize(_)) => { return Response::Pass; }
+            None => { return Response::Consume; }
+        };
+        let page = self.body.size().y.max(1) as usize;
+        match (self.keymap.command(Context::Menu, chord), chord.key) {
+            (Some(Command::Select), _) | (Some(Command::Cancel), _) | (_, KeyCode::Space) | (_, KeyCode::Q) => {
+                return Response::Close;
+            }
+            (Some(Command::Up), _) => { self.top = self.top.saturating_sub(1); }
+            (Some(Command::Down), _) => { self.top += 1; }
+            (Some(Command::PageUp), _) => { self.top = self.top.saturating_sub(page); }
+            (Some(Command::PageDown), _) => { self.top += page; }
+            (Some(Command::First), _) => { self.top = 0; }
+            (Some(Command::Last), _) => { self.top = self.lines.len(); }
+            _ => { return Response::Consume; }
+        }
+        self.render();
+        Response::Consume
+    }
+
+    fn focus(&mut self) {
+        self.frame.set_border(DOUBLE_BORDER);
+    }
+
+    fn blur(&mut self) {
+        self.frame.set_border(SINGLE_BORDER);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell};
+    use std::rc::{Rc};
+    use crate::rect2d::*;
+    use crate::renderer::{HeadlessRenderer, Renderer};
+
+    #[test]
+    fn stack_focus_and_pausing() {
+        let mut r = HeadlessRenderer::new(Point2d::new(40,12));
+        let root = WindowHandle::new("Root".into(), Rect2d::new(Point2d::new(0,0), r.size()));
+        let mut time = TimeManager::new(60);
+        time.unpause();
+        let mut stack = LayerStack::new();
+        let answer = Rc::new(Cell::new(None));
+        let (a, parent) = (answer.clone(), root.clone());
+        let items = vec!["sword".to_string(), "rope".to_string()];
+        stack.push(Box::new(MenuDialog::new(&root, "Inventory", items, Box::new(move |i| {
+            if i == 0 { return None; }
+            let a = a.clone();
+            Some(Box::new(ConfirmDialog::new(&parent, "Drop", "Drop the rope?", Box::new(move |yes| a.set(Some(yes))))))
+        }))), &mut time);
+        assert!(time.paused());
+        // (a window opened later would cover the menu, but not a layer pushed after it)
+        let _hud = root.new_child("Hud".into(), Point2d::new(0,0), root.span());
+        let corner = |id:&str| {
+            let w = root.find_by_id(id).unwrap();
+            w.get(Point2d::new(0,0)).unwrap().ch
+        };
+        r.push_keys(&[KeyCode::Down, KeyCode::Enter, KeyCode::X]);
+        while let Some(ev) = r.poll_event() {
+            assert!(stack.handle(&ev, &mut time));
+        }
+        assert_eq!(stack.len(), 2);
+        assert_eq!((corner("MenuDialog"), corner("ConfirmDialog")), ('┌', '╔'));
+        r.draw_window(&root);
+        assert!(r.row_text(4).contains("║ Drop the rope? ║"));
+        assert!(r.row_text(6).contains("║    yes / no    ║"));
+        let top = root.select(Point2d::new(20,5)).unwrap().0;
+        assert!(top.ancestry().iter().any(|w| w.id() == "ConfirmDialog"));
+        // answering closes the confirmation, refocusing the menu beneath
+        assert!(stack.handle(&InputEvent::key(KeyCode::Y), &mut time));
+        assert_eq!(answer.get(), Some(true));
+        assert_eq!(stack.len(), 1);
+        assert!(root.find_by_id("ConfirmDialog").is_none());
+        assert_eq!(corner("MenuDialog"), '╔');
+        assert!(time.paused());
+        assert!(stack.handle(&InputEvent::key(KeyCode::Escape), &mut time));
+        assert!(stack.is_empty());
+        assert!(!time.paused());
+        // with nothing open, it's all for the game
+        assert!(!stack.handle(&InputEvent::key(KeyCode::Y), &mut time));
+        assert!(!stack.handle(&InputEvent::Close, &mut time));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
@@ -0,0 +1,501 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::core_systems::*;
+use crate::handle::{Handle};
+use crate::map_view::{MapViewWindow};
+use crate::paths::{BresenhamIterator, CircleMethod, circle_points};
+use crate::point2d::*;
+use crate::rect2d::*;
+use crate::renderer::{Renderer};
+use crate::time_manager::{TimeManager};
+use crate::window::{WindowHandle};
+
+////////////////////////////////////////////////////////////////////////////////
+
+// what an effect draws in one frame, in map coordinates
+#[derive(Clone,Debug,Default)]
+pub struct EffectFrame {
+    pub cells: Vec<(Point2d,Glyph)>,
+    // glyphs shifted off their cell (fractions of a cell, ie -1.0 .. 1.0 each way),
+    // where the renderer can draw them so
+    pub sprites: Vec<(Point2d,(f64,f64),Glyph)>,
+}
+
+impl EffectFrame {
+    pub fn put(&mut self, p:Point2d, g:Glyph) {
+        self.cells.push((p, g));
+    }
+
+    pub fn put_str(&mut self, p:Point2d, s:&str, fg:Color) {
+        for (i, ch) in s.chars().enumerate() {
+            self.put(p + Point2d::new(i as i32, 0), Glyph::new(ch, fg, Color::transparent()));
+        }
+    }
+
+    pub fn sprite(&mut self, p:Point2d, shift:(f64,f64), g:Glyph) {
+        self.sprites.push((p, shift, g));
+    }
+}
+
+// something drawn over the map for a number of (unpaused) frames
+pub trait Effect {
+    fn duration(&self) -> usize;
+
+    // draws frame t (0 .. duration)
+    fn draw(&self, t:usize, out:&mut EffectFrame);
+}
+
+// glyph at strength w: as it is at 1, fading out to nothing at 0
+fn faded(g:Glyph, w:f64) -> Glyph {
+    Glyph::new(g.ch, g.fg.lint(&g.fg.with_alpha(0), w), g.bg.lint(&g.bg.with_alpha(0), w))
+}
+
+////////////////////////////////////////
+
+// a glyph flying along a line, leaving a fading trail
+pub struct Projectile {
+    path: Vec<Point2d>,     // (not including the start)
+    glyph: Glyph,
+    trail: char,
+    tail: usize,            // cells of trail
+    speed: usize,           // frames per cell
+}
+
+impl Projectile {
+    pub fn new(from:Point2d, to:Point2d, glyph:Glyph) -> Self {
+        let path = BresenhamIterator::new(from, to).skip(1).collect();
+        Projectile { path, glyph, trail:'·', tail:3, speed:2 }
+    }
+
+    pub fn with_trail(mut self, trail:char, tail:usize) -> Self {
+        self.trail = trail;
+        self.tail = tail;
+        self
+    }
+
+    pub fn with_speed(mut self, frames_per_cell:usize) -> Self {
+        self.speed = frames_per_cell.max(1);
+        self
+    }
+
+    // frames until it lands
+    pub fn flight(&self) -> usize {
+        self.path.len().saturating_sub(1) * self.speed + 1
+    }
+}
+
+impl Effect for Projectile {
+    // (the trail catches up after it lands)
+    fn duration(&self) -> usize {
+        if self.path.is_empty() { 0 } else { self.flight() + self.tail * self.speed }
+    }
+
+    fn draw(&self, t:usize, out:&mut EffectFrame) {
+        let n = self.path.len();
+        let i = t / self.speed;
+        let trail = Glyph::new(self.trail, self.glyph.fg, Color::transparent());
+        for k in 1 ..= self.tail {
+            if k <= i && i - k < n {
+                out.put(self.path[i - k], faded(trail, 1.0 - k as f64 / (self.tail + 1) as f64));
+            }
+        }
+        if i < n {
+            let f = (t % self.speed) as f64 / self.speed as f64;
+            let d = if i + 1 < n { self.path[i + 1] - self.path[i] } else { Point2d::new(0,0) };
+            out.sprite(self.path[i], (d.x as f64 * f, d.y as f64 * f), self.glyph);
+        }
+    }
+}
+
+// rings spreading out from a point, cooling as they go
+pub struct Explosion {
+    center: Point2d,
+    radius: u32,
+    hot: Color,
+    cold: Color,
+    step: usize,            // frames per ring
+}
+
+impl Explosion {
+    pub fn new(center:Point2d, radius:u32) -> Self {
+        Explosion { center, radius, hot:Color::vanilla(), cold:Color::blood(), step:3 }
+    }
+
+    pub fn with_colors(mut self, hot:Color, cold:Color) -> Self {
+        self.hot = hot;
+        self.cold = cold;
+        self
+    }
+}
+
+impl Effect for Explosion {
+    fn duration(&self) -> usize {
+        (self.radius as usize + 2) * self.step
+    }
+
+    // the ring at r, the one inside it dimmer
+    fn draw(&self, t:usize, out:&mut EffectFrame) {
+        let r = (t / self.step) as u32;
+        let mut points = Vec::new();
+        for (ring, w) in [(r, 1.0), (r.wrapping_sub(1), 0.5)] {
+            if ring > self.radius { continue; }
+            let c = self.hot.lint(&self.cold, 1.0 - ring as f64 / (self.radius as f64 + 1.0));
+            circle_points(self.center, ring, &mut points, CircleMethod::Kennedy);
+            // (octants meet, and a ring of 0 is all the centre)
+            points.sort();
+            points.dedup();
+            for p in points.iter() {
+                out.put(*p, faded(Glyph::new('*', c, c.scale(0.5)), w));
+            }
+        }
+    }
+}
+
+// a glyph blinking on & off
+pub struct Flash {
+    p: Point2d,
+    glyph: Glyph,
+    times: usize,
+    period: usize,          // frames on (then off)
+}
+
+impl Flash {
+    pub fn new(p:Point2d, glyph:Glyph, times:usize) -> Self {
+        Flash { p, glyph, times, period:4 }
+    }
+}
+
+impl Effect for Flash {
+    fn duration(&self) -> usize {
+        self.times * self.period * 2
+    }
+
+    fn draw(&self, t:usize, out:&mut EffectFrame) {
+        if (t / self.period).is_multiple_of(2) { out.put(self.p, self.glyph); }
+    }
+}
+
+// a glyph shading from its colours to another (ie a hit flashing red, then back)
+pub struct Fade {
+    p: Point2d,
+    glyph: Glyph,
+    to: Color,
+    frames: usize,
+}
+
+impl Fade {
+    pub fn new(p:Point2d, glyph:Glyph, to:Color, frames:usize) -> Self {
+        Fade { p, glyph, to, frames }
+    }
+}
+
+impl Effect for Fade {
+    fn duration(&self) -> usize {
+        self.frames
+    }
+
+    fn draw(&self, t:usize, out:&mut EffectFrame) {
+        let w = 1.0 - t as f64 / self.frames.max(1) as f64;
+        let g = self.glyph;
+        out.put(self.p, Glyph::new(g.ch, g.fg.lint(&self.to, w), g.bg.lint(&self.to, w)));
+    }
+}
+
+// text drifting up from a point & fading (ie damage numbers), centred on it
+pub struct FloatingText {
+    p: Point2d,
+    text: String,
+    color: Color,
+    rise: i32,              // cells
+    step: usize,            // frames per cell
+}
+
+impl FloatingText {
+    pub fn new(p:Point2d, text:&str, color:Color) -> Self {
+        FloatingText { p, text:text.into(), color, rise:2, step:8 }
+    }
+
+    pub fn damage(p:Point2d, amount:i32) -> Self {
+        FloatingText::new(p, &format!("-{}", amount), Color::maximum_red())
+    }
+}
+
+impl Effect for FloatingText {
+    fn duration(&self) -> usize {
+        (self.rise as usize + 1) * self.step
+    }
+
+    // (map y grows downward, as on screen)
+    fn draw(&self, t:usize, out:&mut EffectFrame) {
+        let up = (t / self.step) as i32;
+        let w = 1.0 - t as f64 / self.duration() as f64;
+        let at = self.p - Point2d::new(self.text.chars().count() as i32 / 2, up);
+        out.put_str(at, &self.text, self.color.lint(&self.color.with_alpha(0), 0.25 + 0.75*w));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct Playing {
+    effect: Box<dyn Effect>,
+    start: usize,           // frame
+    blocking: bool,
+}
+
+// plays effects over a map view, keyed to TimeManager frames (so they hold
+// still while paused): cells go in a transparent overlay on the view, leaving
+// the map alone, & sprites in windows of their own, shifted by pixel offsets
+// where the renderer has layers; blocking effects hold up the game's turn
+pub struct Effects<C:Clone> {
+    view: Handle<MapViewWindow<C>>,
+    overlay: WindowHandle<Glyph>,
+    sprites: Vec<WindowHandle<Glyph>>,  // (over the root, hidden when unused)
+    playing: Vec<Playing>,
+    frame: usize,
+    drawn: Vec<Point2d>,                // overlay cells drawn last frame
+}
+
+impl<C:Clone> Effects<C> {
+    pub fn new(view:&Handle<MapViewWindow<C>>) -> Self {
+        let window = view.borrow().window().clone();
+        let overlay = window.new_child("Effects".into(), Point2d::new(0,0), window.span());
+        overlay.set_default(Glyph::transparent());
+        overlay.fill(Glyph::transparent());
+        overlay.set_blend(BlendMode::Normal);
+        Effects { view:view.clone(), overlay, sprites:Vec::new(), playing:Vec::new(), frame:0, drawn:Vec::new() }
+    }
+
+    pub fn play(&mut self, effect:Box<dyn Effect>) {
+        self.play_after(0, effect, false);
+    }
+
+    // played through before the game goes on (see blocking())
+    pub fn play_blocking(&mut self, effect:Box<dyn Effect>) {
+        self.play_after(0, effect, true);
+    }
+
+    // starting some frames from now (ie an explosion once its projectile lands)
+    pub fn play_after(&mut self, delay:usize, effect:Box<dyn Effect>, blocking:bool) {
+        self.playing.push(Playing { effect, start:self.frame + delay, blocking });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.playing.is_empty()
+    }
+
+    // whether the turn should wait
+    pub fn blocking(&self) -> bool {
+        self.playing.iter().any(|p| p.blocking)
+    }
+
+    // draws the given frame (TimeManager::frame_count()) of whatever's playing,
+    // dropping what's done; cell_pixels as Renderer::cell_pixels()
+    pub fn update(&mut self, frame:usize, cell_pixels:Point2d) {
+        self.frame = frame;
+        self.playing.retain(|p| frame < p.start + p.effect.duration());
+        let mut out = EffectFrame::default();
+        for p in self.playing.iter().filter(|p| frame >= p.start) {
+            p.effect.draw(frame - p.start, &mut out);
+        }
+        let window = self.view.borrow().window().clone();
+        if self.overlay.size() != window.size() {
+            self.overlay.set_size(window.size());
+            self.overlay.fill(Glyph::transparent());
+            self.drawn.clear();
+        }
+        let view = self.view.borrow().view_rect();
+        for lp in self.drawn.drain(..) {
+            self.overlay.set(lp, Glyph::transparent());
+        }
+        for (mp, g) in out.cells {
+            if self.overlay.set(mp - view.bl, g) { self.drawn.push(mp - view.bl); }
+        }
+        self.place_sprites(&out.sprites, cell_pixels);
+    }
+
+    fn place_sprites(&mut self, sprites:&[(Point2d,(f64,f64),Glyph)], cell_pixels:Point2d) {
+        let root = self.overlay.root();
+        while self.sprites.len() < sprites.len() {
+            let w = root.new_child(format!("Sprite[{}]", self.sprites.len()), Point2d::new(0,0),
+                Rect2d::new(Point2d::new(0,0), Point2d::new(1,1)));
+            w.set_default(Glyph::transparent());
+            w.set_blend(BlendMode::Normal);
+            self.sprites.push(w);
+        }
+        for (i, w) in self.sprites.iter().enumerate() {
+            let at = sprites.get(i).and_then(|(mp, shift, g)| Some((self.view.borrow().map_to_screen(*mp)?, *shift, *g)));
+            match at {
+                Some((sp, (dx, dy), g)) => {
+                    w.set(Point2d::new(0,0), g);
+                    w.set_offset(sp);
+                    w.set_pixel_offset(Point2d::new((dx * cell_pixels.x as f64).round() as i32,
+                        (dy * cell_pixels.y as f64).round() as i32));
+                    w.set_hidden(false);
+                }
+                None => { w.set_hidden(true); }
+            }
+        }
+    }
+
+    // runs frames until nothing blocking is left, so the turn can go on
+    // (input waits meanwhile); returns at once if the game is paused
+    pub fn play_out(&mut self, renderer:&mut dyn Renderer, time:&mut TimeManager) {
+        let root = self.overlay.root();
+        while self.blocking() && !time.paused() {
+            time.tick();
+            self.update(time.frame_count(), renderer.cell_pixels());
+            renderer.draw_window(&root);
+            renderer.set_frame_time(time.since_start());
+            renderer.refresh();
+        }
+    }
+}
+
+impl<C:Clone> Drop for Effects<C> {
+    fn drop(&mut self) {
+        self.overlay.demolish();
+        self.sprites.iter().for_each(|w| w.demolish());
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{RefCell};
+    use std::rc::{Rc};
+    use crate::entity::{EntityManagerHandle};
+    use crate::map::{Map};
+    use crate::mouse::{MouseButton, MouseEvent, MouseEventKind, MouseInput, MouseRouter};
+    use crate::renderer::{HeadlessRenderer};
+
+    #[test]
+    fn blocking_effects_play_out() {
+        let ids = EntityManagerHandle::new();
+        let player = ids.new_id();
+        let span = Rect2d::new(Point2d::new(0,0), Point2d::new(12,6));
+        let mut map = Map::new("test", span, 0u8);
+        map.set_entity_position(player, Point2d::new(1,2));
+        let map = Rc::new(RefCell::new(map));
+        let root = WindowHandle::new("Root".into(), span);
+        let view = Handle::new_from(MapViewWindow::new(&root, Point2d::new(0,0), span.size(),
+            map.clone(), player, Box::new(|_| Glyph::new('.', Color::grey(), Color::black()))));
+        let mut effects = Effects::new(&view);
+        let mut r = HeadlessRenderer::new(span.size());
+        let mut time = TimeManager::new(1000);
+        time.unpause();
+
+        let bolt = Projectile::new(Point2d::new(1,2), Point2d::new(9,2), Glyph::new('*', Color::gold(), Color::transparent()));
+        let landing = bolt.flight();
+        assert_eq!(landing, 15);
+        effects.play_blocking(Box::new(bolt));
+        effects.play_after(landing, Box::new(FloatingText::damage(Point2d::new(9,2), 7)), false);
+        effects.update(time.frame_count(), r.cell_pixels());
+        r.draw_window(&root);
+        // the bolt is a sprite, in a window of its own
+        assert_eq!(r.row_text(2), ".@*.........");
+        effects.play_out(&mut r, &mut time);
+        assert!(!effects.blocking() && !effects.is_empty());
+        // (landed & its trail gone, while the number floats up from where it hit)
+        assert_eq!(r.frames().len(), 21);
+        assert_eq!(r.row_text(2), ".@......-7..");
+        effects.update(time.frame_count() + 5, r.cell_pixels());
+        r.draw_window(&root);
+        assert_eq!(r.row_text(1), "........-7..");
+        assert_eq!(r.row_text(2), ".@..........");
+        effects.update(time.frame_count() + 100, r.cell_pixels());
+        assert!(effects.is_empty());
+        r.draw_window(&root);
+        assert_eq!(r.row_text(1), "............");
+        // the map itself was never touched
+        assert!(map.borrow().span().into_iter().all(|p| map.borrow().cell(p) == Some(&0)));
+    }
+
+    // as in main: a right click on the view (under the effects' overlay) fires a bolt
+    #[test]
+    fn clicked_bolt() {
+        let ids = EntityManagerHandle::new();
+        let player = ids.new_id();
+        let span = Rect2d::new(Point2d::new(0,0), Point2d::new(12,6));
+        let mut map = Map::new("test", span, 0u8);
+        map.set_entity_position(player, Point2d::new(1,2));
+        let root = WindowHandle::new("Root".into(), span);
+        let view = Handle::new_from(MapViewWindow::new(&root, Point2d::new(0,0), span.size(),
+            Rc::new(RefCell::new(map)), player, Box::new(|_| Glyph::new('.', Color::grey(), Color::black()))));
+        let effects = Handle::new_from(Effects::new(&view));
+        let mut router : MouseRouter<Glyph> = MouseRouter::new();
+        {
+            let (v, effects) = (view.clone(), effects.clone());
+            router.set_handler(view.borrow().window(), Box::new(move |ev:&MouseEvent| {
+                if ev.kind != MouseEventKind::Click(MouseButton::Right, 1) { return false; }
+                let view = v.borrow();
+                let (from, to) = (view.map().borrow().entity_position(view.followed()).unwrap(), view.local_to_map(ev.pos));
+                let bolt = Projectile::new(from, to, Glyph::new('*', Color::saffron(), Color::transparent()));
+                let landing = bolt.flight();
+                let mut effects = effects.borrow_mut();
+                effects.play_blocking(Box::new(bolt));
+                effects.play_after(landing, Box::new(Explosion::new(to, 1)), true);
+                true
+            }));
+        }
+        let at = view.borrow().map_to_screen(Point2d::new(7,2)).unwrap();
+        router.route(&root, MouseInput::Press(MouseButton::Right, at, 1));
+        assert!(router.route(&root, MouseInput::Release(MouseButton::Right, at)));
+        assert!(effects.borrow().blocking());
+        let mut r = HeadlessRenderer::new(span.size());
+        let mut time = TimeManager::new(1000);
+        time.unpause();
+        effects.borrow_mut().play_out(&mut r, &mut time);
+        assert!(effects.borrow().is_empty());
+        assert!(r.frames().iter().any(|f| f.get(at).unwrap().ch == '*' && f.get(at).unwrap().bg != Color::black()));
+    }
+
+    fn frame(e:&dyn Effect, t:usize) -> Vec<(Point2d,Glyph)> {
+        let mut out = EffectFrame::default();
+        e.draw(t, &mut out);
+        out.cells
+    }
+
+    #[test]
+    fn fading_explosions_flashes() {
+        let g = Glyph::new('*', Color::gold(), Color::blood());
+        assert_eq!(faded(g, 1.0), g);
+        let half = faded(g, 0.5);
+        assert_eq!((half.fg.with_alpha(255), half.fg.a(), half.bg.with_alpha(255), half.bg.a()),
+            (Color::gold(), 128, Color::blood(), 128));
+        assert_eq!((faded(g, 0.0).fg.a(), faded(g, 0.0).bg.a()), (0, 0));
+
+        // rings out to the radius, hot at the centre, each followed by a dimmer one
+        let c = Point2d::new(5,5);
+        let boom = Explosion::new(c, 2).with_colors(Color::white(), Color::black());
+        assert_eq!(boom.duration(), 12);
+        assert_eq!(frame(&boom, 0), vec![(c, Glyph::new('*', Color::white(), Color::white().scale(0.5)))]);
+        let f = frame(&boom, 3);
+        let (outer, inner) : (Vec<_>, Vec<_>) = f.iter().partition(|(p, _)| *p != c);
+        assert_eq!(inner.len(), 1);
+        assert!(!outer.is_empty() && outer.iter().all(|(p, _)| p.chebyshev(c) == 1));
+        assert!(outer.iter().all(|(_, g)| g.fg.a() == 255 && g.fg.r() < 255));
+        assert_eq!(inner[0].1.fg.a(), 128);
+        assert!(frame(&boom, 9).iter().all(|(p, g)| p.chebyshev(c) == 2 && g.fg.a() == 128));
+        assert!(frame(&boom, 11).iter().all(|(_, g)| g.fg.r() > 0));
+
+        let flash = Flash::new(c, g, 2);
+        assert_eq!(flash.duration(), 16);
+        let on : Vec<bool> = (0 .. 16).map(|t| frame(&flash, t) == vec![(c, g)]).collect();
+        assert_eq!(on.iter().filter(|x| **x).count(), 8);
+        assert!(on[0] && on[3] && !on[4] && !on[7] && on[8] && !on[15]);
+        assert!(frame(&flash, 5).is_empty());
+
+        // from its own colours most of the way to the other's
+        let fade = Fade::new(c, g, Color::white(), 4);
+        assert_eq!(fade.duration(), 4);
+        assert_eq!(frame(&fade, 0), vec![(c, g)]);
+        let (_, g2) = frame(&fade, 2)[0];
+        assert_eq!((g2.ch, g2.fg), ('*', Color::gold().lint(&Color::white(), 0.5)));
+        let (_, g3) = frame(&fade, 3)[0];
+        assert_eq!(g3.bg, Color::blood().lint(&Color::white(), 0.25));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
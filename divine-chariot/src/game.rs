@@ -0,0 +1,201 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use std::cell::{RefCell};
+use std::rc::{Rc};
+
+use crate::entity::{EntityId};
+use crate::hash::{Hasher32, XXHash};
+use crate::map::{Map};
+use crate::point2d::*;
+use crate::replay::{CommandLog, GameCommand, Simulation};
+use crate::rng::{Rng};
+
+////////////////////////////////////////////////////////////////////////////////
+
+// whether a cell can be walked into
+pub type PassableFn<C> = Box<dyn Fn(&C)->bool>;
+
+// the game state commands act on: the player, what they carry, & wandering
+// entities, moved by the Rng after each turn the player takes; every accepted
+// command is logged (with the starting seed) for replay
+pub struct Game<C:Clone> {
+    map: Rc<RefCell<Map<C>>>,
+    player: EntityId,
+    inventory: Vec<EntityId>,
+    wanderers: Vec<EntityId>,
+    rng: Rng,
+    turn: u64,
+    passable: PassableFn<C>,
+    log: CommandLog,
+}
+
+impl<C:Clone> Game<C> {
+    pub fn new(map:Rc<RefCell<Map<C>>>, player:EntityId, seed:[u32; 4], passable:PassableFn<C>) -> Self {
+        Game { map, player, inventory:Vec::new(), wanderers:Vec::new(), rng:Rng::from_seed(seed), turn:0,
+            passable, log:CommandLog::new(seed) }
+    }
+
+    pub fn map(&self) -> &Rc<RefCell<Map<C>>> {
+        &self.map
+    }
+
+    pub fn player(&self) -> EntityId {
+        self.player
+    }
+
+    pub fn inventory(&self) -> &[EntityId] {
+        &self.inventory
+    }
+
+    pub fn log(&self) -> &CommandLog {
+        &self.log
+    }
+
+    // log with the current state hash as its final one
+    pub fn finished_log(&self) -> CommandLog {
+        let mut log = self.log.clone();
+        log.finish(self);
+        log
+    }
+
+    pub fn add_wanderer(&mut self, e:EntityId) {
+        if !self.wanderers.contains(&e) { self.wanderers.push(e); }
+    }
+
+    // a player command (logged if accepted)
+    pub fn execute(&mut self, cmd:GameCommand) -> bool {
+        let turn = self.turn;
+        if !self.apply(cmd) { return false; }
+        self.log.push(turn, cmd);
+        true
+    }
+
+    fn passable(&self, p:Point2d) -> bool {
+        self.map.borrow().cell(p).is_some_and(|c| (self.passable)(c))
+    }
+
+    // each wanderer steps a random way, or stays put (1 in 9)
+    fn end_turn(&mut self) {
+        for i in 0 .. self.wanderers.len() {
+            let e = self.wanderers[i];
+            let roll = (self.rng.next_u32() % 9) as usize;
+            let p = match (self.map.borrow().entity_position(e), Dir::all().nth(roll)) {
+                (Some(p), Some(d)) => p + d,
+                _ => { continue; }
+            };
+            if self.passable(p) { self.map.borrow_mut().set_entity_position(e, p); }
+        }
+        self.turn += 1;
+    }
+}
+
+impl<C:Clone> Simulation for Game<C> {
+    fn turn(&self) -> u64 {
+        self.turn
+    }
+
+    fn apply(&mut self, cmd:GameCommand) -> bool {
+        let at = match self.map.borrow().entity_position(self.player) { Some(p) => p, None => { return false; } };
+        match cmd {
+            GameCommand::Move(d) => {
+                if !self.passable(at + d) { return false; }
+                self.map.borrow_mut().set_entity_position(self.player, at + d);
+            }
+            GameCommand::Wait => {}
+            GameCommand::Pickup(e) => {
+                let here = self.map.borrow().entities_at(at).is_some_and(|es| es.contains(&e));
+                if e == self.player || !here { return false; }
+                self.map.borrow_mut().remove_entity(e);
+                self.wanderers.retain(|w| *w != e);
+                self.inventory.push(e);
+            }
+            GameCommand::Drop(e) => {
+                let i = match self.inventory.iter().position(|x| *x == e) { Some(i) => i, None => { return false; } };
+                self.inventory.remove(i);
+                self.map.borrow_mut().set_entity_position(e, at);
+            }
+        }
+        self.end_turn();
+        true
+    }
+
+    // turn, rng state, entity positions (by id) & inventory
+    fn state_hash(&self) -> u32 {
+        let mut data : Vec<u32> = vec![self.turn as u32, (self.turn >> 32) as u32];
+        data.extend(self.rng.seed());
+        let map = self.map.borrow();
+        let mut es : Vec<(EntityId,Point2d)> = map.entities_iter().map(|(e, p)| (*e, *p)).collect();
+        es.sort();
+        for (e, p) in es {
+            data.extend([usize::from(e) as u32, p.x as u32, p.y as u32]);
+        }
+        data.push(self.inventory.len() as u32);
+        data.extend(self.inventory.iter().map(|e| usize::from(*e) as u32));
+        XXHash::new(0).hash32(&data[..])
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::{EntityManagerHandle};
+    use crate::rect2d::*;
+    use crate::replay::{ReplayError};
+
+    // open floor (0) walled in (1), with the player, a crate & two wandering drones
+    fn new_game(seed:[u32; 4]) -> Game<u8> {
+        let ids = EntityManagerHandle::new();
+        let (player, crate_id, d1, d2) = (ids.new_id(), ids.new_id(), ids.new_id(), ids.new_id());
+        let span = Rect2d::new(Point2d::new(0,0), Point2d::new(20,12));
+        let mut map = Map::new("test", span, 0u8);
+        for p in span {
+            if p.x == 0 || p.y == 0 || p.x == 19 || p.y == 11 { map.set_cell(p, 1); }
+        }
+        map.set_entity_position(player, Point2d::new(3,3));
+        map.set_entity_position(crate_id, Point2d::new(5,3));
+        map.set_entity_position(d1, Point2d::new(10,6));
+        map.set_entity_position(d2, Point2d::new(15,8));
+        let mut game = Game::new(Rc::new(RefCell::new(map)), player, seed, Box::new(|c| *c == 0));
+        game.add_wanderer(d1);
+        game.add_wanderer(d2);
+        game
+    }
+
+    #[test]
+    fn verify_replay() {
+        let seed = [0x193a6754, 0xa8a7d469, 0x97830e05, 0x113ba7bb];
+        let mut game = new_game(seed);
+        let crate_id = game.map().borrow().entities_at(Point2d::new(5,3)).unwrap()[0];
+        assert!(!game.execute(GameCommand::Pickup(crate_id)));
+        for cmd in [GameCommand::Move(Dir::E), GameCommand::Move(Dir::E), GameCommand::Pickup(crate_id),
+            GameCommand::Move(Dir::N), GameCommand::Move(Dir::N), GameCommand::Move(Dir::N), GameCommand::Wait,
+            GameCommand::Move(Dir::SE), GameCommand::Drop(crate_id)]
+        {
+            game.execute(cmd);
+        }
+        // (walking into the wall was refused, & not logged)
+        assert_eq!(game.turn(), 8);
+        assert_eq!(game.log().commands.len(), 8);
+        assert_eq!(game.log().commands[3].turn, 3);
+        assert_eq!(game.map().borrow().entity_position(crate_id), Some(Point2d::new(6,2)));
+        let log = game.finished_log();
+        let bytes = serde_cbor::to_vec(&log).unwrap();
+        let log : CommandLog = serde_cbor::from_slice(&bytes).unwrap();
+        // replayed from the seed, it ends the same
+        let mut replayed = new_game(log.seed);
+        assert_eq!(log.verify(&mut replayed), Ok(game.state_hash()));
+        assert!(replayed.log().commands.is_empty());
+        // from another seed the drones wander elsewhere
+        let mut other = new_game([1, 2, 3, 4]);
+        assert!(matches!(log.verify(&mut other), Err(ReplayError::HashMismatch { .. })));
+        // & a log missing a turn falls out of step
+        let mut skipped = log.clone();
+        skipped.commands.remove(2);
+        assert_eq!(skipped.verify(&mut new_game(log.seed)),
+            Err(ReplayError::OutOfStep { index:2, logged:3, actual:2 }));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
@@ -5,7 +5,8 @@ use crate::rect2d::*;
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Clone,Debug,serde::Serialize,serde::Deserialize)]
+// (Deserialize is implemented in grid_serde, to also accept the compact forms)
+#[derive(Clone,Debug,serde::Serialize)]
 pub struct Grid<T:Clone> {
     cells: Vec<T>,
     span: Rect2d,
@@ -19,6 +20,20 @@ impl<T:Clone> Grid<T> {
         Grid { cells, span }
     }
 
+    // fails unless there is exactly one cell per point of span
+    pub fn from_cells(span:Rect2d, cells:Vec<T>) -> Result<Self,String> {
+        let count = span.area().max(0) as usize;
+        if cells.len() != count {
+            return Err(format!("grid {} needs {} cells, got {}", span, count, cells.len()));
+        }
+        Ok(Grid { cells, span })
+    }
+
+    // cells in span (row-major) order
+    pub fn cells(&self) -> &[T] {
+        &self.cells
+    }
+
     pub fn get(&self, p:Point2d) -> Option<&T> {
         if let Some(i) = self.span.index(p) {
             Some(&self.cells[i])
@@ -59,4 +74,259 @@ impl<T:Clone+Copy> Grid<T> {
     }
 }
 
+////////////////////////////////////////
+
+// which edge of the old contents stays put (along one axis) when resizing
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Anchor {
+    Min,
+    Center,
+    Max,
+}
+
+impl Anchor {
+    fn shift(&self, old:i32, new:i32) -> i32 {
+        match self {
+            Anchor::Min => 0,
+            Anchor::Center => (new - old)/2,
+            Anchor::Max => new - old,
+        }
+    }
+}
+
+impl<T:Clone> Grid<T> {
+    pub fn iter(&self) -> impl Iterator<Item=(Point2d,&T)> {
+        self.span.iter().zip(self.cells.iter())
+    }
+
+    pub fn fill(&mut self, t:T) {
+        self.cells.iter_mut().for_each(|c| *c = t.clone());
+    }
+
+    pub fn fill_rect(&mut self, rect:Rect2d, t:T) {
+        self.view_mut(rect).fill(t);
+    }
+
+    // copies src cells within src_rect so that src_rect.bl lands on dst,
+    // clipping against both grids; returns number of cells copied
+    pub fn blit(&mut self, src:&Grid<T>, src_rect:Rect2d, dst:Point2d) -> usize {
+        let (dst_rect, delta) = match self.blit_rect(src.span, src_rect, dst) {
+            Some(x) => x,
+            None => { return 0; }
+        };
+        // row-at-a-time, as this is on the per-frame path
+        let w = dst_rect.size().x as usize;
+        for y in dst_rect.bl.y .. dst_rect.tr.y {
+            let dp = Point2d::new(dst_rect.bl.x, y);
+            let di = self.span.index(dp).unwrap();
+            let si = src.span.index(dp - delta).unwrap();
+            self.cells[di .. di+w].clone_from_slice(&src.cells[si .. si+w]);
+        }
+        dst_rect.area() as usize
+    }
+
+    // as blit, but combines each src cell into the existing dst cell via f
+    pub fn blit_with<U:Clone, F:FnMut(&mut T,&U)>(
+        &mut self, src:&Grid<U>, src_rect:Rect2d, dst:Point2d, f:&mut F) -> usize
+    {
+        let (dst_rect, delta) = match self.blit_rect(src.span, src_rect, dst) {
+            Some(x) => x,
+            None => { return 0; }
+        };
+        for p in dst_rect {
+            let i = self.span.index(p).unwrap();
+            f(&mut self.cells[i], src.get(p - delta).unwrap());
+        }
+        dst_rect.area() as usize
+    }
+
+    // clipped destination rect & (dst - src) translation for a blit
+    fn blit_rect(&self, src_span:Rect2d, src_rect:Rect2d, dst:Point2d) -> Option<(Rect2d,Point2d)> {
+        let delta = dst - src_rect.bl;
+        let src_rect = src_rect.intersection(&src_span)?;
+        let dst_rect = (src_rect + delta).intersection(&self.span)?;
+        Some((dst_rect, delta))
+    }
+
+    pub fn map<U:Clone, F:FnMut(&T)->U>(&self, f:F) -> Grid<U> {
+        let cells = self.cells.iter().map(f).collect();
+        Grid { cells, span:self.span }
+    }
+
+    // combines cells over the intersection of both spans (None if disjoint)
+    pub fn zip_with<U:Clone, V:Clone, F:FnMut(&T,&U)->V>(&self, other:&Grid<U>, mut f:F) -> Option<Grid<V>> {
+        let span = self.span.intersection(&other.span)?;
+        let cells = span.iter().map(|p| f(self.get(p).unwrap(), other.get(p).unwrap())).collect();
+        Some(Grid { cells, span })
+    }
+
+    // new grid of given size with the same bl, old contents positioned by anchors
+    // (which edge, per axis, stays put), new cells set to fill
+    pub fn resized(&self, size:Point2d, ax:Anchor, ay:Anchor, fill:T) -> Grid<T> {
+        let old = self.span.size();
+        let span = Rect2d::new(self.span.bl, self.span.bl + size);
+        let shift = Point2d::new(ax.shift(old.x, size.x), ay.shift(old.y, size.y));
+        let mut res = Grid::new(span, fill);
+        res.blit(self, self.span, self.span.bl + shift);
+        res
+    }
+
+    pub fn resize(&mut self, size:Point2d, ax:Anchor, ay:Anchor, fill:T) {
+        *self = self.resized(size, ax, ay, fill);
+    }
+
+    // read-only view of the part of the grid within rect
+    // (same coordinates as the grid, clipped to its span)
+    pub fn view(&self, rect:Rect2d) -> GridView<'_,T> {
+        let span = rect.intersection(&self.span).unwrap_or_default();
+        GridView { grid:self, span }
+    }
+
+    pub fn view_mut(&mut self, rect:Rect2d) -> GridViewMut<'_,T> {
+        let span = rect.intersection(&self.span).unwrap_or_default();
+        GridViewMut { grid:self, span }
+    }
+}
+
+////////////////////////////////////////
+
+pub struct GridView<'a,T:Clone> {
+    grid: &'a Grid<T>,
+    span: Rect2d,
+}
+
+impl<'a,T:Clone> GridView<'a,T> {
+    pub fn span(&self) -> Rect2d {
+        self.span
+    }
+
+    pub fn contains(&self, p:Point2d) -> bool {
+        self.span.contains(p)
+    }
+
+    pub fn get(&self, p:Point2d) -> Option<&'a T> {
+        if self.span.contains(p) { self.grid.get(p) } else { None }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(Point2d,&'a T)> + '_ {
+        self.span.iter().map(move|p| (p, self.grid.get(p).unwrap()))
+    }
+
+    // copies the view out into its own grid (with the view's span)
+    pub fn to_grid(&self) -> Grid<T> {
+        let cells = self.span.iter().map(|p| self.grid.get(p).unwrap().clone()).collect();
+        Grid { cells, span:self.span }
+    }
+}
+
+pub struct GridViewMut<'a,T:Clone> {
+    grid: &'a mut Grid<T>,
+    span: Rect2d,
+}
+
+impl<'a,T:Clone> GridViewMut<'a,T> {
+    pub fn span(&self) -> Rect2d {
+        self.span
+    }
+
+    pub fn contains(&self, p:Point2d) -> bool {
+        self.span.contains(p)
+    }
+
+    pub fn get(&self, p:Point2d) -> Option<&T> {
+        if self.span.contains(p) { self.grid.get(p) } else { None }
+    }
+
+    pub fn get_mut(&mut self, p:Point2d) -> Option<&mut T> {
+        if self.span.contains(p) { self.grid.get_mut(p) } else { None }
+    }
+
+    pub fn set(&mut self, p:Point2d, t:T) -> bool {
+        self.span.contains(p) && self.grid.set(p, t)
+    }
+
+    pub fn fill(&mut self, t:T) {
+        for p in self.span {
+            self.grid.set(p, t.clone());
+        }
+    }
+
+    pub fn for_each_mut<F:FnMut(Point2d,&mut T)>(&mut self, mut f:F) {
+        for p in self.span {
+            f(p, self.grid.get_mut(p).unwrap());
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x0:i32, y0:i32, x1:i32, y1:i32) -> Rect2d {
+        Rect2d::new(Point2d::new(x0,y0), Point2d::new(x1,y1))
+    }
+
+    fn grid_of(rows:&[&str]) -> Grid<char> {
+        let cells = rows.iter().flat_map(|r| r.chars()).collect();
+        Grid::from_cells(rect(0, 0, rows[0].len() as i32, rows.len() as i32), cells).unwrap()
+    }
+
+    fn rows(g:&Grid<char>) -> Vec<String> {
+        let span = g.span();
+        (span.bl.y .. span.tr.y).map(|y| (span.bl.x .. span.tr.x).map(|x| *g.get(Point2d::new(x, y)).unwrap()).collect()).collect()
+    }
+
+    #[test]
+    fn blits_clip() {
+        let src = grid_of(&["abc", "def"]);
+        let mut dst = grid_of(&["....", "....", "...."]);
+        // hanging off the bottom-right
+        assert_eq!(dst.blit(&src, src.span(), Point2d::new(2,2)), 2);
+        assert_eq!(rows(&dst), ["....", "....", "..ab"]);
+        // a source rect partly outside the source, landing partly off the top-left
+        let mut dst = grid_of(&["....", "....", "...."]);
+        assert_eq!(dst.blit(&src, rect(1, -1, 4, 2), Point2d::new(-1,0)), 2);
+        assert_eq!(rows(&dst), ["....", "c...", "f..."]);
+        assert_eq!(dst.blit(&src, src.span(), Point2d::new(4,0)), 0);
+        assert_eq!(dst.blit(&src, rect(5, 5, 6, 6), Point2d::new(0,0)), 0);
+        let mut counts = Grid::new(rect(0, 0, 3, 3), 1u32);
+        let ones = Grid::new(rect(0, 0, 2, 2), 10u32);
+        assert_eq!(counts.blit_with(&ones, ones.span(), Point2d::new(-1,-1), &mut |d, s| *d += *s), 1);
+        assert_eq!(counts.blit_with(&ones, ones.span(), Point2d::new(2,1), &mut |d, s| *d += *s), 2);
+        assert_eq!(counts.cells(), [11, 1, 1, 1, 1, 11, 1, 1, 11]);
+    }
+
+    #[test]
+    fn resize_zip_views() {
+        let g = grid_of(&["abc", "def"]);
+        assert_eq!(rows(&g.resized(Point2d::new(5,3), Anchor::Center, Anchor::Max, '.')), [".....", ".abc.", ".def."]);
+        assert_eq!(rows(&g.resized(Point2d::new(2,1), Anchor::Max, Anchor::Min, '.')), ["bc"]);
+        assert_eq!(rows(&g.resized(Point2d::new(4,2), Anchor::Min, Anchor::Min, '.')), ["abc.", "def."]);
+
+        let mut shifted = Grid::new(rect(1, 1, 4, 3), 'x');
+        shifted.set(Point2d::new(2,1), 'y');
+        let zipped = g.zip_with(&shifted, |a, b| format!("{}{}", a, b)).unwrap();
+        assert_eq!(zipped.span(), rect(1, 1, 3, 2));
+        assert_eq!(zipped.cells(), ["ex", "fy"]);
+        assert!(g.zip_with(&Grid::new(rect(3, 0, 4, 1), 'x'), |a, b| (*a, *b)).is_none());
+        assert_eq!(g.map(|c| c.to_ascii_uppercase()).cells(), ['A', 'B', 'C', 'D', 'E', 'F']);
+
+        // views are clipped to the grid, & only reach within themselves
+        let mut g = g;
+        let mut v = g.view_mut(rect(-2, -2, 2, 1));
+        assert_eq!(v.span(), rect(0, 0, 2, 1));
+        v.fill('#');
+        assert!(!v.set(Point2d::new(2,0), '!') && !v.set(Point2d::new(-1,0), '!'));
+        assert!(v.get(Point2d::new(0,1)).is_none());
+        assert_eq!(rows(&g), ["##c", "def"]);
+        g.fill_rect(rect(2, 1, 9, 9), '%');
+        assert_eq!(rows(&g), ["##c", "de%"]);
+        let v = g.view(rect(1, 1, 5, 5));
+        assert_eq!(rows(&v.to_grid()), ["e%"]);
+        assert_eq!(g.view(rect(5, 5, 6, 6)).to_grid().cells(), &[] as &[char]);
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
@@ -0,0 +1,290 @@
+////////////////////////////////////////////////////////////////////////////////
+
+// Compact serialized representations for Grid<T>.
+//
+// Grid itself still serializes as the plain {cells, span} form, but deserializes
+// from any of the forms here, so a field can be switched between encodings
+// (with #[serde(with = "...")]) without breaking older saves:
+//   rle     : {span, runs:[(count,cell)...]}
+//   palette : {span, palette:[cell...], runs:[(count,index)...]}
+//   bits    : {span, bits:[u8...]}  (Grid<bool> only)
+
+use bit_vec::{BitVec};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{Error};
+
+use crate::grid::*;
+use crate::rect2d::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+// NB: order matters for untagged matching, as a palette repr of a Grid<u32>
+// would otherwise also parse as runs (unknown fields are ignored)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GridRepr<T> {
+    Plain { cells:Vec<T>, span:Rect2d },
+    Palette { span:Rect2d, palette:Vec<T>, runs:Vec<(u32,u32)> },
+    Runs { span:Rect2d, runs:Vec<(u32,T)> },
+}
+
+impl<T:Clone> GridRepr<T> {
+    fn into_grid(self) -> Result<Grid<T>,String> {
+        match self {
+            GridRepr::Plain { cells, span } => {
+                span_cells(&span)?;
+                Grid::from_cells(span, cells)
+            }
+            GridRepr::Palette { span, palette, runs } => {
+                let area = span_cells(&span)?;
+                let mut cells = Vec::new();
+                for (n, i) in runs {
+                    let c = palette.get(i as usize)
+                        .ok_or_else(|| format!("palette index {} out of range ({})", i, palette.len()))?;
+                    check_run(area, cells.len(), n)?;
+                    cells.extend(std::iter::repeat_n(c.clone(), n as usize));
+                }
+                Grid::from_cells(span, cells)
+            }
+            GridRepr::Runs { span, runs } => {
+                let area = span_cells(&span)?;
+                let mut cells = Vec::new();
+                for (n, c) in runs {
+                    check_run(area, cells.len(), n)?;
+                    cells.extend(std::iter::repeat_n(c, n as usize));
+                }
+                Grid::from_cells(span, cells)
+            }
+        }
+    }
+}
+
+// spans are untrusted too; a bogus one would overflow area() or have us
+// allocate gigabytes, so size it with checked maths against a cap
+const MAX_CELLS : i64 = 1 << 26;
+
+fn span_cells(span:&Rect2d) -> Result<usize,String> {
+    let w = (span.tr.x as i64 - span.bl.x as i64).max(0);
+    let h = (span.tr.y as i64 - span.bl.y as i64).max(0);
+    match w.checked_mul(h) {
+        Some(n) if n <= MAX_CELLS => Ok(n as usize),
+        _ => Err(format!("span {} is larger than {} cells", span, MAX_CELLS)),
+    }
+}
+
+// run counts are untrusted, so refuse to expand one that overruns the span
+fn check_run(area:usize, len:usize, n:u32) -> Result<(),String> {
+    if n as usize > area - len.min(area) {
+        return Err(format!("runs cover more than the {} cells of the span", area));
+    }
+    Ok(())
+}
+
+impl<'de, T:Clone+Deserialize<'de>> Deserialize<'de> for Grid<T> {
+    fn deserialize<D:Deserializer<'de>>(d:D) -> Result<Self,D::Error> {
+        GridRepr::<T>::deserialize(d)?.into_grid().map_err(D::Error::custom)
+    }
+}
+
+// (count, value) runs in cell order
+fn runs<T:PartialEq>(cells:&[T]) -> Vec<(u32,&T)> {
+    let mut res : Vec<(u32,&T)> = Vec::new();
+    for c in cells {
+        match res.last_mut() {
+            Some((n, x)) if *x == c => { *n += 1; }
+            _ => { res.push((1, c)); }
+        }
+    }
+    res
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// run-length encoded cells; good for large areas of identical cells
+pub mod rle {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct RunsRef<'a,T:Serialize> { span:Rect2d, runs:Vec<(u32,&'a T)> }
+
+    pub fn serialize<T:Clone+PartialEq+Serialize, S:Serializer>(g:&Grid<T>, s:S) -> Result<S::Ok,S::Error> {
+        RunsRef { span:g.span(), runs:runs(g.cells()) }.serialize(s)
+    }
+
+    pub fn deserialize<'de, T:Clone+Deserialize<'de>, D:Deserializer<'de>>(d:D) -> Result<Grid<T>,D::Error> {
+        Grid::deserialize(d)
+    }
+}
+
+////////////////////////////////////////
+
+// distinct cells stored once, with run-length encoded indices into them;
+// good for large, repetitive grids of bulky cells (ie terrain)
+// (palette lookup is a linear scan, so assumes a smallish number of distinct cells)
+pub mod palette {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct PaletteRef<'a,T:Serialize> { span:Rect2d, palette:Vec<&'a T>, runs:Vec<(u32,u32)> }
+
+    pub fn serialize<T:Clone+PartialEq+Serialize, S:Serializer>(g:&Grid<T>, s:S) -> Result<S::Ok,S::Error> {
+        let mut palette : Vec<&T> = Vec::new();
+        let mut idx_runs : Vec<(u32,u32)> = Vec::new();
+        for (n, c) in runs(g.cells()) {
+            let i = match palette.iter().position(|p| *p == c) {
+                Some(i) => i,
+                None => { palette.push(c); palette.len()-1 }
+            } as u32;
+            match idx_runs.last_mut() {
+                Some((m, j)) if *j == i => { *m += n; }
+                _ => { idx_runs.push((n, i)); }
+            }
+        }
+        PaletteRef { span:g.span(), palette, runs:idx_runs }.serialize(s)
+    }
+
+    pub fn deserialize<'de, T:Clone+Deserialize<'de>, D:Deserializer<'de>>(d:D) -> Result<Grid<T>,D::Error> {
+        Grid::deserialize(d)
+    }
+}
+
+////////////////////////////////////////
+
+// bit-packed Grid<bool> (eg visibility, explored, passability masks)
+pub mod bits {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct BitsRef { span:Rect2d, bits:Vec<u8> }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BitsRepr {
+        Bits { span:Rect2d, bits:Vec<u8> },
+        Other(GridRepr<bool>),
+    }
+
+    pub fn serialize<S:Serializer>(g:&Grid<bool>, s:S) -> Result<S::Ok,S::Error> {
+        let bv : BitVec = g.cells().iter().cloned().collect();
+        BitsRef { span:g.span(), bits:bv.to_bytes() }.serialize(s)
+    }
+
+    pub fn deserialize<'de, D:Deserializer<'de>>(d:D) -> Result<Grid<bool>,D::Error> {
+        let g = match BitsRepr::deserialize(d)? {
+            BitsRepr::Bits { span, bits } => {
+                let n = span_cells(&span).map_err(D::Error::custom)?;
+                let bv = BitVec::from_bytes(&bits);
+                if bv.len() < n {
+                    return Err(D::Error::custom(format!("only {} bits for {} cells", bv.len(), n)));
+                }
+                Grid::from_cells(span, bv.iter().take(n).collect())
+            }
+            BitsRepr::Other(repr) => repr.into_grid(),
+        };
+        g.map_err(D::Error::custom)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point2d::*;
+
+    #[derive(Debug,Serialize,Deserialize)]
+    struct Deck {
+        #[serde(with = "crate::grid_serde::palette")]
+        terrain: Grid<String>,
+        #[serde(with = "crate::grid_serde::rle")]
+        heights: Grid<i32>,
+        #[serde(with = "crate::grid_serde::bits")]
+        seen: Grid<bool>,
+    }
+
+    fn deck() -> Deck {
+        let span = Rect2d::new(Point2d::new(-3,-2), Point2d::new(61,62));
+        let mut terrain = Grid::new(span, "floor".to_string());
+        let mut heights = Grid::new(span, 0);
+        let mut seen = Grid::new(span, false);
+        terrain.fill_rect(Rect2d::new(Point2d::new(0,0), Point2d::new(10,3)), "wall".into());
+        terrain.set(Point2d::new(40,40), "door".into());
+        heights.fill_rect(Rect2d::new(Point2d::new(5,5), Point2d::new(8,50)), 3);
+        for p in Rect2d::new(Point2d::new(1,1), Point2d::new(9,13)) { seen.set(p, p.x % 3 != 0); }
+        Deck { terrain, heights, seen }
+    }
+
+    fn same<T:Clone+PartialEq>(a:&Grid<T>, b:&Grid<T>) -> bool {
+        a.span() == b.span() && a.cells() == b.cells()
+    }
+
+    #[test]
+    fn roundtrip_json_cbor() {
+        let d = deck();
+        let js = serde_json::to_string(&d).unwrap();
+        let plain = serde_json::to_string(&d.terrain).unwrap();
+        assert!(js.len() * 10 < plain.len());
+        let d2 : Deck = serde_json::from_str(&js).unwrap();
+        let cb = serde_cbor::to_vec(&d).unwrap();
+        let d3 : Deck = serde_cbor::from_slice(&cb).unwrap();
+        for dd in [&d2, &d3] {
+            assert!(same(&d.terrain, &dd.terrain));
+            assert!(same(&d.heights, &dd.heights));
+            assert!(same(&d.seen, &dd.seen));
+        }
+    }
+
+    #[test]
+    fn roundtrip_bson() {
+        let d = deck();
+        let bs = bson::serialize_to_vec(&d).unwrap();
+        let d2 : Deck = bson::deserialize_from_slice(&bs).unwrap();
+        assert!(same(&d.terrain, &d2.terrain));
+        assert!(same(&d.heights, &d2.heights));
+        assert!(same(&d.seen, &d2.seen));
+    }
+
+    #[test]
+    fn plain_still_loads() {
+        let d = deck();
+        let js = format!("{{\"terrain\":{},\"heights\":{},\"seen\":{}}}",
+            serde_json::to_string(&d.terrain).unwrap(),
+            serde_json::to_string(&d.heights).unwrap(),
+            serde_json::to_string(&d.seen).unwrap());
+        let d2 : Deck = serde_json::from_str(&js).unwrap();
+        assert!(same(&d.terrain, &d2.terrain));
+        assert!(same(&d.heights, &d2.heights));
+        assert!(same(&d.seen, &d2.seen));
+        // and compact forms load into a plain Grid too
+        let rle = serde_json::to_string(&serde_json::to_value(&d).unwrap()["heights"]).unwrap();
+        let g : Grid<i32> = serde_json::from_str(&rle).unwrap();
+        assert!(same(&d.heights, &g));
+    }
+
+    #[test]
+    fn bad_lengths_rejected() {
+        let bad = "{\"span\":{\"bl\":{\"x\":0,\"y\":0},\"tr\":{\"x\":2,\"y\":2}},\"runs\":[[3,7]]}";
+        assert!(serde_json::from_str::<Grid<i32>>(bad).is_err());
+        let bad = "{\"span\":{\"bl\":{\"x\":0,\"y\":0},\"tr\":{\"x\":2,\"y\":2}},\"palette\":[1],\"runs\":[[4,1]]}";
+        assert!(serde_json::from_str::<Grid<i32>>(bad).is_err());
+        // (a huge run fails before anything is expanded)
+        let bad = "{\"span\":{\"bl\":{\"x\":0,\"y\":0},\"tr\":{\"x\":2,\"y\":2}},\"runs\":[[1,7],[4294967295,7]]}";
+        let e = serde_json::from_str::<Grid<i32>>(bad).unwrap_err();
+        assert!(e.to_string().contains("more than the 4 cells"));
+    }
+
+    #[test]
+    fn huge_spans_rejected() {
+        let huge = "{\"bl\":{\"x\":-2147483648,\"y\":0},\"tr\":{\"x\":2147483647,\"y\":100000}}";
+        for body in ["\"cells\":[]", "\"runs\":[[1,7]]", "\"palette\":[7],\"runs\":[[1,0]]"] {
+            let bad = format!("{{\"span\":{},{}}}", huge, body);
+            let e = serde_json::from_str::<Grid<i32>>(&bad).unwrap_err();
+            assert!(e.to_string().contains("larger than"), "{}", e);
+        }
+        let bad = format!("{{\"span\":{},\"bits\":[255]}}", huge);
+        let mut de = serde_json::Deserializer::from_str(&bad);
+        assert!(bits::deserialize(&mut de).is_err());
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
@@ -79,6 +79,10 @@ impl<T> WeakHandle<T>
     pub fn upgrade(&self) -> Option<Handle<T>> {
         self.0.upgrade().map(|rc|Handle(rc))
     }
+
+    pub fn ptr_eq(a:&WeakHandle<T>, b:&WeakHandle<T>) -> bool {
+        Weak::ptr_eq(&a.0, &b.0)
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
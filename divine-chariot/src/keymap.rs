@@ -0,0 +1,484 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path};
+
+use bear_lib_terminal::terminal::{KeyCode};
+
+use crate::point2d::*;
+use crate::renderer::{InputEvent};
+
+////////////////////////////////////////////////////////////////////////////////
+
+// every key a chord can name (ie in config files), by its KeyCode name
+const KEYS : [KeyCode; 96] = {
+    use KeyCode::*;
+    [A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+     Row1, Row2, Row3, Row4, Row5, Row6, Row7, Row8, Row9, Row0,
+     Grave, Minus, Equals, LeftBracket, RightBracket, Backslash, Semicolon, Apostrophe, Comma, Period, Slash,
+     F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+     Enter, Escape, Backspace, Tab, Space, Pause, Insert, Home, PageUp, Delete, End, PageDown,
+     Right, Left, Down, Up,
+     NumDivide, NumMultiply, NumMinus, NumPlus, NumEnter, NumPeriod,
+     Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9, Num0,
+     MouseLeft, MouseRight, MouseMiddle, MouseFourth, MouseFifth]
+};
+
+pub fn key_name(key:KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+pub fn key_from_name(name:&str) -> Option<KeyCode> {
+    KEYS.iter().copied().find(|k| key_name(*k).eq_ignore_ascii_case(name))
+}
+
+// a key with modifiers, written (ie in config files) as "Ctrl+Alt+Shift+Key"
+#[derive(Clone,Copy,Debug,Eq,Hash,PartialEq)]
+pub struct KeyChord {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyChord {
+    pub fn new(key:KeyCode) -> Self {
+        KeyChord { key, ctrl:false, shift:false, alt:false }
+    }
+
+    pub fn shift(key:KeyCode) -> Self {
+        KeyChord { shift:true, ..KeyChord::new(key) }
+    }
+
+    pub fn ctrl(key:KeyCode) -> Self {
+        KeyChord { ctrl:true, ..KeyChord::new(key) }
+    }
+
+    // (None for anything but a key press)
+    pub fn from_event(ev:&InputEvent) -> Option<KeyChord> {
+        match *ev {
+            InputEvent::Key { key, ctrl, shift, alt } => Some(KeyChord { key, ctrl, shift, alt }),
+            _ => None,
+        }
+    }
+
+    pub fn parse(s:&str) -> Option<KeyChord> {
+        let mut parts : Vec<&str> = s.split('+').map(|p| p.trim()).collect();
+        let mut chord = KeyChord::new(key_from_name(parts.pop()?)?);
+        for m in parts {
+            match m.to_ascii_lowercase().as_str() {
+                "ctrl" => { chord.ctrl = true; }
+                "shift" => { chord.shift = true; }
+                "alt" => { chord.alt = true; }
+                _ => { return None; }
+            }
+        }
+        Some(chord)
+    }
+
+    // for help screens: punctuation as typed (ie "?" for Shift+Slash)
+    pub fn label(&self) -> String {
+        use KeyCode::*;
+        let punct = [(Grave,'`','~'), (Minus,'-','_'), (Equals,'=','+'), (LeftBracket,'[','{'), (RightBracket,']','}'),
+            (Backslash,'\\','|'), (Semicolon,';',':'), (Apostrophe,'\'','"'), (Comma,',','<'), (Period,'.','>'), (Slash,'/','?'),
+            (Row1,'1','!'), (Row2,'2','@'), (Row3,'3','#'), (Row4,'4','$'), (Row5,'5','%'),
+            (Row6,'6','^'), (Row7,'7','&'), (Row8,'8','*'), (Row9,'9','('), (Row0,'0',')')];
+        let letter = KEYS[..26].contains(&self.key);
+        match punct.iter().find(|(k, _, _)| *k == self.key) {
+            Some((_, plain, shifted)) if !self.ctrl && !self.alt => (if self.shift { *shifted } else { *plain }).to_string(),
+            _ if letter && !self.ctrl && !self.alt => {
+                let s = key_name(self.key);
+                if self.shift { s } else { s.to_lowercase() }
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.ctrl { write!(f, "Ctrl+")?; }
+        if self.alt { write!(f, "Alt+")?; }
+        if self.shift { write!(f, "Shift+")?; }
+        write!(f, "{}", key_name(self.key))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone,Copy,Debug,Eq,Hash,PartialEq)]
+pub enum Command {
+    // map (& targeting, for the cursor)
+    Move(Dir),
+    Run(Dir),
+    Wait,
+    Pickup,
+    Drop,
+    Look,
+    Extended,
+    Help,
+    Quit,
+    ZoomIn,
+    ZoomOut,
+    LogOlder,
+    LogNewer,
+    LogLatest,
+    // menus (& targeting, for Select & Cancel)
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    First,
+    Last,
+    Select,
+    Cancel,
+}
+
+impl Command {
+    pub fn all() -> impl Iterator<Item=Command> {
+        use Command::*;
+        Dir::all().map(Move)
+            .chain(Dir::all().map(Run))
+            .chain([Wait, Pickup, Drop, Look, Extended, Help, Quit, ZoomIn, ZoomOut, LogOlder, LogNewer, LogLatest,
+                Up, Down, PageUp, PageDown, First, Last, Select, Cancel])
+    }
+
+    // as written in config files (ie "move_ne")
+    pub fn name(&self) -> String {
+        match self {
+            Command::Move(d) => format!("move_{:?}", d).to_lowercase(),
+            Command::Run(d) => format!("run_{:?}", d).to_lowercase(),
+            Command::LogOlder => "log_older".into(),
+            Command::LogNewer => "log_newer".into(),
+            Command::LogLatest => "log_latest".into(),
+            Command::ZoomIn => "zoom_in".into(),
+            Command::ZoomOut => "zoom_out".into(),
+            Command::PageUp => "page_up".into(),
+            Command::PageDown => "page_down".into(),
+            c => format!("{:?}", c).to_lowercase(),
+        }
+    }
+
+    pub fn from_name(name:&str) -> Option<Command> {
+        Command::all().find(|c| c.name() == name)
+    }
+
+    pub fn description(&self) -> String {
+        let dir = |d:&Dir| match d {
+            Dir::E => "east", Dir::NE => "northeast", Dir::N => "north", Dir::NW => "northwest",
+            Dir::W => "west", Dir::SW => "southwest", Dir::S => "south", Dir::SE => "southeast",
+        };
+        match self {
+            Command::Move(d) => format!("move {}", dir(d)),
+            Command::Run(d) => format!("run {}", dir(d)),
+            Command::Wait => "wait a turn".into(),
+            Command::Pickup => "pick up".into(),
+            Command::Drop => "drop the last thing picked up".into(),
+            Command::Look => "look around".into(),
+            Command::Extended => "command by name".into(),
+            Command::Help => "show this help".into(),
+            Command::Quit => "quit".into(),
+            Command::ZoomIn => "zoom minimap in".into(),
+            Command::ZoomOut => "zoom minimap out".into(),
+            Command::LogOlder => "scroll log back".into(),
+            Command::LogNewer => "scroll log forward".into(),
+            Command::LogLatest => "latest messages".into(),
+            Command::Up => "previous item".into(),
+            Command::Down => "next item".into(),
+            Command::PageUp => "page up".into(),
+            Command::PageDown => "page down".into(),
+            Command::First => "first item".into(),
+            Command::Last => "last item".into(),
+            Command::Select => "select".into(),
+            Command::Cancel => "cancel".into(),
+        }
+    }
+}
+
+#[derive(Clone,Copy,Debug,Eq,Hash,Ord,PartialEq,PartialOrd)]
+pub enum Context {
+    Map,
+    Menu,
+    Targeting,
+}
+
+impl Context {
+    pub fn all() -> impl Iterator<Item=Context> {
+        [Context::Map, Context::Menu, Context::Targeting].into_iter()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Context::Map => "map",
+            Context::Menu => "menu",
+            Context::Targeting => "targeting",
+        }
+    }
+
+    pub fn from_name(name:&str) -> Option<Context> {
+        Context::all().find(|c| c.name() == name)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// built-in sets of movement (& menu navigation) keys
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Preset {
+    Arrows,
+    Numpad,
+    Vi,         // hjkl yubn (not for menus, where letters are hotkeys)
+}
+
+// a key chord bound to more than one command in the same context
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub struct Conflict {
+    pub context: Context,
+    pub chord: KeyChord,
+    pub commands: Vec<Command>,
+}
+
+// key chords to commands, per context; a command can have any number of chords
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub struct Keymap {
+    bindings: HashMap<Context,Vec<(KeyChord,Command)>>,
+}
+
+impl Keymap {
+    // (no bindings at all)
+    pub fn new() -> Self {
+        Keymap { bindings:HashMap::new() }
+    }
+
+    // the fixed bindings, plus the given presets
+    pub fn with_presets(presets:&[Preset]) -> Self {
+        use Command::*;
+        use KeyCode as K;
+        let mut km = Keymap::new();
+        let (map, menu, targeting) = (Context::Map, Context::Menu, Context::Targeting);
+        km.bind(map, KeyChord::new(K::Period), Wait);
+        km.bind(map, KeyChord::new(K::G), Pickup);
+        km.bind(map, KeyChord::new(K::Comma), Pickup);
+        km.bind(map, KeyChord::new(K::D), Drop);
+        km.bind(map, KeyChord::new(K::X), Look);
+        km.bind(map, KeyChord::shift(K::Row3), Extended);
+        km.bind(map, KeyChord::new(K::F1), Help);
+        km.bind(map, KeyChord::shift(K::Slash), Help);
+        km.bind(map, KeyChord::new(K::Escape), Quit);
+        km.bind(map, KeyChord::new(K::Q), Quit);
+        km.bind(map, KeyChord::new(K::Equals), ZoomIn);
+        km.bind(map, KeyChord::shift(K::Equals), ZoomIn);
+        km.bind(map, KeyChord::new(K::NumPlus), ZoomIn);
+        km.bind(map, KeyChord::new(K::Minus), ZoomOut);
+        km.bind(map, KeyChord::new(K::NumMinus), ZoomOut);
+        km.bind(map, KeyChord::new(K::PageUp), LogOlder);
+        km.bind(map, KeyChord::new(K::PageDown), LogNewer);
+        km.bind(map, KeyChord::new(K::End), LogLatest);
+        for ctx in [menu, targeting] {
+            km.bind(ctx, KeyChord::new(K::Enter), Select);
+            km.bind(ctx, KeyChord::new(K::NumEnter), Select);
+            km.bind(ctx, KeyChord::new(K::Escape), Cancel);
+        }
+        km.bind(targeting, KeyChord::new(K::X), Cancel);
+        for p in presets {
+            km.add_preset(*p);
+        }
+        km
+    }
+
+    pub fn add_preset(&mut self, preset:Preset) {
+        use KeyCode as K;
+        let (dirs, menu) : (Vec<_>, Vec<(KeyCode,Command)>) = match preset {
+            Preset::Arrows => (
+                vec![(K::Right,Dir::E), (K::Up,Dir::N), (K::Left,Dir::W), (K::Down,Dir::S)],
+                vec![(K::Up,Command::Up), (K::Down,Command::Down), (K::PageUp,Command::PageUp),
+                    (K::PageDown,Command::PageDown), (K::Home,Command::First), (K::End,Command::Last)]),
+            Preset::Numpad => (
+                vec![(K::Num6,Dir::E), (K::Num9,Dir::NE), (K::Num8,Dir::N), (K::Num7,Dir::NW),
+                    (K::Num4,Dir::W), (K::Num1,Dir::SW), (K::Num2,Dir::S), (K::Num3,Dir::SE)],
+                vec![(K::Num8,Command::Up), (K::Num2,Command::Down), (K::Num9,Command::PageUp),
+                    (K::Num3,Command::PageDown), (K::Num7,Command::First), (K::Num1,Command::Last)]),
+            Preset::Vi => (
+                vec![(K::L,Dir::E), (K::U,Dir::NE), (K::K,Dir::N), (K::Y,Dir::NW),
+                    (K::H,Dir::W), (K::B,Dir::SW), (K::J,Dir::S), (K::N,Dir::SE)],
+                vec![]),
+        };
+        for ctx in [Context::Map, Context::Targeting] {
+            for (k, d) in dirs.iter() {
+                self.bind(ctx, KeyChord::new(*k), Command::Move(*d));
+                self.bind(ctx, KeyChord::shift(*k), Command::Run(*d));
+            }
+        }
+        if preset == Preset::Numpad {
+            self.bind(Context::Map, KeyChord::new(K::Num5), Command::Wait);
+        }
+        for (k, c) in menu {
+            self.bind(Context::Menu, KeyChord::new(k), c);
+        }
+    }
+
+    // (already bound chord/command pairs are left as they are)
+    pub fn bind(&mut self, ctx:Context, chord:KeyChord, cmd:Command) {
+        let bs = self.bindings.entry(ctx).or_default();
+        if !bs.contains(&(chord, cmd)) { bs.push((chord, cmd)); }
+    }
+
+    // other's bindings over these: a chord it binds loses whatever it was bound to
+    // here, while chords it leaves alone keep theirs (so a partial keys.json works)
+    pub fn merge(&mut self, other:&Keymap) {
+        for (ctx, bs) in other.bindings.iter() {
+            for (chord, _) in bs.iter() { self.unbind(*ctx, *chord); }
+            for (chord, cmd) in bs.iter() { self.bind(*ctx, *chord, *cmd); }
+        }
+    }
+
+    // removes every binding of chord in ctx
+    pub fn unbind(&mut self, ctx:Context, chord:KeyChord) {
+        if let Some(bs) = self.bindings.get_mut(&ctx) {
+            bs.retain(|(c, _)| *c != chord);
+        }
+    }
+
+    // (the first bound, if in conflict)
+    pub fn command(&self, ctx:Context, chord:KeyChord) -> Option<Command> {
+        self.bindings.get(&ctx)?.iter().find(|(c, _)| *c == chord).map(|(_, cmd)| *cmd)
+    }
+
+    pub fn chords(&self, ctx:Context, cmd:Command) -> Vec<KeyChord> {
+        self.bindings.get(&ctx).map(|bs| bs.iter().filter(|(_, c)| *c == cmd).map(|(k, _)| *k).collect()).unwrap_or_default()
+    }
+
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut res = Vec::new();
+        for ctx in Context::all() {
+            let bs = match self.bindings.get(&ctx) { Some(bs) => bs, None => { continue; } };
+            for (i, (chord, _)) in bs.iter().enumerate() {
+                // (reported once, at the chord's first binding)
+                if bs[..i].iter().any(|(c, _)| c == chord) { continue; }
+                let commands : Vec<Command> = bs[i..].iter().filter(|(c, _)| c == chord).map(|(_, cmd)| *cmd).collect();
+                if commands.len() > 1 {
+                    res.push(Conflict { context:ctx, chord:*chord, commands });
+                }
+            }
+        }
+        res
+    }
+
+    // markup lines for a help screen: each command's chords & what it does
+    pub fn help_lines(&self, ctx:Context) -> Vec<String> {
+        let rows : Vec<(String,String)> = Command::all()
+            .map(|cmd| (self.chords(ctx, cmd), cmd))
+            .filter(|(chords, _)| !chords.is_empty())
+            .map(|(chords, cmd)| (chords.iter().map(|c| c.label()).collect::<Vec<_>>().join(" "), cmd.description()))
+            .collect();
+        let w = rows.iter().map(|(keys, _)| keys.chars().count()).max().unwrap_or(0);
+        rows.into_iter()
+            .map(|(keys, desc)| format!("[fg=gold]{:<w$}[/fg]  {}", keys.replace('[', "[["), desc, w=w))
+            .collect()
+    }
+
+    ////////////////////////////////////////
+
+    // {"context": {"command": ["Chord", ...], ...}, ...}
+    pub fn to_json(&self) -> String {
+        let mut out : BTreeMap<&str,BTreeMap<String,Vec<String>>> = BTreeMap::new();
+        for (ctx, bs) in self.bindings.iter() {
+            let cmds = out.entry(ctx.name()).or_default();
+            for (chord, cmd) in bs.iter() {
+                cmds.entry(cmd.name()).or_default().push(chord.to_string());
+            }
+        }
+        serde_json::to_string_pretty(&out).unwrap()
+    }
+
+    pub fn from_json(s:&str) -> io::Result<Keymap> {
+        let bad = |what:String| io::Error::new(io::ErrorKind::InvalidData, what);
+        let parsed : BTreeMap<String,BTreeMap<String,Vec<String>>> = serde_json::from_str(s).map_err(io::Error::other)?;
+        let mut km = Keymap::new();
+        for (ctx, cmds) in parsed {
+            let ctx = Context::from_name(&ctx).ok_or_else(|| bad(format!("unknown context '{}'", ctx)))?;
+            for (cmd, chords) in cmds {
+                let cmd = Command::from_name(&cmd).ok_or_else(|| bad(format!("unknown command '{}'", cmd)))?;
+                for chord in chords {
+                    let chord = KeyChord::parse(&chord).ok_or_else(|| bad(format!("bad key '{}'", chord)))?;
+                    km.bind(ctx, chord, cmd);
+                }
+            }
+        }
+        Ok(km)
+    }
+
+    pub fn load(path:&Path) -> io::Result<Keymap> {
+        Keymap::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path:&Path) -> io::Result<()> {
+        std::fs::write(path, self.to_json())
+    }
+}
+
+// arrows & numpad
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::with_presets(&[Preset::Arrows, Preset::Numpad])
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chords_and_names() {
+        let c = KeyChord::parse("ctrl+Shift+num8").unwrap();
+        assert_eq!(c, KeyChord { key:KeyCode::Num8, ctrl:true, shift:true, alt:false });
+        assert_eq!(c.to_string(), "Ctrl+Shift+Num8");
+        assert_eq!(KeyChord::parse(&c.to_string()), Some(c));
+        assert_eq!(KeyChord::parse("Hyper+A"), None);
+        assert_eq!(KeyChord::parse("Shift+Slash").unwrap().label(), "?");
+        assert_eq!(KeyChord::new(KeyCode::K).label(), "k");
+        assert_eq!(KeyChord::shift(KeyCode::Row3).label(), "#");
+        assert_eq!(KeyChord::shift(KeyCode::K).label(), "K");
+        for cmd in Command::all() {
+            assert_eq!(Command::from_name(&cmd.name()), Some(cmd));
+        }
+        assert_eq!(Command::Run(Dir::SW).name(), "run_sw");
+    }
+
+    #[test]
+    fn presets_conflicts_and_config() {
+        let km = Keymap::with_presets(&[Preset::Arrows, Preset::Numpad, Preset::Vi]);
+        assert_eq!(km.command(Context::Map, KeyChord::new(KeyCode::Y)), Some(Command::Move(Dir::NW)));
+        assert_eq!(km.command(Context::Map, KeyChord::shift(KeyCode::Num3)), Some(Command::Run(Dir::SE)));
+        assert_eq!(km.command(Context::Menu, KeyChord::new(KeyCode::Num8)), Some(Command::Up));
+        assert_eq!(km.command(Context::Targeting, KeyChord::new(KeyCode::Escape)), Some(Command::Cancel));
+        assert_eq!(km.command(Context::Menu, KeyChord::new(KeyCode::Y)), None);
+        assert!(km.conflicts().is_empty());
+        let mut km2 = km.clone();
+        km2.bind(Context::Map, KeyChord::new(KeyCode::L), Command::Look);
+        assert_eq!(km2.conflicts(), vec![Conflict { context:Context::Map, chord:KeyChord::new(KeyCode::L),
+            commands:vec![Command::Move(Dir::E), Command::Look] }]);
+        // (conflicts survive a round trip, to be reported after loading)
+        let loaded = Keymap::from_json(&km2.to_json()).unwrap();
+        assert_eq!(loaded.conflicts().len(), 1);
+        assert_eq!(Keymap::from_json(&km.to_json()).unwrap().chords(Context::Map, Command::Help),
+            vec![KeyChord::new(KeyCode::F1), KeyChord::shift(KeyCode::Slash)]);
+        assert!(Keymap::from_json(r#"{"map": {"fly": ["F"]}}"#).is_err());
+        assert!(Keymap::from_json(r#"{"map": {"look": ["Ctrl+Nope"]}}"#).is_err());
+        // a partial config only rebinds what it names
+        let mut merged = Keymap::default();
+        merged.merge(&Keymap::from_json(r#"{"map": {"look": ["L", "Num5"]}}"#).unwrap());
+        assert_eq!(merged.command(Context::Map, KeyChord::new(KeyCode::Num5)), Some(Command::Look));
+        assert_eq!(merged.command(Context::Map, KeyChord::new(KeyCode::X)), Some(Command::Look));
+        assert_eq!(merged.command(Context::Map, KeyChord::new(KeyCode::Num8)), Some(Command::Move(Dir::N)));
+        assert_eq!(merged.command(Context::Menu, KeyChord::new(KeyCode::Up)), Some(Command::Up));
+        assert!(merged.conflicts().is_empty());
+        let help = km.help_lines(Context::Map);
+        assert!(help.iter().any(|l| l.contains("F1 ?") && l.ends_with("  show this help")));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
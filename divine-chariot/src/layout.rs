@@ -0,0 +1,242 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::core_systems::*;
+use crate::point2d::*;
+use crate::rect2d::*;
+use crate::window::{WindowHandle};
+
+////////////////////////////////////////////////////////////////////////////////
+
+// how much of a split's length a part asks for
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Extent {
+    Fixed(i32),         // cells
+    Percent(i32),       // of the split's length (less gaps)
+    Fill(i32),          // share of whatever is left, by weight
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub struct Constraint {
+    pub extent: Extent,
+    pub min: i32,
+    pub max: i32,
+}
+
+impl Constraint {
+    pub fn fixed(n:i32) -> Self {
+        Constraint { extent:Extent::Fixed(n), min:0, max:i32::MAX }
+    }
+
+    pub fn percent(p:i32) -> Self {
+        Constraint { extent:Extent::Percent(p), min:0, max:i32::MAX }
+    }
+
+    pub fn fill() -> Self {
+        Constraint::fill_weighted(1)
+    }
+
+    pub fn fill_weighted(w:i32) -> Self {
+        Constraint { extent:Extent::Fill(w.max(1)), min:0, max:i32::MAX }
+    }
+
+    pub fn min(mut self, min:i32) -> Self {
+        self.min = min.max(0);
+        self
+    }
+
+    pub fn max(mut self, max:i32) -> Self {
+        self.max = max.max(0);
+        self
+    }
+
+    // (min wins over max if they cross)
+    pub fn clamp(&self, n:i32) -> i32 {
+        n.min(self.max).max(self.min)
+    }
+}
+
+// lengths for parts with the given constraints, splitting total cells with gap
+// cells between each part:
+//  - fixed & percent parts take their clamped extent,
+//  - fill parts share the rest by weight (any pinned at their min/max drop out
+//    and the others reshare),
+//  - if still too long, parts are shrunk from the last back, first to their
+//    min, then to nothing
+pub fn solve(constraints:&[Constraint], total:i32, gap:i32) -> Vec<i32> {
+    if constraints.is_empty() { return Vec::new(); }
+    let avail = (total - gap*(constraints.len() as i32 - 1)).max(0);
+    let mut sizes : Vec<i32> = constraints.iter().map(|c| match c.extent {
+        Extent::Fixed(n) => c.clamp(n),
+        Extent::Percent(p) => c.clamp(avail*p/100),
+        Extent::Fill(_) => 0,
+    }).collect();
+    let weight = |i:usize| match constraints[i].extent { Extent::Fill(w) => w, _ => 0 };
+    let mut open : Vec<usize> = (0..constraints.len()).filter(|i| weight(*i) > 0).collect();
+    while !open.is_empty() {
+        let taken : i32 = (0..sizes.len()).filter(|i| !open.contains(i)).map(|i| sizes[i]).sum();
+        let left = (avail - taken).max(0);
+        let total_w : i32 = open.iter().map(|i| weight(*i)).sum();
+        let mut shares : Vec<i32> = open.iter().map(|i| left*weight(*i)/total_w).collect();
+        // rounding leftovers go to the first fills
+        let mut spare = left - shares.iter().sum::<i32>();
+        for s in shares.iter_mut() {
+            if spare == 0 { break; }
+            *s += 1;
+            spare -= 1;
+        }
+        let pinned : Vec<usize> = open.iter().zip(shares.iter())
+            .filter(|(i, s)| constraints[**i].clamp(**s) != **s)
+            .map(|(i, _)| *i)
+            .collect();
+        for (i, s) in open.iter().zip(shares) {
+            sizes[*i] = constraints[*i].clamp(s);
+        }
+        if pinned.is_empty() { break; }
+        open.retain(|i| !pinned.contains(i));
+    }
+    let mut over = sizes.iter().sum::<i32>() - avail;
+    for to_min in [true, false] {
+        for i in (0..sizes.len()).rev() {
+            if over <= 0 { break; }
+            let floor = if to_min { constraints[i].min.min(sizes[i]) } else { 0 };
+            let d = (sizes[i] - floor).min(over);
+            sizes[i] -= d;
+            over -= d;
+        }
+    }
+    sizes
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Axis {
+    Horizontal,         // parts side by side, left to right
+    Vertical,           // parts stacked, top to bottom
+}
+
+// called with a window's new size after a layout changes it (ie so a widget can rerender)
+pub type ResizeFn = Box<dyn FnMut(Point2d)>;
+
+// tree of splits, placing windows (all children of the same parent) within an area
+// of that parent; apply() again whenever the area changes (ie on terminal resize)
+pub enum Layout {
+    Space,
+    Window(WindowHandle<Glyph>, Option<ResizeFn>),
+    Split { axis:Axis, gap:i32, parts:Vec<(Constraint,Layout)> },
+}
+
+impl Layout {
+    pub fn window(w:&WindowHandle<Glyph>) -> Self {
+        Layout::Window(w.clone(), None)
+    }
+
+    pub fn window_with(w:&WindowHandle<Glyph>, on_resize:ResizeFn) -> Self {
+        Layout::Window(w.clone(), Some(on_resize))
+    }
+
+    pub fn hsplit(gap:i32, parts:Vec<(Constraint,Layout)>) -> Self {
+        Layout::Split { axis:Axis::Horizontal, gap, parts }
+    }
+
+    pub fn vsplit(gap:i32, parts:Vec<(Constraint,Layout)>) -> Self {
+        Layout::Split { axis:Axis::Vertical, gap, parts }
+    }
+
+    // areas for each part of a split
+    pub fn split_areas(axis:Axis, gap:i32, constraints:&[Constraint], area:Rect2d) -> Vec<Rect2d> {
+        let size = area.size();
+        let (len, across) = match axis { Axis::Horizontal => (size.x, size.y), Axis::Vertical => (size.y, size.x) };
+        let mut at = 0;
+        solve(constraints, len, gap).into_iter().map(|n| {
+            let (off, ext) = match axis {
+                Axis::Horizontal => (Point2d::new(at, 0), Point2d::new(n, across)),
+                Axis::Vertical => (Point2d::new(0, at), Point2d::new(across, n)),
+            };
+            at = (at + n + gap).min(len);
+            Rect2d::new(area.bl + off, area.bl + off + ext)
+        }).collect()
+    }
+
+    // moves & resizes every window to fit area (in the windows' parent's local coordinates)
+    pub fn apply(&mut self, area:Rect2d) {
+        match self {
+            Layout::Space => {}
+            Layout::Window(w, on_resize) => {
+                let size = Point2d::new(area.size().x.max(0), area.size().y.max(0));
+                let resized = size != w.get_size();
+                w.set_offset(area.bl);
+                w.set_size(size);
+                if let (true, Some(f)) = (resized, on_resize) { f(size); }
+            }
+            Layout::Split { axis, gap, parts } => {
+                let constraints : Vec<Constraint> = parts.iter().map(|(c, _)| *c).collect();
+                let areas = Layout::split_areas(*axis, *gap, &constraints, area);
+                for ((_, part), a) in parts.iter_mut().zip(areas) {
+                    part.apply(a);
+                }
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell};
+    use std::rc::{Rc};
+
+    #[test]
+    fn solving_constraints() {
+        // fixed & percent first, fills share the rest by weight
+        assert_eq!(solve(&[Constraint::fixed(10), Constraint::fill(), Constraint::percent(25)], 40, 0), vec![10, 20, 10]);
+        assert_eq!(solve(&[Constraint::fill(), Constraint::fill_weighted(2)], 31, 1), vec![10, 20]);
+        // rounding leftovers go to the first fill
+        assert_eq!(solve(&[Constraint::fill(), Constraint::fill(), Constraint::fill()], 11, 0), vec![4, 4, 3]);
+        // a fill pinned at its max gives the rest to the others
+        assert_eq!(solve(&[Constraint::fill().max(5), Constraint::fill()], 30, 0), vec![5, 25]);
+        assert_eq!(solve(&[Constraint::fill().min(20), Constraint::fill()], 30, 0), vec![20, 10]);
+        assert_eq!(solve(&[Constraint::fixed(3).min(5), Constraint::percent(90).max(4)], 100, 0), vec![5, 4]);
+        // too small: shrink from the end, to mins first
+        assert_eq!(solve(&[Constraint::fixed(10).min(6), Constraint::fixed(10).min(6)], 15, 0), vec![9, 6]);
+        assert_eq!(solve(&[Constraint::fixed(10).min(6), Constraint::fixed(10).min(6)], 8, 2), vec![6, 0]);
+        assert!(solve(&[], 8, 2).is_empty());
+    }
+
+    #[test]
+    fn applies_and_reapplies_on_resize() {
+        let root = WindowHandle::new("Root".into(), Rect2d::new(Point2d::new(0,0), Point2d::new(100,40)));
+        let child = |id:&str| root.new_child(id.into(), Point2d::new(0,0), Rect2d::new(Point2d::new(0,0), Point2d::new(1,1)));
+        let (map, side, log) = (child("Map"), child("Side"), child("Log"));
+        let resizes = Rc::new(Cell::new(0));
+        let r = resizes.clone();
+        let mut layout = Layout::hsplit(1, vec![
+            (Constraint::fill().min(20), Layout::vsplit(0, vec![
+                (Constraint::fill(), Layout::window(&map)),
+                (Constraint::fixed(7).min(3), Layout::window_with(&log, Box::new(move |_| r.set(r.get() + 1)))),
+            ])),
+            (Constraint::fixed(16), Layout::vsplit(0, vec![
+                (Constraint::fixed(8), Layout::window(&side)),
+                (Constraint::fill(), Layout::Space),
+            ])),
+        ]);
+        layout.apply(root.span().inset(1));
+        assert_eq!((map.get_offset(), map.get_size()), (Point2d::new(1,1), Point2d::new(81,31)));
+        assert_eq!((log.get_offset(), log.get_size()), (Point2d::new(1,32), Point2d::new(81,7)));
+        assert_eq!((side.get_offset(), side.get_size()), (Point2d::new(83,1), Point2d::new(16,8)));
+        assert_eq!(resizes.get(), 1);
+        root.set_size(Point2d::new(60,20));
+        layout.apply(root.span().inset(1));
+        assert_eq!((map.get_offset(), map.get_size()), (Point2d::new(1,1), Point2d::new(41,11)));
+        assert_eq!((log.get_offset(), log.get_size()), (Point2d::new(1,12), Point2d::new(41,7)));
+        assert_eq!(side.get_offset(), Point2d::new(43,1));
+        assert_eq!(resizes.get(), 2);
+        // unchanged sizes don't call back
+        layout.apply(root.span().inset(1));
+        assert_eq!(resizes.get(), 2);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
@@ -3,6 +3,7 @@
 use crate::map::*;
 use crate::rect2d::*;
 use crate::point2d::*;
+use crate::point3d::*;
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -16,6 +17,10 @@ impl Location {
     pub fn new(p:Point2d, m:MapId) -> Self {
         Location { p, m }
     }
+
+    pub fn with_height(&self, z:i32) -> Location3d {
+        Location3d { p:Point3d::from_2d(self.p, z), m:self.m }
+    }
 }
 
 impl std::fmt::Debug for Location {
@@ -31,3 +36,61 @@ impl std::fmt::Display for Location {
 }
 
 ////////////////////////////////////////////////////////////////////////////////
+
+// a location on a deck (map) at a given height layer above its floor
+#[derive(Clone,Copy,Default,Eq,PartialEq,Ord,PartialOrd,Hash,serde::Serialize,serde::Deserialize)]
+pub struct Location3d {
+    pub p: Point3d,
+    pub m: MapId,
+}
+
+impl Location3d {
+    pub fn new(p:Point3d, m:MapId) -> Self {
+        Location3d { p, m }
+    }
+
+    pub fn height(&self) -> i32 {
+        self.p.z
+    }
+
+    // location on the deck directly beneath (ie ignoring height)
+    pub fn location(&self) -> Location {
+        Location { p:self.p.xy(), m:self.m }
+    }
+}
+
+impl From<Location> for Location3d { fn from(l:Location) -> Location3d { l.with_height(0) } }
+
+impl std::fmt::Debug for Location3d {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "L{}@{}", self.p, self.m)
+    }
+}
+
+impl std::fmt::Display for Location3d {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "L{}@{}", self.p, self.m)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heights() {
+        let l = Location::new(Point2d::new(3,-4), MapId::default());
+        let l3 = l.with_height(2);
+        assert_eq!(l3, Location3d::new(Point3d::new(3,-4,2), l.m));
+        assert_eq!(l3.height(), 2);
+        assert_eq!(l3.location(), l);
+        assert_eq!(Location3d::from(l).height(), 0);
+        assert_eq!(Location3d::from(l).location(), l);
+        assert_ne!(l3, l.with_height(1));
+        assert_eq!(format!("{}", l3), "L(3,-4,2)@".to_string() + &format!("{}", l.m));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
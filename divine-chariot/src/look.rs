@@ -0,0 +1,273 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use std::rc::{Rc};
+
+use crate::core_systems::*;
+use crate::dialog::{Layer, Response};
+use crate::entity::{EntityId};
+use crate::handle::{Handle};
+use crate::keymap::{Command, Context, KeyChord, Keymap};
+use crate::map_view::{MapViewWindow};
+use crate::mouse::{MouseButton, MouseInput};
+use crate::point2d::*;
+use crate::rect2d::*;
+use crate::renderer::{InputEvent};
+use crate::text::{RichText};
+use crate::widgets::{Align, Frame, Style};
+use crate::window::{WindowHandle};
+
+////////////////////////////////////////////////////////////////////////////////
+
+// markup describing a map cell's terrain
+pub type DescribeCellFn<C> = Box<dyn Fn(&C)->String>;
+// markup describing an entity or zone, from its data (None to leave it out)
+pub type DescribeEntityFn = Box<dyn Fn(EntityId)->Option<String>>;
+
+// where to put a panel of the given size near the cursor, within bounds, without
+// covering the cursor: below-right by preference, else below-left, above-right,
+// above-left, and failing those (bounds too small) as far from it as fits
+pub fn place_tooltip(size:Point2d, cursor:Point2d, bounds:Rect2d) -> Point2d {
+    let (right, left) = (cursor.x + 2, cursor.x - 1 - size.x);
+    let (below, above) = (cursor.y + 1, cursor.y - size.y);
+    for (x, y) in [(right, below), (left, below), (right, above), (left, above)] {
+        let r = Rect2d::new(Point2d::new(x, y), Point2d::new(x, y) + size);
+        if bounds.contains_rect(&r) { return r.bl; }
+    }
+    // (if bigger than the bounds, at least its top-left is kept on screen)
+    let far = |c:i32, lo:i32, hi:i32, n:i32| if c - lo > hi - c { lo } else { (hi - n).max(lo) };
+    Point2d::new(far(cursor.x, bounds.bl.x, bounds.tr.x, size.x), far(cursor.y, bounds.bl.y, bounds.tr.y, size.y))
+}
+
+// moves a cursor over a map view by keyboard (the keymap's targeting bindings)
+// or mouse, with a tooltip listing what's there (terrain, entities, zones);
+// the view follows the cursor
+pub struct LookMode<C:Clone> {
+    view: Handle<MapViewWindow<C>>,
+    overlay: WindowHandle<Glyph>,       // transparent, over the whole screen
+    cursor_window: WindowHandle<Glyph>,
+    tooltip: Option<Frame>,
+    placed: Option<(Point2d,Point2d)>,  // cursor (map & screen) the tooltip was made for
+    cursor: Point2d,                    // map coordinates
+    max_width: i32,
+    describe_cell: DescribeCellFn<C>,
+    describe_entity: DescribeEntityFn,
+    keymap: Rc<Keymap>,
+}
+
+impl<C:Clone+'static> LookMode<C> {
+    pub fn new(view:&Handle<MapViewWindow<C>>, start:Point2d,
+        describe_cell:DescribeCellFn<C>, describe_entity:DescribeEntityFn, keymap:Rc<Keymap>) -> Self
+    {
+        let root = view.borrow().window().root();
+        let overlay = root.new_child("LookMode".into(), Point2d::new(0,0), root.span());
+        overlay.set_default(Glyph::transparent());
+        overlay.fill(Glyph::transparent());
+        overlay.set_blend(BlendMode::Normal);
+        let cursor_window = overlay.new_child("LookCursor".into(), Point2d::new(0,0), Rect2d::new(Point2d::new(0,0), Point2d::new(1,1)));
+        let mut res = LookMode { view:view.clone(), overlay, cursor_window, tooltip:None, placed:None, cursor:start,
+            max_width:32, describe_cell, describe_entity, keymap };
+        res.move_to(start);
+        res
+    }
+
+    pub fn cursor(&self) -> Point2d {
+        self.cursor
+    }
+
+    // (cursor kept on the map)
+    pub fn move_to(&mut self, p:Point2d) {
+        let span = self.view.borrow().map().borrow().span();
+        self.cursor = Rect2d::clamp(&span, p);
+        self.view.borrow_mut().set_focus(Some(self.cursor));
+        self.update();
+    }
+
+    // markup lines for what's at p
+    pub fn describe(&self, p:Point2d) -> Vec<String> {
+        let view = self.view.borrow();
+        let map = view.map().borrow();
+        let mut lines = vec![match map.cell(p) {
+            Some(c) => (self.describe_cell)(c),
+            None => "[fg=granite_gray]nothing[/fg]".to_string(),
+        }];
+        if let Some(es) = map.entities_at(p) {
+            lines.extend(es.iter().filter_map(|e| (self.describe_entity)(*e)));
+        }
+        if let Some(mut zs) = map.zones_at(p) {
+            zs.sort();
+            let names : Vec<String> = zs.into_iter().filter_map(|z| (self.describe_entity)(z)).collect();
+            if !names.is_empty() {
+                lines.push(format!("[fg=granite_gray]in[/fg] {}", names.join(", ")));
+            }
+        }
+        lines
+    }
+}
+
+impl<C:Clone+'static> Layer for LookMode<C> {
+    fn window(&self) -> &WindowHandle<Glyph> {
+        &self.overlay
+    }
+
+    fn handle(&mut self, ev:&InputEvent) -> Response {
+        match *ev {
+            // (running moves faster)
+            InputEvent::Key { .. } => {
+                let chord = KeyChord::from_event(ev).unwrap();
+                match self.keymap.command(Context::Targeting, chord) {
+                    Some(Command::Move(d)) => { self.move_to(self.cursor + d.delta()); }
+                    Some(Command::Run(d)) => { self.move_to(self.cursor + d.delta() * 8); }
+                    Some(Command::Select) | Some(Command::Cancel) => { return Response::Close; }
+                    _ => {}
+                }
+            }
+            InputEvent::Mouse(MouseInput::Move(p)) => {
+                let q = self.view.borrow().screen_to_map(p);
+                if let Some(q) = q { self.move_to(q); }
+            }
+            InputEvent::Mouse(MouseInput::Press(MouseButton::Right, _, _)) => {
+                return Response::Close;
+            }
+            InputEvent::Mouse(_) | InputEvent::Text(_) => {}
+            InputEvent::Resize(size) => {
+                self.overlay.set_size(size);
+                return Response::Pass;
+            }
+            InputEvent::Close => { return Response::Pass; }
+        }
+        Response::Consume
+    }
+
+    fn blur(&mut self) {
+        self.view.borrow_mut().set_focus(None);
+    }
+
+    fn focus(&mut self) {
+        self.view.borrow_mut().set_focus(Some(self.cursor));
+    }
+
+    // (re)places the cursor & tooltip, as the view may have moved beneath them
+    fn update(&mut self) {
+        self.overlay.set_size(self.overlay.root().size());
+        let (screen, below, bounds) = {
+            let view = self.view.borrow();
+            let w = view.window();
+            let bounds = Rect2d::new(w.to_screen(Point2d::new(0,0)), w.to_screen(w.size()));
+            let screen = view.map_to_screen(self.cursor);
+            (screen, screen.and_then(|p| w.get(w.to_local(p))), bounds)
+        };
+        let (screen, below) = match (screen, below) {
+            (Some(p), Some(g)) => (p, g),
+            _ => {
+                self.cursor_window.set_hidden(true);
+                self.tooltip = None;
+                self.placed = None;
+                return;
+            }
+        };
+        // (shown in reverse, on gold)
+        self.cursor_window.set_hidden(false);
+        self.cursor_window.set_offset(screen);
+        self.cursor_window.set(Point2d::new(0,0), Glyph::new(below.ch, Color::black(), Color::gold()));
+        if self.placed == Some((self.cursor, screen)) { return; }
+        self.placed = Some((self.cursor, screen));
+        self.tooltip = None;
+        let style = Style::default();
+        let texts : Vec<RichText> = self.describe(self.cursor).iter()
+            .map(|l| RichText::parse(l, style.fg, style.bg)).collect();
+        let title = format!("{}", self.cursor);
+        let w = texts.iter().map(|t| t.width()).max().unwrap_or(1)
+            .max(title.chars().count() as i32 + 4)
+            .clamp(8, self.max_width);
+        let h : i32 = texts.iter().map(|t| t.lines(w, None).len() as i32).sum();
+        let size = Point2d::new(w + 2, h + 2);
+        let at = place_tooltip(size, screen, bounds);
+        let frame = Frame::new(&self.overlay, "LookTooltip", at, size, &title);
+        let mut y = 1;
+        for t in texts.iter() {
+            y += t.draw(frame.window(), Point2d::new(1, y), w, Align::Left, None);
+        }
+        self.tooltip = Some(frame);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{RefCell};
+    use bear_lib_terminal::terminal::{KeyCode};
+    use crate::dialog::{LayerStack};
+    use crate::entity::{EntityManagerHandle};
+    use crate::map::{Map};
+    use crate::renderer::{HeadlessRenderer, Renderer};
+    use crate::time_manager::{TimeManager};
+
+    #[test]
+    fn tooltip_placement() {
+        let bounds = Rect2d::new(Point2d::new(0,0), Point2d::new(40,20));
+        let size = Point2d::new(10,4);
+        assert_eq!(place_tooltip(size, Point2d::new(5,5), bounds), Point2d::new(7,6));
+        assert_eq!(place_tooltip(size, Point2d::new(35,5), bounds), Point2d::new(24,6));
+        assert_eq!(place_tooltip(size, Point2d::new(5,18), bounds), Point2d::new(7,14));
+        assert_eq!(place_tooltip(size, Point2d::new(35,18), bounds), Point2d::new(24,14));
+        // no room anywhere: as far as possible from the cursor
+        let at = place_tooltip(Point2d::new(30,18), Point2d::new(5,5), bounds);
+        assert_eq!(at, Point2d::new(10,2));
+        // and too big for the bounds, clamped to them
+        let bounds = Rect2d::new(Point2d::new(3,2), Point2d::new(40,20));
+        assert_eq!(place_tooltip(Point2d::new(50,30), Point2d::new(5,4), bounds), Point2d::new(3,2));
+        assert_eq!(place_tooltip(Point2d::new(50,30), Point2d::new(38,18), bounds), Point2d::new(3,2));
+    }
+
+    #[test]
+    fn looking_around() {
+        let mut r = HeadlessRenderer::new(Point2d::new(30,14));
+        let root = WindowHandle::new("Root".into(), Rect2d::new(Point2d::new(0,0), r.size()));
+        let ids = EntityManagerHandle::new();
+        let (player, drone, bay) = (ids.new_id(), ids.new_id(), ids.new_id());
+        let mut map = Map::new("test", Rect2d::new(Point2d::new(0,0), Point2d::new(60,30)), 0u8);
+        map.set_entity_position(player, Point2d::new(5,5));
+        map.set_entity_position(drone, Point2d::new(6,5));
+        map.set_zone(bay, Rect2d::new(Point2d::new(0,0), Point2d::new(8,8)));
+        let map = Rc::new(RefCell::new(map));
+        let view = Handle::new_from(MapViewWindow::new(&root, Point2d::new(0,0), Point2d::new(30,14), map,
+            player, Box::new(|_| Glyph::new('.', Color::grey(), Color::black()))));
+        let names = move |e:EntityId| Some(if e == drone { "[fg=iron]drone[/fg]" } else if e == bay { "bay" } else { "you" }.to_string());
+        let look = LookMode::new(&view, Point2d::new(5,5), Box::new(|_| "deck".to_string()), Box::new(names),
+            Rc::new(Keymap::default()));
+        assert_eq!(look.describe(Point2d::new(5,5)), vec!["deck", "you", "[fg=granite_gray]in[/fg] bay"]);
+        let mut time = TimeManager::new(60);
+        let mut layers = LayerStack::new();
+        layers.push(Box::new(look), &mut time);
+        r.push_keys(&[KeyCode::Right]);
+        while let Some(ev) = r.poll_event() {
+            assert!(layers.handle(&ev, &mut time));
+        }
+        layers.update();
+        r.draw_window(&root);
+        // cursor on the drone at (6,5), the tooltip below & to its right
+        let g = r.screen().get(Point2d::new(6,5)).copied().unwrap();
+        assert_eq!((g.ch, g.bg), ('.', Color::gold()));
+        assert_eq!(r.row_text(6).trim_end_matches('.'), "........┌─ (6,5) ─┐");
+        assert_eq!(r.row_text(7).trim_end_matches('.'), "........│deck     │");
+        assert_eq!(r.row_text(8).trim_end_matches('.'), "........│drone    │");
+        assert_eq!(r.row_text(9).trim_end_matches('.'), "........│in bay   │");
+        assert_eq!(r.screen().get(Point2d::new(9,8)).unwrap().fg, Color::iron());
+        // moving far off scrolls the view after the cursor, & escape hands it back to the player
+        r.push_input(InputEvent::Key { key:KeyCode::Right, ctrl:false, shift:true, alt:false });
+        r.push_input(InputEvent::Key { key:KeyCode::Right, ctrl:false, shift:true, alt:false });
+        r.push_input(InputEvent::Key { key:KeyCode::Right, ctrl:false, shift:true, alt:false });
+        while let Some(ev) = r.poll_event() { layers.handle(&ev, &mut time); }
+        assert!(view.borrow().view_rect().contains(Point2d::new(30,5)));
+        assert!(!view.borrow().view_rect().contains(Point2d::new(5,5)));
+        layers.handle(&InputEvent::key(KeyCode::Escape), &mut time);
+        assert!(layers.is_empty());
+        view.borrow_mut().update();
+        assert!(view.borrow().view_rect().contains(Point2d::new(5,5)));
+        assert!(root.find_by_id("LookMode").is_none());
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
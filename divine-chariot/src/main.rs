@@ -4,45 +4,81 @@
 #![allow(unused_parens)]
 #![allow(unused_variables)]
 
+mod ansi;
 mod b64;
 mod core_systems;
+mod dialog;
+mod effects;
 mod entity;
+mod game;
 mod grid;
+mod grid_serde;
 mod handle;
 mod hash;
+mod keymap;
+mod layout;
 mod location;
+mod look;
 mod map;
+mod map_view;
+mod minimap;
+mod mouse;
 mod paths;
 mod perlin;
 mod point2d;
+mod point3d;
 mod priority_queue;
+mod recording;
 mod rect2d;
+mod region;
+mod replay;
+mod renderer;
 mod resource;
 mod rng;
+mod text;
+mod tileset;
 mod time_manager;
 mod value;
+mod widgets;
 mod window;
 
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::rc::{Rc};
+
 use bear_lib_terminal::{geometry,terminal};
 
+use crate::ansi::{AnsiRenderer, ColorMode};
 use crate::core_systems::*;
+use crate::dialog::{ConfirmDialog, LayerStack, PromptDialog, TextDialog};
+use crate::effects::{Effects, Explosion, FloatingText, Projectile};
 use crate::entity::{EntityId, EntityManagerHandle};
+use crate::game::{Game};
 use crate::grid::{Grid};
 use crate::handle::{Handle};
+use crate::keymap::{Command, Context, KeyChord, Keymap, Preset};
+use crate::look::{LookMode};
+use crate::layout::{Constraint, Layout};
+use crate::map::{Map};
+use crate::map_view::{MapViewWindow};
+use crate::minimap::{Minimap};
+use crate::mouse::{MouseButton, MouseEvent, MouseEventKind, MouseInput, MouseRouter};
 use crate::perlin::{Perlin};
 use crate::point2d::*;
+use crate::recording::{Recording, RecordingRenderer};
+use crate::replay::{CommandLog, GameCommand, Simulation};
 use crate::rect2d::*;
+use crate::renderer::{BltRenderer, InputEvent, Renderer};
 use crate::rng::{Rnd, Generator, Rng, Sampler};
+use crate::text::{RichText};
+use crate::tileset::{TilesetManager};
 use crate::time_manager::{TimeManager};
+use crate::value::{Value};
+use crate::widgets::{Align, History, MessageLog};
 use crate::window::{WindowHandle};
 
 ////////////////////////////////////////////////////////////////////////////////
 
-pub struct FontConfig(pub String);
-impl terminal::config::ConfigPart for FontConfig {
-  fn to_config_str(&self) -> String { self.0.clone() }
-}
-
 pub fn window_write_str(window:&WindowHandle<Glyph>, s:&str, p:Point2d, fg:Color, bg:Color) {
   let mut p = p;
   for ch in s.chars() {
@@ -53,97 +89,305 @@ pub fn window_write_str(window:&WindowHandle<Glyph>, s:&str, p:Point2d, fg:Color
 
 ////////////////////////////////////////////////////////////////////////////////
 
-pub struct MapViewWindow {
-  window : WindowHandle<Glyph>,
-  span : Rect2d,
-  eid : EntityId,
-  perlin : Perlin,
+// rolling terrain from perlin noise: 0 (low) to 7 (high), with 6 & 7 impassable
+pub fn demo_map(span:Rect2d) -> Map<u8> {
+  let perlin = Perlin::new();
+  let mut map = Map::new("Deck", span, 0u8);
+  for p in span {
+    let v = perlin.octave_perlin(
+        ((p.x - span.bl.x) as f64)/80.0*3.0,
+        ((p.y - span.bl.y) as f64)/30.0*3.0,
+        0.0, 2, 0.5).powi(2);
+    map.set_cell(p, ((v * 256.0).floor().min(255.0) as u8)/32);
+  }
+  map
 }
 
-impl MapViewWindow {
-  pub fn new(parent:&WindowHandle<Glyph>, offset:Point2d, span:Rect2d, eid:EntityId) -> Self {
-    let window = parent.new_child(format!("MapView[{}]",eid), offset, span);
-    let perlin = Perlin::new();
-    let mut res = MapViewWindow { window, span, eid, perlin, };
-    res.update(0);
-    res
-  }
-
-  pub fn update(&mut self, frame:usize) {
-    if frame % 5 == 0 {
-      for mwp in self.span {
-        let z = frame as f64/120.0;
-        let v = self.perlin.octave_perlin(
-            ((self.span.bl.x + mwp.x) as f64)/self.span.size().x as f64*3.0,
-            ((self.span.bl.y + mwp.y) as f64)/self.span.size().y as f64*3.0,
-            z, 2, 0.5).powi(2);
-        let v = (v * 256.0).floor().min(255.0).floor() as u8;
-        let v = (v/32)*32;
-        self.window.set(mwp, Glyph::new(' ',Color::jade(),Color::rgb(v,v,v)));
-      }
-    }
-  }
+pub fn terrain_name(v:&u8) -> &'static str {
+  ["low deck", "deck", "deck", "raised deck", "ramp", "high deck", "bulkhead", "bulkhead"][*v as usize]
+}
+
+// per-entity properties: "name", "description", "glyph" (char), "color" (palette name)
+pub type EntityData = HashMap<EntityId,HashMap<String,Value>>;
+
+pub fn entity_data(name:&str, description:&str, glyph:char, color:&str) -> HashMap<String,Value> {
+  let mut m = HashMap::new();
+  m.insert("name".to_string(), Value::from(name));
+  m.insert("description".to_string(), Value::from(description));
+  m.insert("glyph".to_string(), Value::from(glyph));
+  m.insert("color".to_string(), Value::from(color));
+  m
 }
 
-impl Drop for MapViewWindow {
-  fn drop(&mut self) {
-    self.window.demolish();
+pub fn terrain_glyph(v:&u8) -> Glyph {
+  let g = v*32;
+  if *v >= 6 { Glyph::new('#', Color::granite_gray(), Color::rgb(g,g,g)) }
+  else { Glyph::new(' ', Color::jade(), Color::rgb(g,g,g)) }
+}
+
+// shaded frame around the edge of the terminal (and down its left side)
+pub fn draw_border(window:&WindowHandle<Glyph>) {
+  let span = window.span();
+  let mdd = ((span.size().x*span.size().x + span.size().y*span.size().y) as f64).sqrt();
+  let col = |x,y| {
+      let dx = x - span.bl.x;
+      let dy = y - span.bl.y;
+      let d = (((dx*dx + dy*dy) as f64).sqrt() / mdd);
+      Color::mistyrose().lint(&Color::rose_garnet(), d)
+      //Color::rose_garnet().lint(&Color::cadmium_blue(), d)
+      //Color::iron().lint(&Color::jade(), d)
+      //Color::english_lavender().lint(&Color::jade(), d)
+    };
+  for p in span.boundary_iter() {
+    window.set(p, Glyph::new('█',col(p.x,p.y),Color::black()));
+  }
+  for y in span.bl.y .. span.tr.y {
+    //let x = base_window.span().bl.x + map_window_offset.x + map_window_size.x;
+    let x = span.bl.x + 0 + 0;
+    window.set(Point2d::new(x,y), Glyph::new('█',col(x,y),Color::black()));
   }
 }
 
+// follows a terminal resize: the border is redrawn on a blank window (as the old
+// one may now be inside it), and the layout reapplied within it
+pub fn resize_terminal(window:&WindowHandle<Glyph>, layout:&mut Layout, size:Point2d) {
+  window.set_size(size);
+  window.fill(window.get_default());
+  draw_border(window);
+  layout.apply(window.span().inset(1));
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 pub fn main() {
   let terminal_span = Rect2d::new(Point2d::new(0,0), Point2d::new(100,40));
-  terminal::open("Test", terminal_span.tr.x as u32, terminal_span.tr.y as u32);
-  terminal::set(terminal::config::Window::empty()
-    .title("Divine-Chariot".to_string())
-    .size(geometry::Size::new(terminal_span.tr.x, terminal_span.tr.y))
-    .cellsize(terminal::config::Cellsize::Auto));
-  terminal::set(vec![terminal::config::InputFilter::Group{group: terminal::config::InputFilterGroup::Keyboard, both: false}]);
-  //"font: default;"
-  terminal::set(FontConfig("font: ./DejaVuSansMono.ttf, size=14;".into()));
-
   println!("Hello, world!");
 
+  // --ansi to play in a plain terminal (ie over ssh) rather than a BearLibTerminal window,
+  // --flat to draw it all on one layer there
+  // --record FILE to record the session, --play FILE to replay one,
+  // --asciicast FILE OUT to convert a recording for sharing
+  // --vi to add vi keys to the default bindings (which keys.json then rebinds over),
+  // --save-keys FILE to write out the bindings (ie to edit into keys.json)
+  // --log-commands FILE to log the player's commands (for bug reports), --replay FILE
+  // to play such a log back (checking it ends the same)
+  // --font FILE & --font-size N for another text font, --tileset FILE WxH to load
+  // (256) tiles into the private use area
+  let args : Vec<String> = std::env::args().collect();
+  let arg_value = |name:&str, i:usize| args.iter().position(|a| a == name).and_then(|n| args.get(n+1+i)).cloned();
+
+  // (keys.json rebinds just the chords it names; if it won't load, the defaults are kept)
+  let keys_path = std::path::Path::new("keys.json");
+  let mut keymap = if args.iter().any(|a| a == "--vi") {
+      Keymap::with_presets(&[Preset::Arrows, Preset::Numpad, Preset::Vi])
+    } else {
+      Keymap::default()
+    };
+  let keys_error = match Keymap::load(keys_path) {
+      Ok(loaded) => { keymap.merge(&loaded); None }
+      Err(e) if keys_path.exists() => Some(e),
+      Err(_) => None,
+    };
+  let keymap = Rc::new(keymap);
+  if let Some(path) = arg_value("--save-keys", 0) {
+    keymap.save(std::path::Path::new(&path)).expect("cannot save key bindings");
+    return;
+  }
+
+  if let (Some(rec), Some(out)) = (arg_value("--asciicast", 0), arg_value("--asciicast", 1)) {
+    let rec = Recording::load(std::path::Path::new(&rec)).expect("cannot load recording");
+    let mut out = std::io::BufWriter::new(std::fs::File::create(out).expect("cannot create output"));
+    rec.write_asciicast(&mut out).expect("cannot write asciicast");
+    return;
+  }
+
+  let mut fonts = TilesetManager::new(
+    std::path::Path::new(&arg_value("--font", 0).unwrap_or("./DejaVuSansMono.ttf".into())),
+    arg_value("--font-size", 0).and_then(|n| n.parse().ok()).unwrap_or(14));
+  if let (Some(path), Some(size)) = (arg_value("--tileset", 0), arg_value("--tileset", 1)) {
+    let size = size.split_once('x').and_then(|(w, h)| Some(Point2d::new(w.parse().ok()?, h.parse().ok()?)))
+      .expect("tile size should be WxH");
+    fonts.add("tiles", std::path::Path::new(&path), size, 256).expect("cannot add tileset");
+  }
+
+  let mut renderer : Box<dyn Renderer> =
+    if args.iter().any(|a| a == "--ansi") {
+      Box::new(AnsiRenderer::open(ColorMode::detect()).expect("cannot open terminal"))
+    } else {
+      let r = BltRenderer::open("Divine-Chariot", terminal_span.size()).expect("cannot open terminal")
+        .with_layers(!args.iter().any(|a| a == "--flat"));
+      // (BearLibTerminal keeps its built-in font for any that fail)
+      if let Err(es) = fonts.apply() { es.iter().for_each(|e| eprintln!("{}", e)); }
+      Box::new(r)
+    };
+
+  if let Some(rec) = arg_value("--play", 0) {
+    let rec = Recording::load(std::path::Path::new(&rec)).expect("cannot load recording");
+    recording::play(&rec, renderer.as_mut(), 1.0);
+    return;
+  }
+  if let Some(path) = arg_value("--record", 0) {
+    renderer = Box::new(RecordingRenderer::new(renderer).saving_to(std::path::Path::new(&path)));
+  }
+
   let entity_manager = EntityManagerHandle::new();
   let player_id = entity_manager.new_id();
 
-  //let z = terminal_span.size().y - 2;
-  //let map_window_span = Rect2d::new(Point2d::new(-z/2,-z/2),Point2d::new(z/2,z/2));
-  let map_window_span = Rect2d::new(Point2d::new(-40,-15),Point2d::new(40,15));
-  let map_window_offset = Point2d::new(1,1);
-  let map_window_size = map_window_span.size();
+  let map = Rc::new(RefCell::new(demo_map(Rect2d::new(Point2d::new(-100,-40),Point2d::new(100,40)))));
+  {
+    let mut map = map.borrow_mut();
+    let start = map.span().into_iter()
+      .filter(|p| *map.cell(*p).unwrap() < 6)
+      .min_by_key(|p| p.chebyshev(Point2d::new(0,0)))
+      .unwrap();
+    map.set_entity_position(player_id, start);
+  }
+  // (a replay's seed is the logged one)
+  let replay = arg_value("--replay", 0).map(|path| CommandLog::load(std::path::Path::new(&path)).expect("cannot load command log"));
+  let seed = match &replay {
+    Some(log) => log.seed,
+    None => {
+      let t = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap();
+      [t.subsec_nanos() | 1, t.as_secs() as u32, 0x97830e05, 0x113ba7bb]
+    }
+  };
+  let mut game = Game::new(map.clone(), player_id, seed, Box::new(|v| *v < 6));
+  let mut replayed = 0;
+
+  let entities : Rc<RefCell<EntityData>> = Rc::new(RefCell::new(HashMap::new()));
+  {
+    let mut map = map.borrow_mut();
+    let mut data = entities.borrow_mut();
+    let start = map.entity_position(player_id).unwrap();
+    data.insert(player_id, entity_data("you", "a stowaway", '@', "pearl"));
+    let drone = entity_manager.new_id();
+    map.set_entity_position(drone, start + Point2d::new(3,1));
+    data.insert(drone, entity_data("maintenance drone", "humming to itself", 'd', "iron"));
+    let crate_id = entity_manager.new_id();
+    map.set_entity_position(crate_id, start + Point2d::new(-2,2));
+    data.insert(crate_id, entity_data("crate", "stencilled RATIONS", '=', "bronze_metallic"));
+    game.add_wanderer(drone);
+    let bay = entity_manager.new_id();
+    map.set_zone(bay, Rect2d::new(start - Point2d::new(12,6), start + Point2d::new(12,6)));
+    data.insert(bay, entity_data("Hydroponics Bay", "", ' ', "mint"));
+  }
 
   let base_window : WindowHandle<Glyph> = WindowHandle::new("Terminal".into(), terminal_span);
-  let mut mapview_window = MapViewWindow::new( &base_window, Point2d::new(1,1), map_window_span, player_id);
-  base_window.dump_tree();
+  let mapview_window = Handle::new_from(MapViewWindow::new(&base_window, Point2d::new(1,1), Point2d::new(81,31),
+    map.clone(), player_id, Box::new(terrain_glyph)));
+  mapview_window.borrow_mut().set_deadzone(Point2d::new(10,5));
   {
-    let mdd = ((terminal_span.size().x*terminal_span.size().x + terminal_span.size().y*terminal_span.size().y) as f64).sqrt();
-    let col = |x,y| {
-        let dx = x - terminal_span.bl.x;
-        let dy = y - terminal_span.bl.y;
-        let d = (((dx*dx + dy*dy) as f64).sqrt() / mdd);
-        Color::mistyrose().lint(&Color::rose_garnet(), d)
-        //Color::rose_garnet().lint(&Color::cadmium_blue(), d)
-        //Color::iron().lint(&Color::jade(), d)
-        //Color::english_lavender().lint(&Color::jade(), d)
-      };
-    for p in terminal_span.boundary_iter() {
-      base_window.set(p, Glyph::new('█',col(p.x,p.y),Color::black()));
-    }
-    for y in terminal_span.bl.y .. terminal_span.tr.y {
-      //let x = base_window.span().bl.x + map_window_offset.x + map_window_size.x;
-      let x = base_window.span().bl.x + 0 + 0;
-      base_window.set(Point2d::new(x,y), Glyph::new('█',col(x,y),Color::black()));
-    }
+    let entities = entities.clone();
+    mapview_window.borrow_mut().set_entity_glyphs(Box::new(move |e, below| {
+      let data = entities.borrow();
+      let d = data.get(&e)?;
+      let fg = d.get("color").and_then(|c| c.as_s()).and_then(|c| Color::named(c)).unwrap_or(Color::pearl());
+      Some(Glyph::new(d.get("glyph")?.as_c()?, fg, below.bg))
+    }));
+  }
+  let effects = Handle::new_from(Effects::new(&mapview_window));
+  let mut minimap = Minimap::new(&base_window, Point2d::new(83,1), Point2d::new(16,8),
+    map.clone(), player_id, Box::new(|v| terrain_glyph(v).bg));
+  let message_log = Handle::new_from(MessageLog::new(&base_window, "MessageLog", Point2d::new(1,32), Point2d::new(81,7), 200));
+  message_log.borrow_mut().push("Welcome aboard.", Color::mistyrose());
+  if let Some(e) = keys_error {
+    message_log.borrow_mut().push(&format!("Cannot load keys.json ({}); using the default keys.", e), Color::maximum_red());
+  }
+  for c in keymap.conflicts() {
+    let cmds : Vec<String> = c.commands.iter().map(|cmd| cmd.name()).collect();
+    message_log.borrow_mut().push(&format!("Key {} is bound to {} (in {}); only the first applies.",
+      c.chord, cmds.join(", "), c.context.name()), Color::gold());
   }
 
-  let mut quit = false;
+  let mut mouse_router : MouseRouter<Glyph> = MouseRouter::new();
+  {
+    let log = message_log.clone();
+    let view = mapview_window.clone();
+    let effects = effects.clone();
+    let view_window = mapview_window.borrow().window().clone();
+    mouse_router.set_handler(&view_window, Box::new(move |ev:&MouseEvent| {
+      match ev.kind {
+        MouseEventKind::Click(MouseButton::Left, _) => {
+          let view = view.borrow();
+          let mp = view.local_to_map(ev.pos);
+          let what = match view.map().borrow().cell(mp) { Some(v) if *v >= 6 => "rock", Some(_) => "open ground", None => "nothing" };
+          log.borrow_mut().push(&format!("You see {} at {}.", what, mp), Color::azure_mist());
+          true
+        }
+        // (a demo of the effects: the turn waits for the bolt to land)
+        MouseEventKind::Click(MouseButton::Right, _) => {
+          let view = view.borrow();
+          let (from, to) = (view.map().borrow().entity_position(view.followed()).unwrap(), view.local_to_map(ev.pos));
+          let bolt = Projectile::new(from, to, Glyph::new('*', Color::saffron(), Color::transparent()));
+          let landing = bolt.flight();
+          let mut effects = effects.borrow_mut();
+          effects.play_blocking(Box::new(bolt));
+          effects.play_after(landing, Box::new(Explosion::new(to, 3)), true);
+          effects.play_after(landing, Box::new(FloatingText::damage(to, 1 + (to.x + to.y).rem_euclid(9))), false);
+          true
+        }
+        _ => false,
+      }
+    }));
+    let log = message_log.clone();
+    let log_window = message_log.borrow().window().clone();
+    mouse_router.set_handler(&log_window, Box::new(move |ev:&MouseEvent| {
+      match ev.kind {
+        MouseEventKind::Scroll(d) => { log.borrow_mut().scroll_by(-d); true }
+        _ => false,
+      }
+    }));
+  }
+
+  let glossary = base_window.new_child("Glossary".into(), Point2d::new(0,0), Rect2d::new(Point2d::new(0,0), Point2d::new(1,1)));
+  let draw_glossary = {
+    let glossary = glossary.clone();
+    move |size:Point2d| {
+      glossary.fill(Glyph::new(' ', Color::pearl(), Color::white().scale(0.1)));
+      let mut y = 0;
+      for entry in [
+          "[fg=gold]ḫurāṣum[/fg] gold",
+          "[fg=iron]kaspum[/fg] silver; price, money",
+          "[fg=iron]ṣarpum[/fg] silver",
+          "[fg=mint]ḫaiāṭum[/fg] spy, surveyor, inspector, watchman, warden"] {
+        y += RichText::parse(entry, Color::pearl(), Color::white().scale(0.1))
+          .draw(&glossary, Point2d::new(0,y), size.x, Align::Left, Some((size.y - y).min(2)));
+      }
+    }
+  };
+
+  // map & log on the left, minimap at the top of a side column;
+  // reapplied within the border whenever the terminal is resized
+  let mut layout = {
+    let log = message_log.clone();
+    Layout::hsplit(1, vec![
+      (Constraint::fill().min(20), Layout::vsplit(0, vec![
+        (Constraint::fill().min(5), Layout::window(mapview_window.borrow().window())),
+        (Constraint::fixed(7).min(3), Layout::window_with(message_log.borrow().window(),
+          Box::new(move |_| log.borrow_mut().redraw()))),
+      ])),
+      (Constraint::fixed(16), Layout::vsplit(0, vec![
+        (Constraint::fixed(8), Layout::window(minimap.window())),
+        (Constraint::fill(), Layout::vsplit(0, vec![
+          (Constraint::fixed(1), Layout::Space),
+          (Constraint::fill(), Layout::window_with(&glossary, Box::new(draw_glossary))),
+        ])),
+      ])),
+    ])
+  };
+  layout.apply(base_window.span().inset(1));
+  base_window.dump_tree();
+  draw_border(&base_window);
+
+  let quit = Rc::new(Cell::new(false));
   let mut time_manager = TimeManager::new(61);
   time_manager.unpause();
+  let mut layers = LayerStack::new();
+  // commands from keys (or typed by name), carried out after each frame's input
+  let commands : Rc<RefCell<VecDeque<Command>>> = Rc::new(RefCell::new(VecDeque::new()));
+  let command_history : History = Handle::new_from(Vec::new());
   let mut n = 0;
-  while !quit {
+  while !quit.get() {
     //game.step();
     time_manager.tick();
 
@@ -166,47 +410,129 @@ pub fn main() {
       terminal::put_xy(termp.x, termp.y, ['.',',','X','O','*','#'][(rng.next_u32()%6) as usize]);
     }
     */
-    mapview_window.update(time_manager.frame_count());
-    base_window.update_data();
-    for termp in terminal_span {
-      let glyph = base_window.data(termp).unwrap();
-      terminal::set_background(glyph.bg.blt());
-      terminal::set_foreground(glyph.fg.blt());
-      terminal::put_xy(termp.x, termp.y, glyph.ch);
+    if let Some(log) = replay.as_ref().filter(|_| !time_manager.paused()) {
+      if replayed < log.commands.len() {
+        if let Err(e) = log.replay_one(replayed, &mut game) {
+          message_log.borrow_mut().push(&format!("Replay failed: {}.", e), Color::maximum_red());
+          replayed = log.commands.len();
+        } else {
+          replayed += 1;
+          if replayed == log.commands.len() {
+            let (hash, ok) = (game.state_hash(), log.final_hash.is_none_or(|h| h == game.state_hash()));
+            message_log.borrow_mut().push(&format!("Replay finished, state {:08x} ({}).", hash,
+              if ok { "as logged" } else { "NOT as logged" }), if ok { Color::mint() } else { Color::maximum_red() });
+          }
+        }
+      }
     }
+    mapview_window.borrow_mut().update();
+    effects.borrow_mut().update(time_manager.frame_count(), renderer.cell_pixels());
+    minimap.update();
+    layers.update();
+    renderer.draw_window(&base_window);
 
-    terminal::set_background(Color::white().scale(0.1).blt());
-    terminal::set_foreground(Color::mistyrose().blt());
-    terminal::print_xy(80, 18, &format!("fps:{:.1}/{}", time_manager.average_fps(), time_manager.target_fps()));
-    //terminal::set_foreground(Color::azure_mist().blt());
-    //terminal::print_xy(80, 17, &format!("t:{}", game.now()));
-    //terminal::set_foreground(Color::parakeet_blue().blt());
-    //terminal::print_xy(80, 16, &format!("{:?}", game.state()));
-    terminal::set_foreground(Color::mint().blt());
-    terminal::print_xy(80, 15, "ḫaiāṭum");
-    terminal::set_foreground(Color::gold().blt());
-    terminal::print_xy(80, 14, "ḫurāṣum");
-    terminal::set_foreground(Color::iron().blt());
-    terminal::print_xy(80, 13, "kaspum");
-
-    terminal::refresh();
-
-    if terminal::has_input() {
-      let ev = terminal::wait_event();
+    let fps_bg = Color::white().scale(0.1);
+    let side_x = base_window.size().x - 17;
+    renderer.print(Point2d::new(side_x, base_window.size().y - 2), &format!("fps:{:.1}/{}", time_manager.average_fps(), time_manager.target_fps()), Color::mistyrose(), fps_bg);
+    //renderer.print(Point2d::new(80, 17), &format!("t:{}", game.now()), Color::azure_mist(), fps_bg);
+    //renderer.print(Point2d::new(80, 16), &format!("{:?}", game.state()), Color::parakeet_blue(), fps_bg);
+
+    renderer.set_frame_time(time_manager.since_start());
+    renderer.refresh();
+
+    while let Some(ev) = renderer.poll_event() {
+      if layers.handle(&ev, &mut time_manager) { continue; }
       match ev {
-        Some(terminal::Event::KeyPressed{key:k,ctrl,shift,alt}) => {
-          match k {
-            terminal::KeyCode::Escape => { quit = true; }
-            terminal::KeyCode::Q => { quit = true; }
-            _ => {}
+        InputEvent::Mouse(mi) => { mouse_router.route(&base_window, mi); }
+        InputEvent::Key{..} => {
+          if let Some(cmd) = keymap.command(Context::Map, KeyChord::from_event(&ev).unwrap()) {
+            commands.borrow_mut().push_back(cmd);
+          }
+        }
+        InputEvent::Resize(size) => {
+          resize_terminal(&base_window, &mut layout, size);
+          renderer.clear();
+        }
+        InputEvent::Text(_) => {}
+        InputEvent::Close => { quit.set(true); }
+      }
+    }
+
+    // (once the frame's input is in, so the text of a key opening a prompt isn't typed into it;
+    // held while blocking effects play out)
+    let pending : Vec<Command> = if effects.borrow().blocking() { Vec::new() } else { commands.borrow_mut().drain(..).collect() };
+    for cmd in pending {
+      match cmd {
+        Command::Look => {
+          let entities = entities.clone();
+          let describe_entity = move |e:EntityId| {
+            let data = entities.borrow();
+            let d = data.get(&e)?;
+            let name = d.get("name")?.as_s()?.clone();
+            let color = d.get("color").and_then(|c| c.as_s()).cloned().unwrap_or("pearl".into());
+            Some(match d.get("description").and_then(|v| v.as_s()) {
+              Some(desc) if !desc.is_empty() => format!("[fg={}]{}[/fg]: {}", color, name, desc),
+              _ => format!("[fg={}]{}[/fg]", color, name),
+            })
+          };
+          let start = map.borrow().entity_position(player_id).unwrap();
+          layers.push(Box::new(LookMode::new(&mapview_window, start,
+            Box::new(|v| terrain_name(v).to_string()), Box::new(describe_entity), keymap.clone())), &mut time_manager);
+        }
+        Command::Help => {
+          layers.push(Box::new(TextDialog::new(&base_window, "Keys", &keymap.help_lines(Context::Map), keymap.clone())),
+            &mut time_manager);
+        }
+        Command::Quit => {
+          let q = quit.clone();
+          layers.push(Box::new(ConfirmDialog::new(&base_window, "Quit", "Leave the [fg=gold]Divine Chariot[/fg]?",
+            Box::new(move |yes| q.set(yes)))), &mut time_manager);
+        }
+        // (running stops at the first obstacle; a replay takes no commands)
+        Command::Move(_) | Command::Run(_) | Command::Wait | Command::Pickup |
+          Command::Drop if replay.is_some() => {}
+        Command::Move(d) => { game.execute(GameCommand::Move(d)); }
+        Command::Run(d) => { while game.execute(GameCommand::Move(d)) {} }
+        Command::Wait => { game.execute(GameCommand::Wait); }
+        Command::Pickup => {
+          let at = map.borrow().entity_position(player_id).unwrap();
+          let here = map.borrow().entities_at(at).and_then(|es| es.iter().copied().find(|e| *e != player_id));
+          let name = |e:EntityId| entities.borrow().get(&e).and_then(|d| d.get("name")?.as_s().cloned()).unwrap_or("it".into());
+          match here {
+            Some(e) if game.execute(GameCommand::Pickup(e)) =>
+              message_log.borrow_mut().push(&format!("You pick up the {}.", name(e)), Color::azure_mist()),
+            _ => message_log.borrow_mut().push("There is nothing here.", Color::granite_gray()),
           }
         }
-        Some(terminal::Event::Close) => { quit = true; }
+        Command::Drop => {
+          if let Some(e) = game.inventory().last().copied() {
+            game.execute(GameCommand::Drop(e));
+          }
+        }
+        Command::ZoomIn => { minimap.zoom_in(); }
+        Command::ZoomOut => { minimap.zoom_out(); }
+        Command::LogOlder => { message_log.borrow_mut().handle_key(terminal::KeyCode::PageUp); }
+        Command::LogNewer => { message_log.borrow_mut().handle_key(terminal::KeyCode::PageDown); }
+        Command::LogLatest => { message_log.borrow_mut().scroll_to_end(); }
+        Command::Extended => {
+          let (queue, log) = (commands.clone(), message_log.clone());
+          let names = |word:&str| Command::all().map(|c| c.name()).filter(|n| n.starts_with(word)).collect();
+          layers.push(Box::new(PromptDialog::new(&base_window, "Command", "# ", 32, Box::new(move |line| {
+            match Command::from_name(line.trim()) {
+              Some(cmd) => queue.borrow_mut().push_back(cmd),
+              None if line.trim().is_empty() => {}
+              None => log.borrow_mut().push(&format!("Unknown command '{}'.", line.trim()), Color::granite_gray()),
+            }
+          })).with_history(command_history.clone()).with_completer(Box::new(names))), &mut time_manager);
+        }
         _ => {}
       }
     }
   }
-  terminal::close();
+  drop(renderer);
+  if let Some(path) = arg_value("--log-commands", 0) {
+    game.finished_log().save(std::path::Path::new(&path)).expect("cannot save command log");
+  }
 
   println!("Goodbye, world!");
 
@@ -217,3 +543,28 @@ pub fn main() {
   println!("ḫaiāṭum = a spy , a surveyor , an inspector , a watchman , a warden");
   println!("\u{cf88}  魔界");
 }
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn resize_redraws_border() {
+    let base = WindowHandle::new("Terminal".into(), Rect2d::new(Point2d::new(0,0), Point2d::new(10,6)));
+    let inner = base.new_child("Inner".into(), Point2d::new(1,1), Rect2d::new(Point2d::new(0,0), Point2d::new(1,1)));
+    let mut layout = Layout::window(&inner);
+    draw_border(&base);
+    resize_terminal(&base, &mut layout, Point2d::new(14,8));
+    let ch = |x, y| base.get(Point2d::new(x, y)).unwrap().ch;
+    // (the old right & bottom edges are gone, the new ones drawn)
+    assert_eq!((ch(9,3), ch(4,5)), (Glyph::default().ch, Glyph::default().ch));
+    assert_eq!((ch(13,3), ch(4,7), ch(0,3), ch(4,0)), ('█', '█', '█', '█'));
+    assert_eq!(inner.span().size(), Point2d::new(12,6));
+    resize_terminal(&base, &mut layout, Point2d::new(6,4));
+    assert_eq!((ch(5,2), ch(2,3), ch(2,2)), ('█', '█', Glyph::default().ch));
+  }
+}
+
+////////////////////////////////////////////////////////////////////////////////
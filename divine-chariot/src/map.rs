@@ -114,12 +114,16 @@ impl<C:Clone> MapManager<C> {
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Clone,Debug,serde::Serialize,serde::Deserialize)]
+#[serde(bound(serialize = "C:serde::Serialize+PartialEq", deserialize = "C:serde::Deserialize<'de>"))]
 pub struct Map<C:Clone> {
     name: String,
+    #[serde(with = "crate::grid_serde::palette")]
     cells: Grid<C>,
     entities: HashMap<EntityId,Point2d>,
     entities_at: HashMap<Point2d,Vec<EntityId>>,
     zones: HashMap<EntityId,Rect2d>,
+    #[serde(skip)]
+    revision: u64,      // bumped whenever cells may have changed (ie for caches of them)
 }
 
 impl<
@@ -131,7 +135,7 @@ impl<
         let entities = HashMap::new();
         let entities_at = HashMap::new();
         let zones = HashMap::new();
-        Map { name, cells, entities, entities_at, zones }
+        Map { name, cells, entities, entities_at, zones, revision:0 }
     }
 
     pub fn span(&self) -> Rect2d {
@@ -143,17 +147,31 @@ impl<
     }
 
     pub fn cell_mut(&mut self, p:Point2d) -> Option<&mut C> {
+        self.revision += 1;
         self.cells.get_mut(p)
     }
 
     pub fn set_cell(&mut self, p:Point2d, c:C) -> bool {
+        self.revision += 1;
         self.cells.set(p, c)
     }
 
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
     pub fn zone_span(&self, e:EntityId) -> Option<Rect2d> {
         self.zones.get(&e).cloned()
     }
 
+    pub fn set_zone(&mut self, e:EntityId, r:Rect2d) {
+        self.zones.insert(e, r);
+    }
+
+    pub fn remove_zone(&mut self, e:EntityId) {
+        self.zones.remove(&e);
+    }
+
     pub fn zones_at(&self, p:Point2d) -> Option<Vec<EntityId>> {
         let v : Vec<_> =
             self.zones.iter()
@@ -0,0 +1,227 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use std::cell::{RefCell};
+use std::rc::{Rc};
+
+use crate::core_systems::*;
+use crate::entity::{EntityId};
+use crate::map::{Map};
+use crate::point2d::*;
+use crate::rect2d::*;
+use crate::window::{WindowHandle};
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub type CellGlyphFn<C> = Box<dyn Fn(&C)->Glyph>;
+// glyph to draw an entity with, given the cell glyph beneath it (None to not draw it)
+pub type EntityGlyphFn = Box<dyn Fn(EntityId,&Glyph)->Option<Glyph>>;
+
+// view of a map, following an entity:
+//  - the view scrolls once the entity leaves the deadzone about the centre
+//    (a (0,0) deadzone keeps it centred),
+//  - but never shows beyond the map edges (maps smaller than the view are centred)
+pub struct MapViewWindow<C:Clone> {
+    window: WindowHandle<Glyph>,
+    map: Rc<RefCell<Map<C>>>,
+    eid: EntityId,
+    cell_glyph: CellGlyphFn<C>,
+    entity_glyph: EntityGlyphFn,
+    center: Point2d,                    // map point at the centre of the view
+    focus: Option<Point2d>,             // followed instead of the entity, if set
+    deadzone: Point2d,                  // half-extents
+}
+
+impl<C:Clone> MapViewWindow<C> {
+    pub fn new(parent:&WindowHandle<Glyph>, offset:Point2d, size:Point2d,
+        map:Rc<RefCell<Map<C>>>, eid:EntityId, cell_glyph:CellGlyphFn<C>) -> Self
+    {
+        let window = parent.new_child(format!("MapView[{}]",eid), offset, Rect2d::new(Point2d::new(0,0), size));
+        let center = map.borrow().entity_position(eid).unwrap_or(map.borrow().span().center());
+        let entity_glyph : EntityGlyphFn = Box::new(move |e, below| {
+            if e == eid { Some(Glyph::new('@', Color::pearl(), below.bg)) } else { None }
+        });
+        let mut res = MapViewWindow { window, map, eid, cell_glyph, entity_glyph, center, focus:None, deadzone:Point2d::default() };
+        res.update();
+        res
+    }
+
+    pub fn window(&self) -> &WindowHandle<Glyph> {
+        &self.window
+    }
+
+    pub fn map(&self) -> &Rc<RefCell<Map<C>>> {
+        &self.map
+    }
+
+    pub fn followed(&self) -> EntityId {
+        self.eid
+    }
+
+    pub fn follow(&mut self, eid:EntityId) {
+        self.eid = eid;
+        self.update();
+    }
+
+    pub fn set_deadzone(&mut self, deadzone:Point2d) {
+        self.deadzone = deadzone.abs();
+        self.update();
+    }
+
+    pub fn set_entity_glyphs(&mut self, f:EntityGlyphFn) {
+        self.entity_glyph = f;
+        self.update();
+    }
+
+    // follows a point (ie a look cursor) rather than the entity, until set back to None
+    pub fn set_focus(&mut self, p:Option<Point2d>) {
+        self.focus = p;
+        self.update();
+    }
+
+    // jumps the view (ie for look mode); the next update() scrolls back to the entity
+    pub fn center_on(&mut self, p:Point2d) {
+        self.center = p;
+        self.clamp_center();
+        self.render();
+    }
+
+    pub fn center(&self) -> Point2d {
+        self.center
+    }
+
+    // map area shown
+    pub fn view_rect(&self) -> Rect2d {
+        let size = self.window.size();
+        let bl = self.center - size/2;
+        Rect2d::new(bl, bl + size)
+    }
+
+    // window-local point to map point (whether or not on the map)
+    pub fn local_to_map(&self, p:Point2d) -> Point2d {
+        p + self.view_rect().bl
+    }
+
+    // root (ie terminal) point to map point, if within this view
+    pub fn screen_to_map(&self, p:Point2d) -> Option<Point2d> {
+        let q = self.window.to_local(p);
+        if self.window.span().contains(q) { Some(self.local_to_map(q)) } else { None }
+    }
+
+    // map point to root (ie terminal) point, if in view
+    pub fn map_to_screen(&self, p:Point2d) -> Option<Point2d> {
+        let view = self.view_rect();
+        if view.contains(p) { Some(self.window.to_screen(p - view.bl)) } else { None }
+    }
+
+    fn clamp_center(&mut self) {
+        let span = self.map.borrow().span();
+        let size = self.window.size();
+        // centre c of n cells within [lo,hi), or the middle of it if it's too small
+        let clamp_axis = |c:i32, lo:i32, hi:i32, n:i32| {
+            if hi - lo <= n { lo - (n - (hi - lo))/2 + n/2 }
+            else { c.clamp(lo + n/2, hi - n + n/2) }
+        };
+        self.center = Point2d::new(
+            clamp_axis(self.center.x, span.bl.x, span.tr.x, size.x),
+            clamp_axis(self.center.y, span.bl.y, span.tr.y, size.y));
+    }
+
+    // scrolls to keep the followed entity (or focus) within the deadzone, then redraws
+    pub fn update(&mut self) {
+        let pos = self.focus.or_else(|| self.map.borrow().entity_position(self.eid));
+        if let Some(p) = pos {
+            let d = p - self.center;
+            let excess = |d:i32, dz:i32| if d.abs() > dz { d - d.signum()*dz } else { 0 };
+            self.center += Point2d::new(excess(d.x, self.deadzone.x), excess(d.y, self.deadzone.y));
+        }
+        self.clamp_center();
+        self.render();
+    }
+
+    fn render(&mut self) {
+        let map = self.map.borrow();
+        let view = self.view_rect();
+        let blank = Glyph::new(' ', Color::black(), Color::black());
+        for lp in self.window.span() {
+            let mp = lp + view.bl;
+            let g = map.cell(mp).map(|c| (self.cell_glyph)(c)).unwrap_or(blank);
+            self.window.set(lp, g);
+        }
+        for (e, mp) in map.entities_iter() {
+            if !view.contains(*mp) { continue; }
+            let lp = *mp - view.bl;
+            let below = self.window.get(lp).unwrap();
+            if let Some(g) = (self.entity_glyph)(*e, &below) {
+                self.window.set(lp, g);
+            }
+        }
+    }
+}
+
+impl<C:Clone> Drop for MapViewWindow<C> {
+    fn drop(&mut self) {
+        self.window.demolish();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::{EntityManagerHandle};
+
+    fn setup(dz:Point2d) -> (WindowHandle<Glyph>, MapViewWindow<u8>, EntityId) {
+        let root = WindowHandle::new("Root".into(), Rect2d::new(Point2d::new(0,0), Point2d::new(30,20)));
+        let mut map = Map::new("test", Rect2d::new(Point2d::new(-10,-10), Point2d::new(40,30)), 0u8);
+        for p in map.span() { map.set_cell(p, ((p.x + 100) % 10) as u8); }
+        let eid = EntityManagerHandle::new().new_id();
+        map.set_entity_position(eid, Point2d::new(-9,-9));
+        let map = Rc::new(RefCell::new(map));
+        let glyph : CellGlyphFn<u8> = Box::new(|c| Glyph::new((b'0' + *c) as char, Color::white(), Color::black()));
+        let mut view = MapViewWindow::new(&root, Point2d::new(2,3), Point2d::new(10,6), map, eid, glyph);
+        view.set_deadzone(dz);
+        (root, view, eid)
+    }
+
+    fn move_to(view:&mut MapViewWindow<u8>, eid:EntityId, p:Point2d) {
+        view.map().borrow_mut().set_entity_position(eid, p);
+        view.update();
+    }
+
+    #[test]
+    fn follows_with_deadzone_and_clamps() {
+        let (_root, mut view, eid) = setup(Point2d::new(2,1));
+        // near the map's corner, the view is clamped to its edge
+        assert_eq!(view.view_rect(), Rect2d::new(Point2d::new(-10,-10), Point2d::new(0,-4)));
+        move_to(&mut view, eid, Point2d::new(15,10));
+        assert_eq!(view.center(), Point2d::new(13,9));
+        // within the deadzone, no scrolling
+        move_to(&mut view, eid, Point2d::new(12,9));
+        assert_eq!(view.center(), Point2d::new(13,9));
+        move_to(&mut view, eid, Point2d::new(10,9));
+        assert_eq!(view.center(), Point2d::new(12,9));
+        move_to(&mut view, eid, Point2d::new(39,29));
+        assert_eq!(view.view_rect().tr, Point2d::new(40,30));
+        let lp = view.window().to_local(view.map_to_screen(Point2d::new(39,29)).unwrap());
+        assert_eq!(view.window().get(lp).unwrap().ch, '@');
+        assert_eq!(view.window().get(lp - Point2d::new(1,0)).unwrap().ch, '8');
+    }
+
+    #[test]
+    fn screen_map_conversion() {
+        let (_root, mut view, eid) = setup(Point2d::new(0,0));
+        move_to(&mut view, eid, Point2d::new(5,5));
+        assert_eq!(view.view_rect().bl, Point2d::new(0,2));
+        assert_eq!(view.screen_to_map(Point2d::new(2,3)), Some(Point2d::new(0,2)));
+        assert_eq!(view.screen_to_map(Point2d::new(7,6)), Some(Point2d::new(5,5)));
+        assert_eq!(view.screen_to_map(Point2d::new(1,3)), None);
+        assert_eq!(view.map_to_screen(Point2d::new(5,5)), Some(Point2d::new(7,6)));
+        assert_eq!(view.map_to_screen(Point2d::new(30,5)), None);
+        for p in view.view_rect() {
+            assert_eq!(view.screen_to_map(view.map_to_screen(p).unwrap()), Some(p));
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
@@ -0,0 +1,366 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use std::cell::{RefCell};
+use std::rc::{Rc};
+
+use bear_lib_terminal::terminal::{KeyCode};
+
+use crate::core_systems::*;
+use crate::entity::{EntityId};
+use crate::grid::*;
+use crate::map::{Map};
+use crate::point2d::*;
+use crate::rect2d::*;
+use crate::window::{WindowHandle};
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone,Copy,Debug,Default,Eq,PartialEq)]
+pub enum Aggregate {
+    #[default]
+    Majority,   // most common cell in the block (ties to higher priority)
+    Priority,   // highest priority cell in the block (ie so doors & stairs aren't lost)
+}
+
+// block of map cells covered by sample s at the given factor
+pub fn sample_block(s:Point2d, factor:i32) -> Rect2d {
+    Rect2d::new(s * factor, (s + Point2d::new(1,1)) * factor)
+}
+
+// map point to the sample covering it
+pub fn sample_of(p:Point2d, factor:i32) -> Point2d {
+    Point2d::new(p.x.div_euclid(factor), p.y.div_euclid(factor))
+}
+
+// aggregate of the map cells within block, if any are on the map
+pub fn aggregate_block<C:Clone+PartialEq>(map:&Map<C>, block:Rect2d, agg:Aggregate, priority:&dyn Fn(&C)->i32) -> Option<C> {
+    let block = block.intersection(&map.span())?;
+    match agg {
+        Aggregate::Majority => {
+            // (linear, but blocks hold few distinct cells)
+            let mut counts : Vec<(&C,usize)> = Vec::new();
+            for p in block {
+                let c = map.cell(p).unwrap();
+                match counts.iter_mut().find(|(d, _)| *d == c) {
+                    Some((_, n)) => { *n += 1; }
+                    None => { counts.push((c, 1)); }
+                }
+            }
+            counts.into_iter()
+                .fold(None, |best:Option<(&C,usize)>, (c, n)| match best {
+                    Some((b, m)) if m > n || (m == n && priority(b) >= priority(c)) => Some((b, m)),
+                    _ => Some((c, n)),
+                })
+                .map(|(c, _)| c.clone())
+        }
+        Aggregate::Priority => {
+            block.into_iter().map(|p| map.cell(p).unwrap())
+                .fold(None, |best:Option<&C>, c| match best {
+                    Some(b) if priority(b) >= priority(c) => Some(b),
+                    _ => Some(c),
+                })
+                .cloned()
+        }
+    }
+}
+
+// whole map, downsampled by factor (in sample coordinates)
+pub fn downsample<C:Clone+PartialEq>(map:&Map<C>, factor:i32, agg:Aggregate, priority:&dyn Fn(&C)->i32) -> Grid<Option<C>> {
+    let span = map.span();
+    let samples = Rect2d::new(sample_of(span.bl, factor), sample_of(span.tr - Point2d::new(1,1), factor) + Point2d::new(1,1));
+    let mut res = Grid::new(samples, None);
+    for s in samples {
+        res.set(s, aggregate_block(map, sample_block(s, factor), agg, priority));
+    }
+    res
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub type CellColorFn<C> = Box<dyn Fn(&C)->Color>;
+pub type CellPriorityFn<C> = Box<dyn Fn(&C)->i32>;
+// marker (char & colour) for an entity, if it should be shown
+pub type MarkerFn = Box<dyn Fn(EntityId)->Option<(char,Color)>>;
+// marker for a notable cell (ie stairs, doors), if it should be shown
+pub type FeatureFn<C> = Box<dyn Fn(&C)->Option<(char,Color)>>;
+// whether a map point is known (ie has been seen), so its features are shown
+pub type KnownFn = Box<dyn Fn(Point2d)->bool>;
+
+// the samples last drawn; kept until the map's cells change or the view moves
+struct SampleCache {
+    revision: u64,
+    samples: Rect2d,
+    factor: i32,
+    colors: Grid<Color>,
+    features: Grid<Option<(char,Color)>>,
+}
+
+// overview of a map around an entity, each char showing two vertically stacked
+// samples (as a half-block), each sample aggregating scale x scale map cells
+pub struct Minimap<C:Clone> {
+    window: WindowHandle<Glyph>,
+    map: Rc<RefCell<Map<C>>>,
+    eid: EntityId,
+    scales: Vec<i32>,
+    zoom: usize,                // index into scales
+    aggregate: Aggregate,
+    color: CellColorFn<C>,
+    priority: CellPriorityFn<C>,
+    markers: MarkerFn,
+    features: FeatureFn<C>,
+    known: KnownFn,
+    cache: Option<SampleCache>,
+}
+
+impl<C:Clone+PartialEq> Minimap<C> {
+    pub fn new(parent:&WindowHandle<Glyph>, offset:Point2d, size:Point2d,
+        map:Rc<RefCell<Map<C>>>, eid:EntityId, color:CellColorFn<C>) -> Self
+    {
+        let window = parent.new_child(format!("Minimap[{}]",eid), offset, Rect2d::new(Point2d::new(0,0), size));
+        let markers : MarkerFn = Box::new(move |e| if e == eid { Some(('@', Color::pearl())) } else { None });
+        let mut res = Minimap { window, map, eid, scales:vec![1, 2, 4, 8], zoom:1, aggregate:Aggregate::Majority,
+            color, priority:Box::new(|_| 0), markers, features:Box::new(|_| None), known:Box::new(|_| true), cache:None };
+        res.update();
+        res
+    }
+
+    pub fn window(&self) -> &WindowHandle<Glyph> {
+        &self.window
+    }
+
+    // map cells per sample at each zoom level, most detailed first
+    // (a full deck at 5' squares wants the 64/128/256 of the notes in map.rs)
+    pub fn set_scales(&mut self, scales:&[i32]) {
+        self.scales = scales.iter().map(|s| (*s).max(1)).collect();
+        if self.scales.is_empty() { self.scales.push(1); }
+        self.zoom = self.zoom.min(self.scales.len() - 1);
+        self.invalidate();
+    }
+
+    pub fn scale(&self) -> i32 {
+        self.scales[self.zoom]
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.zoom = self.zoom.saturating_sub(1);
+        self.update();
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.zoom = (self.zoom + 1).min(self.scales.len() - 1);
+        self.update();
+    }
+
+    pub fn set_aggregate(&mut self, agg:Aggregate, priority:CellPriorityFn<C>) {
+        self.aggregate = agg;
+        self.priority = priority;
+        self.invalidate();
+    }
+
+    pub fn set_markers(&mut self, markers:MarkerFn) {
+        self.markers = markers;
+        self.update();
+    }
+
+    // features are marked where known (the most important per sample, by priority)
+    pub fn set_features(&mut self, features:FeatureFn<C>, known:KnownFn) {
+        self.features = features;
+        self.known = known;
+        self.invalidate();
+    }
+
+    // redraws everything, ie once more of the map is known
+    pub fn invalidate(&mut self) {
+        self.cache = None;
+        self.update();
+    }
+
+    pub fn handle_key(&mut self, key:KeyCode) -> bool {
+        match key {
+            KeyCode::Equals | KeyCode::NumPlus => { self.zoom_in(); }
+            KeyCode::Minus | KeyCode::NumMinus => { self.zoom_out(); }
+            _ => { return false; }
+        }
+        true
+    }
+
+    // samples shown, centred on the entity (2 per char vertically)
+    pub fn sample_rect(&self) -> Rect2d {
+        let map = self.map.borrow();
+        let p = map.entity_position(self.eid).unwrap_or(map.span().center());
+        let size = self.window.size();
+        let n = Point2d::new(size.x, size.y*2);
+        let bl = sample_of(p, self.scale()) - n/2;
+        Rect2d::new(bl, bl + n)
+    }
+
+    // aggregates the samples in view afresh
+    fn build_cache(&self, samples:Rect2d, factor:i32) -> SampleCache {
+        let map = self.map.borrow();
+        let mut colors = Grid::new(samples, Color::black());
+        let mut features = Grid::new(samples, None);
+        for s in samples {
+            let block = sample_block(s, factor);
+            if let Some(c) = aggregate_block(&map, block, self.aggregate, &self.priority) {
+                colors.set(s, (self.color)(&c));
+            }
+            let feature = block.intersection(&map.span()).into_iter().flatten()
+                .filter(|p| (self.known)(*p))
+                .filter_map(|p| map.cell(p))
+                .filter_map(|c| (self.features)(c).map(|m| (m, (self.priority)(c))))
+                .fold(None, |best:Option<((char,Color),i32)>, (m, pr)| match best {
+                    Some((b, bp)) if bp >= pr => Some((b, bp)),
+                    _ => Some((m, pr)),
+                });
+            features.set(s, feature.map(|(m, _)| m));
+        }
+        SampleCache { revision:map.revision(), samples, factor, colors, features }
+    }
+
+    // redraws from the current map (reaggregating only if its cells changed or the view moved)
+    pub fn update(&mut self) {
+        let f = self.scale();
+        let samples = self.sample_rect();
+        let revision = self.map.borrow().revision();
+        let fresh = self.cache.as_ref().is_some_and(|c| c.revision == revision && c.samples == samples && c.factor == f);
+        if !fresh {
+            self.cache = Some(self.build_cache(samples, f));
+        }
+        let cache = self.cache.as_ref().unwrap();
+        let color = |s:Point2d| *cache.colors.get(s).unwrap_or(&Color::black());
+        for lp in self.window.span() {
+            let top = samples.bl + Point2d::new(lp.x, lp.y*2);
+            let bottom = top + Point2d::new(0,1);
+            let g = match cache.features.get(top).cloned().flatten().or(cache.features.get(bottom).cloned().flatten()) {
+                Some((ch, fg)) => Glyph::new(ch, fg, color(top)),
+                None => Glyph::new('▀', color(top), color(bottom)),
+            };
+            self.window.set(lp, g);
+        }
+        let map = self.map.borrow();
+        for (e, p) in map.entities_iter() {
+            let s = sample_of(*p, f);
+            if !samples.contains(s) { continue; }
+            if let Some((ch, fg)) = (self.markers)(*e) {
+                let d = s - samples.bl;
+                self.window.set(Point2d::new(d.x, d.y/2), Glyph::new(ch, fg, color(s)));
+            }
+        }
+    }
+}
+
+impl<C:Clone> Drop for Minimap<C> {
+    fn drop(&mut self) {
+        self.window.demolish();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::{EntityManagerHandle};
+
+    // 8x8 of 0s, with a 1 in each 2x2 block's corner and a 2x2 of 2s at (0,0)
+    fn map() -> Map<u8> {
+        let mut m = Map::new("test", Rect2d::new(Point2d::new(-4,-4), Point2d::new(4,4)), 0u8);
+        for p in m.span() {
+            if p.x.rem_euclid(2) == 0 && p.y.rem_euclid(2) == 0 { m.set_cell(p, 1); }
+        }
+        for p in Rect2d::new(Point2d::new(0,0), Point2d::new(2,2)) { m.set_cell(p, 2); }
+        m
+    }
+
+    #[test]
+    fn downsampling() {
+        let m = map();
+        let prio = |c:&u8| *c as i32;
+        let g = downsample(&m, 2, Aggregate::Majority, &prio);
+        assert_eq!(g.span(), Rect2d::new(Point2d::new(-2,-2), Point2d::new(2,2)));
+        assert_eq!(g.get(Point2d::new(-2,-2)), Some(&Some(0)));
+        assert_eq!(g.get(Point2d::new(0,0)), Some(&Some(2)));
+        let g = downsample(&m, 2, Aggregate::Priority, &prio);
+        assert_eq!(g.get(Point2d::new(-2,-2)), Some(&Some(1)));
+        // partial blocks at the edge only aggregate what's on the map
+        let g = downsample(&m, 3, Aggregate::Priority, &prio);
+        assert_eq!(g.span(), Rect2d::new(Point2d::new(-2,-2), Point2d::new(2,2)));
+        assert_eq!(g.get(Point2d::new(-2,-2)), Some(&Some(1)));
+        assert_eq!(g.get(Point2d::new(1,1)), Some(&Some(0)));
+        // ties go to priority
+        let mut m = Map::new("tie", Rect2d::new(Point2d::new(0,0), Point2d::new(2,1)), 3u8);
+        m.set_cell(Point2d::new(1,0), 5);
+        assert_eq!(aggregate_block(&m, m.span(), Aggregate::Majority, &prio), Some(5));
+    }
+
+    #[test]
+    fn half_blocks_markers_and_zoom() {
+        let root = WindowHandle::new("Root".into(), Rect2d::new(Point2d::new(0,0), Point2d::new(20,20)));
+        let mut m = map();
+        let eid = EntityManagerHandle::new().new_id();
+        m.set_entity_position(eid, Point2d::new(1,1));
+        let m = Rc::new(RefCell::new(m));
+        let color : CellColorFn<u8> = Box::new(|c| [Color::black(), Color::white(), Color::gold()][*c as usize]);
+        let mut mm = Minimap::new(&root, Point2d::new(0,0), Point2d::new(4,2), m, eid, color);
+        mm.set_scales(&[1, 2, 4]);
+        assert_eq!(mm.scale(), 2);
+        // samples (-2,-2)..(2,2), player's sample (0,0) at char (2,1)
+        assert_eq!(mm.sample_rect(), Rect2d::new(Point2d::new(-2,-2), Point2d::new(2,2)));
+        let g = mm.window().get(Point2d::new(2,1)).unwrap();
+        assert_eq!((g.ch, g.fg, g.bg), ('@', Color::pearl(), Color::gold()));
+        let g = mm.window().get(Point2d::new(0,0)).unwrap();
+        assert_eq!((g.ch, g.fg, g.bg), ('▀', Color::black(), Color::black()));
+        mm.set_aggregate(Aggregate::Priority, Box::new(|c| *c as i32));
+        let g = mm.window().get(Point2d::new(0,0)).unwrap();
+        assert_eq!((g.fg, g.bg), (Color::white(), Color::white()));
+        assert!(mm.handle_key(KeyCode::Minus));
+        assert_eq!(mm.scale(), 4);
+        mm.zoom_out();
+        assert_eq!(mm.scale(), 4);
+        mm.zoom_in();
+        mm.zoom_in();
+        mm.zoom_in();
+        assert_eq!(mm.scale(), 1);
+    }
+
+    #[test]
+    fn cached_samples_and_features() {
+        let root = WindowHandle::new("Root".into(), Rect2d::new(Point2d::new(0,0), Point2d::new(20,20)));
+        let mut m = map();
+        let eid = EntityManagerHandle::new().new_id();
+        m.set_entity_position(eid, Point2d::new(1,1));
+        let m = Rc::new(RefCell::new(m));
+        let calls = Rc::new(std::cell::Cell::new(0));
+        let counted = calls.clone();
+        let color : CellColorFn<u8> = Box::new(move |c| { counted.set(counted.get() + 1); Color::grey().scale(*c as f64) });
+        let mut mm = Minimap::new(&root, Point2d::new(0,0), Point2d::new(4,2), m.clone(), eid, color);
+        // (one colour per sample on the map: 4x4 of them)
+        let built = calls.get();
+        assert_eq!(built, 16);
+        mm.update();
+        assert_eq!(calls.get(), built);
+        // moving within the same sample doesn't reaggregate; into another does
+        m.borrow_mut().set_entity_position(eid, Point2d::new(0,1));
+        mm.update();
+        assert_eq!(calls.get(), built);
+        m.borrow_mut().set_entity_position(eid, Point2d::new(2,1));
+        mm.update();
+        // (now samples (-1,-2)..(3,2), of which 3x4 are on the map)
+        assert_eq!(calls.get(), built + 12);
+        m.borrow_mut().set_cell(Point2d::new(-4,-4), 2);
+        mm.update();
+        assert_eq!(calls.get(), built + 24);
+        // the 2 at (-4,-4) is out of view, so only the 2x2 at (0,0) is marked, left of the player
+        mm.set_features(Box::new(|c| if *c == 2 { Some(('>', Color::gold())) } else { None }), Box::new(|p| p.x >= 0));
+        let g = mm.window().get(Point2d::new(1,1)).unwrap();
+        assert_eq!((g.ch, g.fg), ('>', Color::gold()));
+        assert_eq!(mm.window().get(Point2d::new(2,1)).unwrap().ch, '@');
+        let marked = |mm:&Minimap<u8>| mm.window().span().into_iter().filter(|p| mm.window().get(*p).unwrap().ch == '>').count();
+        assert_eq!(marked(&mm), 1);
+        // (and not where unknown)
+        mm.set_features(Box::new(|c| if *c == 2 { Some(('>', Color::gold())) } else { None }), Box::new(|p| p.x < 0));
+        assert_eq!(marked(&mm), 0);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
@@ -0,0 +1,352 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use bear_lib_terminal::terminal::{Event, KeyCode};
+
+use crate::core_systems::{Composite};
+use crate::handle::*;
+use crate::point2d::*;
+use crate::window::{WindowHandle, WindowWeakHandle};
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone,Copy,Debug,Eq,Hash,PartialEq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Fourth,
+    Fifth,
+}
+
+impl MouseButton {
+    pub fn from_key(k:KeyCode) -> Option<MouseButton> {
+        match k {
+            KeyCode::MouseLeft => Some(MouseButton::Left),
+            KeyCode::MouseRight => Some(MouseButton::Right),
+            KeyCode::MouseMiddle => Some(MouseButton::Middle),
+            KeyCode::MouseFourth => Some(MouseButton::Fourth),
+            KeyCode::MouseFifth => Some(MouseButton::Fifth),
+            _ => None,
+        }
+    }
+}
+
+// raw mouse input, in screen (ie root window) coordinates
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum MouseInput {
+    Move(Point2d),
+    Press(MouseButton, Point2d, i32),   // with count of fast consecutive clicks
+    Release(MouseButton, Point2d),
+    Scroll(i32, Point2d),               // positive is down/backwards
+}
+
+impl MouseInput {
+    // translates a BearLibTerminal event (which needs the mouse input filter enabled)
+    pub fn from_blt(ev:&Event) -> Option<MouseInput> {
+        match *ev {
+            Event::MouseMove{x, y, ..} => Some(MouseInput::Move(Point2d::new(x, y))),
+            Event::MouseScroll{delta, x, y, ..} => Some(MouseInput::Scroll(delta, Point2d::new(x, y))),
+            Event::MousePressed{key, x, y, clicks, ..} =>
+                MouseButton::from_key(key).map(|b| MouseInput::Press(b, Point2d::new(x, y), clicks)),
+            Event::MouseReleased{key, x, y, ..} =>
+                MouseButton::from_key(key).map(|b| MouseInput::Release(b, Point2d::new(x, y))),
+            _ => None,
+        }
+    }
+
+    pub fn position(&self) -> Point2d {
+        match *self {
+            MouseInput::Move(p) => p,
+            MouseInput::Press(_, p, _) => p,
+            MouseInput::Release(_, p) => p,
+            MouseInput::Scroll(_, p) => p,
+        }
+    }
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum MouseEventKind {
+    Move,
+    Press(MouseButton),
+    Release(MouseButton),
+    Click(MouseButton, i32),    // release over the window pressed, with click count
+    Drag(MouseButton),          // move while a button is held
+    Scroll(i32),
+    Enter,                      // cursor moved over this window (or a descendant); doesn't bubble
+    Leave,                      // cursor left this window (and all descendants); doesn't bubble
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub struct MouseEvent {
+    pub kind: MouseEventKind,
+    pub pos: Point2d,           // in receiving window's local coordinates
+    pub screen: Point2d,
+}
+
+// returns true if the event was handled (otherwise it bubbles up to the parent)
+pub type MouseHandler = Box<dyn FnMut(&MouseEvent)->bool>;
+
+////////////////////////////////////////////////////////////////////////////////
+
+// delivers mouse input to handlers attached to windows in the tree:
+//  - events go to the deepest visible window under the cursor, bubbling up
+//    through its ancestors until a handler returns true
+//  - a press that is handled captures the mouse to the handling window until
+//    released, so drags and the release go to it even outside its area
+//  - a release over the window pressed (handled or not) also sends it Click
+//  - capture() can also be used explicitly (ie for modal dialogs)
+pub struct MouseRouter<Cell:Clone> {
+    handlers: Vec<(WindowWeakHandle<Cell>, MouseHandler)>,
+    hover: Vec<WindowWeakHandle<Cell>>,             // under cursor, deepest first
+    capture: Option<WindowWeakHandle<Cell>>,
+    pressed: Option<(MouseButton, i32, WindowWeakHandle<Cell>)>,
+    explicit_capture: bool,
+}
+
+impl<Cell:Clone+Copy+Default+::std::fmt::Debug+Eq+Composite> MouseRouter<Cell> {
+    pub fn new() -> Self {
+        MouseRouter { handlers:Vec::new(), hover:Vec::new(), capture:None, pressed:None, explicit_capture:false }
+    }
+
+    // replaces any existing handler for w
+    pub fn set_handler(&mut self, w:&WindowHandle<Cell>, handler:MouseHandler) {
+        self.remove_handler(w);
+        self.handlers.push((w.weak(), handler));
+    }
+
+    pub fn remove_handler(&mut self, w:&WindowHandle<Cell>) {
+        let w = w.weak();
+        self.handlers.retain(|(h, _)| !WeakHandle::ptr_eq(h, &w));
+    }
+
+    pub fn capture(&mut self, w:&WindowHandle<Cell>) {
+        self.capture = Some(w.weak());
+        self.explicit_capture = true;
+    }
+
+    pub fn release_capture(&mut self) {
+        self.capture = None;
+        self.explicit_capture = false;
+    }
+
+    pub fn captured(&self) -> Option<WindowHandle<Cell>> {
+        self.capture.as_ref().and_then(|c| c.upgrade())
+    }
+
+    // whether the window is currently under the cursor (or a descendant of it is)
+    pub fn is_hovered(&self, w:&WindowHandle<Cell>) -> bool {
+        let w = w.weak();
+        self.hover.iter().any(|h| WeakHandle::ptr_eq(h, &w))
+    }
+
+    // routes input from the root window down; returns true if handled
+    pub fn route(&mut self, root:&WindowHandle<Cell>, input:MouseInput) -> bool {
+        // drop handlers for demolished windows
+        self.handlers.retain(|(h, _)| h.upgrade().is_some());
+        let screen = input.position();
+        if self.captured().is_none() || !self.explicit_capture {
+            self.update_hover(root, screen);
+        }
+        let target = match self.captured() {
+            Some(c) => c,
+            None => match self.hover.first().and_then(|h| h.upgrade()) {
+                Some(w) => w,
+                None => { return false; }
+            },
+        };
+        match input {
+            MouseInput::Move(_) => {
+                let kind = match self.pressed {
+                    Some((b, _, _)) if self.capture.is_some() => MouseEventKind::Drag(b),
+                    _ => MouseEventKind::Move,
+                };
+                self.bubble(&target, kind, screen).is_some()
+            }
+            MouseInput::Scroll(d, _) => {
+                self.bubble(&target, MouseEventKind::Scroll(d), screen).is_some()
+            }
+            MouseInput::Press(b, _, clicks) => {
+                let handled_by = self.bubble(&target, MouseEventKind::Press(b), screen);
+                // (remembered even if unhandled, as a window may only want clicks)
+                let pressed = handled_by.as_ref().unwrap_or(&target).weak();
+                self.pressed = Some((b, clicks, pressed));
+                if let Some(w) = &handled_by {
+                    if !self.explicit_capture {
+                        self.capture = Some(w.weak());
+                    }
+                }
+                handled_by.is_some()
+            }
+            MouseInput::Release(b, _) => {
+                let handled = self.bubble(&target, MouseEventKind::Release(b), screen).is_some();
+                let pressed = self.pressed.take();
+                if !self.explicit_capture {
+                    self.capture = None;
+                    self.update_hover(root, screen);
+                }
+                match pressed {
+                    Some((pb, clicks, pw)) if pb == b => {
+                        match pw.upgrade() {
+                            Some(pw) if self.is_hovered(&pw) =>
+                                self.bubble(&pw, MouseEventKind::Click(b, clicks), screen).is_some() || handled,
+                            _ => handled,
+                        }
+                    }
+                    _ => handled,
+                }
+            }
+        }
+    }
+
+    // sends Leave to windows no longer under the cursor (deepest first),
+    // then Enter to those newly under it (outermost first)
+    fn update_hover(&mut self, root:&WindowHandle<Cell>, screen:Point2d) {
+        let now : Vec<WindowWeakHandle<Cell>> = match root.select(screen) {
+            Some((w, _)) => w.ancestry().iter().map(|a| a.weak()).collect(),
+            None => Vec::new(),
+        };
+        let old = std::mem::take(&mut self.hover);
+        for h in old.iter() {
+            if !now.iter().any(|n| WeakHandle::ptr_eq(n, h)) {
+                if let Some(w) = h.upgrade() { self.deliver(&w, MouseEventKind::Leave, screen); }
+            }
+        }
+        for h in now.iter().rev() {
+            if !old.iter().any(|o| WeakHandle::ptr_eq(o, h)) {
+                if let Some(w) = h.upgrade() { self.deliver(&w, MouseEventKind::Enter, screen); }
+            }
+        }
+        self.hover = now;
+    }
+
+    // offers the event to w and then each ancestor in turn; returns whichever handled it
+    fn bubble(&mut self, w:&WindowHandle<Cell>, kind:MouseEventKind, screen:Point2d) -> Option<WindowHandle<Cell>> {
+        w.ancestry().into_iter().find(|a| self.deliver(a, kind, screen))
+    }
+
+    fn deliver(&mut self, w:&WindowHandle<Cell>, kind:MouseEventKind, screen:Point2d) -> bool {
+        let weak = w.weak();
+        match self.handlers.iter_mut().find(|(h, _)| WeakHandle::ptr_eq(h, &weak)) {
+            Some((_, handler)) => handler(&MouseEvent { kind, pos:w.to_local(screen), screen }),
+            None => false,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rect2d::*;
+
+    type Log = Handle<Vec<(String,MouseEventKind,Point2d)>>;
+
+    // root 0..20 with panel at (2,2) spanning (-5,-5)..(5,5), holding button at (1,1) of size 3x2
+    // (so button covers screen (8,8)..(11,10))
+    fn tree() -> (WindowHandle<u8>, WindowHandle<u8>, WindowHandle<u8>) {
+        let root = WindowHandle::new("Root".into(), Rect2d::new(Point2d::new(0,0), Point2d::new(20,20)));
+        let panel = root.new_child("Panel".into(), Point2d::new(2,2), Rect2d::new(Point2d::new(-5,-5), Point2d::new(5,5)));
+        let button = panel.new_child("Button".into(), Point2d::new(1,1), Rect2d::new(Point2d::new(0,0), Point2d::new(3,2)));
+        (root, panel, button)
+    }
+
+    fn logger(log:&Log, name:&str, handles:bool) -> MouseHandler {
+        let log = log.clone();
+        let name = name.to_string();
+        Box::new(move |ev:&MouseEvent| {
+            log.borrow_mut().push((name.clone(), ev.kind, ev.pos));
+            handles && !matches!(ev.kind, MouseEventKind::Enter | MouseEventKind::Leave)
+        })
+    }
+
+    #[test]
+    fn local_coordinates_and_bubbling() {
+        let (root, panel, button) = tree();
+        assert_eq!(button.to_local(Point2d::new(9,9)), Point2d::new(1,1));
+        assert_eq!(button.to_screen(Point2d::new(1,1)), Point2d::new(9,9));
+        assert!(Handle::ptr_eq(&root.select(Point2d::new(9,9)).unwrap().0, &button));
+        let log = Log::new_from(Vec::new());
+        let mut router = MouseRouter::new();
+        router.set_handler(&panel, logger(&log, "panel", true));
+        router.set_handler(&button, logger(&log, "button", false));
+        assert!(router.route(&root, MouseInput::Scroll(1, Point2d::new(9,9))));
+        assert_eq!(*log.borrow(), vec![
+            ("panel".to_string(), MouseEventKind::Enter, Point2d::new(2,2)),
+            ("button".to_string(), MouseEventKind::Enter, Point2d::new(1,1)),
+            ("button".to_string(), MouseEventKind::Scroll(1), Point2d::new(1,1)),
+            ("panel".to_string(), MouseEventKind::Scroll(1), Point2d::new(2,2)),
+        ]);
+        log.borrow_mut().clear();
+        assert!(!router.route(&root, MouseInput::Move(Point2d::new(15,15))));
+        assert_eq!(*log.borrow(), vec![
+            ("button".to_string(), MouseEventKind::Leave, Point2d::new(7,7)),
+            ("panel".to_string(), MouseEventKind::Leave, Point2d::new(8,8)),
+        ]);
+        // hidden windows are skipped
+        button.set_hidden(true);
+        log.borrow_mut().clear();
+        router.route(&root, MouseInput::Move(Point2d::new(9,9)));
+        assert_eq!(log.borrow().len(), 2);
+        assert!(log.borrow().iter().all(|e| e.0 == "panel"));
+    }
+
+    #[test]
+    fn drag_capture_and_click() {
+        let (root, _panel, button) = tree();
+        let log = Log::new_from(Vec::new());
+        let mut router = MouseRouter::new();
+        router.set_handler(&button, logger(&log, "button", true));
+        router.route(&root, MouseInput::Press(MouseButton::Left, Point2d::new(8,8), 1));
+        assert!(Handle::ptr_eq(&router.captured().unwrap(), &button));
+        router.route(&root, MouseInput::Move(Point2d::new(18,8)));
+        router.route(&root, MouseInput::Move(Point2d::new(9,8)));
+        router.route(&root, MouseInput::Release(MouseButton::Left, Point2d::new(9,8)));
+        assert!(router.captured().is_none());
+        let kinds : Vec<_> = log.borrow().iter().map(|e| (e.1, e.2)).collect();
+        assert_eq!(kinds, vec![
+            (MouseEventKind::Enter, Point2d::new(0,0)),
+            (MouseEventKind::Press(MouseButton::Left), Point2d::new(0,0)),
+            (MouseEventKind::Leave, Point2d::new(10,0)),
+            (MouseEventKind::Drag(MouseButton::Left), Point2d::new(10,0)),
+            (MouseEventKind::Enter, Point2d::new(1,0)),
+            (MouseEventKind::Drag(MouseButton::Left), Point2d::new(1,0)),
+            (MouseEventKind::Release(MouseButton::Left), Point2d::new(1,0)),
+            (MouseEventKind::Click(MouseButton::Left, 1), Point2d::new(1,0)),
+        ]);
+        // released outside: no click
+        log.borrow_mut().clear();
+        router.route(&root, MouseInput::Press(MouseButton::Left, Point2d::new(8,8), 1));
+        router.route(&root, MouseInput::Release(MouseButton::Left, Point2d::new(18,18)));
+        assert!(!log.borrow().iter().any(|e| matches!(e.1, MouseEventKind::Click(..))));
+        // and handlers go with their windows
+        button.demolish();
+        assert!(!router.route(&root, MouseInput::Scroll(1, Point2d::new(8,8))));
+        drop(button);
+        router.route(&root, MouseInput::Move(Point2d::new(8,8)));
+        assert!(router.handlers.is_empty());
+    }
+
+    #[test]
+    fn click_without_press() {
+        let (root, panel, button) = tree();
+        let log = Log::new_from(Vec::new());
+        let mut router = MouseRouter::new();
+        let clicks = log.clone();
+        router.set_handler(&panel, Box::new(move |ev:&MouseEvent| {
+            clicks.borrow_mut().push(("panel".into(), ev.kind, ev.pos));
+            matches!(ev.kind, MouseEventKind::Click(..))
+        }));
+        router.set_handler(&button, logger(&log, "button", false));
+        assert!(!router.route(&root, MouseInput::Press(MouseButton::Left, Point2d::new(8,8), 2)));
+        assert!(router.captured().is_none());
+        assert!(router.route(&root, MouseInput::Release(MouseButton::Left, Point2d::new(9,9))));
+        assert!(log.borrow().contains(&("panel".to_string(), MouseEventKind::Click(MouseButton::Left, 2), Point2d::new(2,2))));
+        // (handlers stay while their windows live, even out of the tree)
+        panel.demolish();
+        router.route(&root, MouseInput::Move(Point2d::new(15,15)));
+        assert_eq!(router.handlers.len(), 2);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
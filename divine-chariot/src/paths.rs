@@ -1,12 +1,13 @@
 ////////////////////////////////////////////////////////////////////////////////
 
-use std::collections::{HashMap};
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
 use std::ops::{Add};
 
 ////////////////////////////////////////////////////////////////////////////////
 
 use crate::point2d::*;
+use crate::point3d::*;
 use crate::rect2d::*;
 use crate::priority_queue::*;
 
@@ -104,6 +105,74 @@ impl Iterator for BresenhamIterator {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+pub fn bresenham3d(start:Point3d, goal:Point3d, path:&mut Vec<Point3d>) -> usize
+{
+    path.clear();
+    for p in Bresenham3dIterator::new(start, goal) {
+        path.push(p);
+    }
+    path.len()
+}
+
+// 3d bresenham: steps one unit along the dominant axis each time,
+// carrying an error term for each of the other two
+#[derive(Clone,Copy)]
+pub struct Bresenham3dIterator {
+    p: Point3d,
+    step: Point3d,
+    d: [i32; 3],        // absolute deltas, indexed by axis
+    major: usize,       // dominant axis
+    err: [i32; 3],      // error terms for the minor axes
+    remaining: i32,
+    started: bool,
+}
+
+impl Bresenham3dIterator {
+    pub fn new(start:Point3d, goal:Point3d) -> Self {
+        let delta = goal - start;
+        let step = Point3d::new(delta.x.signum(), delta.y.signum(), delta.z.signum());
+        let d = [delta.x.abs(), delta.y.abs(), delta.z.abs()];
+        let major = if d[0] >= d[1] && d[0] >= d[2] { 0 } else if d[1] >= d[2] { 1 } else { 2 };
+        let mut err = [0; 3];
+        for (a, e) in err.iter_mut().enumerate() {
+            *e = 2*d[a] - d[major];
+        }
+        Bresenham3dIterator { p:start, step, d, major, err, remaining:d[major], started:false }
+    }
+
+    fn advance(&mut self, axis:usize) {
+        match axis {
+            0 => { self.p.x += self.step.x; }
+            1 => { self.p.y += self.step.y; }
+            _ => { self.p.z += self.step.z; }
+        }
+    }
+}
+
+impl Iterator for Bresenham3dIterator {
+    type Item = Point3d;
+    fn next(&mut self) -> Option<Point3d> {
+        if !self.started {
+            self.started = true;
+            return Some(self.p);
+        }
+        if self.remaining == 0 { return None; }
+        self.remaining -= 1;
+        for a in 0 .. 3 {
+            if a == self.major { continue; }
+            if self.err[a] > 0 {
+                self.advance(a);
+                self.err[a] -= 2*self.d[self.major];
+            }
+            self.err[a] += 2*self.d[a];
+        }
+        self.advance(self.major);
+        Some(self.p)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 pub fn rectangle_points(span:Rect2d, path:&mut Vec<Point2d>) -> usize {
     path.clear();
 
@@ -122,7 +191,17 @@ pub fn rectangle_points(span:Rect2d, path:&mut Vec<Point2d>) -> usize {
 
 ////////////////////////////////////////////////////////////////////////////////
 
-pub fn circle_points(center:Point2d, radius:u32, path:&mut Vec<Point2d>, method:isize) -> usize {
+// how circle_points() picks the cells of a ring
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum CircleMethod {
+    Round,      // naive, slow: x = sqrt(r^2 - y^2), rounded
+    Floor,      // (likewise, rounded down)
+    Ceil,       // (likewise, rounded up)
+    Kennedy,    // midpoint, after John Kennedy; so far the best looking
+    Bresenham,  // bresenham's circle
+}
+
+pub fn circle_points(center:Point2d, radius:u32, path:&mut Vec<Point2d>, method:CircleMethod) -> usize {
     fn reflect (path:&mut Vec<Point2d>, center:Point2d, x:i32, y:i32) {
         path.push(center + Point2d::new( x,  y));
         path.push(center + Point2d::new( x, -y));
@@ -135,7 +214,7 @@ pub fn circle_points(center:Point2d, radius:u32, path:&mut Vec<Point2d>, method:
     };
     path.clear();
 
-    if method<=2 {
+    if let CircleMethod::Round | CircleMethod::Floor | CircleMethod::Ceil = method {
         // naive, slow method
         let r2 = (radius*radius) as f64;
         let mut x = radius as i32;
@@ -143,16 +222,16 @@ pub fn circle_points(center:Point2d, radius:u32, path:&mut Vec<Point2d>, method:
         while y < x {
             reflect(path, center, x, y);
             y += 1;
-            if method==0 {
+            if method==CircleMethod::Round {
                 x = (r2 - ((y*y) as f64)).sqrt().round() as i32;
-            } else if method==1 {
+            } else if method==CircleMethod::Floor {
                 x = (r2 - ((y*y) as f64)).sqrt().floor() as i32;
-            } else if method==2 {
+            } else if method==CircleMethod::Ceil {
                 x = (r2 - ((y*y) as f64)).sqrt().ceil() as i32;
             }
         }
         reflect(path, center, x, y);
-    } else if method==3 {
+    } else if method==CircleMethod::Kennedy {
         // decent approach by John Kennedy (paper at http://web.engr.oregonstate.edu/~sllu/bcircle.pdf)
         // so far the best looking approach...
         let r = radius as i32;
@@ -172,7 +251,7 @@ pub fn circle_points(center:Point2d, radius:u32, path:&mut Vec<Point2d>, method:
                 xchange += 2;
             }
         }
-    } else if method==4 {
+    } else if method==CircleMethod::Bresenham {
         let r = radius as i32;
         let mut x = 0;
         let mut y = r;
@@ -195,6 +274,106 @@ pub fn circle_points(center:Point2d, radius:u32, path:&mut Vec<Point2d>, method:
 
 ////////////////////////////////////////////////////////////////////////////////
 
+// filled disc; the +radius slack rounds off the "pointy" cardinal extremes
+pub fn disc_points(center:Point2d, radius:u32, path:&mut Vec<Point2d>) -> usize {
+    path.clear();
+    let r = radius as i32;
+    let r2 = r*r + r;
+    for y in -r ..= r {
+        for x in -r ..= r {
+            if x*x + y*y <= r2 {
+                path.push(center + Point2d::new(x, y));
+            }
+        }
+    }
+    path.len()
+}
+
+// filled ellipse with the given x & y radii
+pub fn ellipse_points(center:Point2d, rx:u32, ry:u32, path:&mut Vec<Point2d>) -> usize {
+    path.clear();
+    let rx = rx as i32;
+    let ry = ry as i32;
+    // (x/rx)^2 + (y/ry)^2 <= 1, scaled up to stay in integers (with same slack as disc_points)
+    let a2 = (rx*rx) as i64;
+    let b2 = (ry*ry) as i64;
+    let lim = a2*b2 + (a2*b2) / (rx.max(ry).max(1) as i64);
+    for y in -ry ..= ry {
+        for x in -rx ..= rx {
+            if (x*x) as i64*b2 + (y*y) as i64*a2 <= lim {
+                path.push(center + Point2d::new(x, y));
+            }
+        }
+    }
+    path.len()
+}
+
+// filled diamond, ie all points within given manhattan distance
+pub fn diamond_points(center:Point2d, radius:u32, path:&mut Vec<Point2d>) -> usize {
+    path.clear();
+    let r = radius as i32;
+    for y in -r ..= r {
+        let w = r - y.abs();
+        for x in -w ..= w {
+            path.push(center + Point2d::new(x, y));
+        }
+    }
+    path.len()
+}
+
+// filled cone from origin (exclusive) pointing at toward, out to radius,
+// spreading over angle (full width, in radians)
+pub fn cone_points(origin:Point2d, toward:Point2d, radius:u32, angle:f64, path:&mut Vec<Point2d>) -> usize {
+    path.clear();
+    let dir = toward - origin;
+    if dir == Point2d::default() { return 0; }
+    let theta = (dir.y as f64).atan2(dir.x as f64);
+    let half = angle.abs() * 0.5;
+    let r = radius as i32;
+    let r2 = r*r + r;
+    for y in -r ..= r {
+        for x in -r ..= r {
+            if (x==0 && y==0) || x*x + y*y > r2 { continue; }
+            let mut d = (y as f64).atan2(x as f64) - theta;
+            while d >  std::f64::consts::PI { d -= 2.0*std::f64::consts::PI; }
+            while d < -std::f64::consts::PI { d += 2.0*std::f64::consts::PI; }
+            if d.abs() <= half + 1e-9 {
+                path.push(origin + Point2d::new(x, y));
+            }
+        }
+    }
+    path.len()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// all points reachable from start (within span) through points satisfying pred
+// (start itself must satisfy pred, else nothing is filled)
+pub fn flood_fill<Pred:FnMut(Point2d)->bool>(
+    start:Point2d, span:Rect2d, pred:&mut Pred, diagonal:bool, path:&mut Vec<Point2d>) -> usize
+{
+    path.clear();
+    if !span.contains(start) || !pred(start) { return 0; }
+    let mut seen = HashSet::new();
+    let mut stack = vec![start];
+    seen.insert(start);
+    while let Some(p) = stack.pop() {
+        path.push(p);
+        let ns : Vec<Point2d> =
+            if diagonal { Point2d::neighbors8(p).collect() }
+            else { Point2d::neighbors4(p).collect() };
+        for q in ns {
+            if span.contains(q) && !seen.contains(&q) {
+                seen.insert(q);
+                if pred(q) { stack.push(q); }
+            }
+        }
+    }
+    path.len()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 pub fn astar<
     Pos:Copy+Default+Eq+Hash,
     Weight:Add<Weight,Output=Weight>+Copy+Default+Eq+Ord,
@@ -268,3 +447,85 @@ pub fn astar<
 }
 
 ////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // points as rows of '#' over '.', top row first
+    fn art(points:&[Point2d], span:Rect2d) -> Vec<String> {
+        (span.bl.y .. span.tr.y).map(|y| (span.bl.x .. span.tr.x)
+            .map(|x| if points.contains(&Point2d::new(x, y)) { '#' } else { '.' }).collect()).collect()
+    }
+
+    #[test]
+    fn filled_shapes() {
+        let span = Rect2d::new(Point2d::new(-3,-3), Point2d::new(4,4));
+        let c = Point2d::new(0,0);
+        let mut path = Vec::new();
+        assert_eq!(disc_points(c, 2, &mut path), 21);
+        assert_eq!(art(&path, span), [".......", "..###..", ".#####.", ".#####.", ".#####.", "..###..", "......."]);
+        assert_eq!(ellipse_points(c, 3, 1, &mut path), 13);
+        assert_eq!(art(&path, span), [".......", ".......", "..###..", "#######", "..###..", ".......", "......."]);
+        assert_eq!(diamond_points(c, 2, &mut path), 13);
+        assert_eq!(art(&path, span), [".......", "...#...", "..###..", ".#####.", "..###..", "...#...", "......."]);
+        // (the origin itself left out)
+        assert_eq!(cone_points(c, Point2d::new(1,0), 3, std::f64::consts::FRAC_PI_2, &mut path), 11);
+        assert_eq!(art(&path, span), [".......", ".....#.", "....###", "....###", "....###", ".....#.", "......."]);
+        assert_eq!(cone_points(c, c, 3, 1.0, &mut path), 0);
+    }
+
+    #[test]
+    fn circles() {
+        let span = Rect2d::new(Point2d::new(-3,-3), Point2d::new(4,4));
+        let c = Point2d::new(0,0);
+        let mut path = Vec::new();
+        circle_points(c, 3, &mut path, CircleMethod::Kennedy);
+        assert_eq!(art(&path, span), ["..###..", ".#...#.", "#.....#", "#.....#", "#.....#", ".#...#.", "..###.."]);
+        for m in [CircleMethod::Round, CircleMethod::Floor, CircleMethod::Ceil, CircleMethod::Kennedy, CircleMethod::Bresenham] {
+            circle_points(c, 3, &mut path, m);
+            assert!(path.iter().all(|p| (p.euclidean(c) - 3.0).abs() < 1.0), "{:?}", m);
+            assert!(path.contains(&Point2d::new(3,0)) && path.contains(&Point2d::new(0,-3)), "{:?}", m);
+        }
+    }
+
+    #[test]
+    fn flood_fill_walls() {
+        let span = Rect2d::new(Point2d::new(0,0), Point2d::new(5,4));
+        let wall = ["..#..", "..#..", "##...", "....."];
+        let open = |p:Point2d| wall[p.y as usize].as_bytes()[p.x as usize] == b'.';
+        let mut path = Vec::new();
+        assert_eq!(flood_fill(Point2d::new(0,0), span, &mut |p| open(p), false, &mut path), 4);
+        assert_eq!(art(&path, span), ["##...", "##...", ".....", "....."]);
+        // (diagonally, squeezing between the walls)
+        assert_eq!(flood_fill(Point2d::new(0,0), span, &mut |p| open(p), true, &mut path), 16);
+        assert_eq!(art(&path, span), ["##.##", "##.##", "..###", "#####"]);
+        assert_eq!(flood_fill(Point2d::new(2,0), span, &mut |p| open(p), false, &mut path), 0);
+        assert_eq!(flood_fill(Point2d::new(9,9), span, &mut |p| open(p), false, &mut path), 0);
+        assert!(path.is_empty());
+    }
+
+    #[test]
+    fn bresenham3d_lines() {
+        let mut path = Vec::new();
+        let start = Point3d::new(2,-1,3);
+        for d in [Point3d::new(5,-3,1), Point3d::new(-2,4,-7), Point3d::new(-6,-6,2), Point3d::new(0,1,-1),
+                  Point3d::new(3,0,0), Point3d::new(-1,7,7), Point3d::new(0,0,0)] {
+            let goal = start + d;
+            let n = bresenham3d(start, goal, &mut path);
+            let m = d.x.abs().max(d.y.abs()).max(d.z.abs());
+            assert_eq!(n as i32, m+1, "{:?}", d);
+            assert_eq!(path[0], start);
+            assert_eq!(path[n-1], goal, "{:?}", d);
+            // each step is to one of the 26 neighbors, never away from the goal
+            for w in path.windows(2) {
+                assert_eq!(w[0].chebyshev(w[1]), 1);
+                assert!(w[1].manhattan(goal) < w[0].manhattan(goal));
+            }
+        }
+        assert_eq!(Bresenham3dIterator::new(start, start+Point3d::new(-2,1,0)).collect::<Vec<_>>(),
+            [start, Point3d::new(1,-1,3), Point3d::new(0,0,3)]);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
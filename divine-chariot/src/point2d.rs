@@ -12,7 +12,8 @@ pub struct Point2d {
 }
 
 static DIRS_4 : [Point2d; 4] = [ Point2d{x:1,y:0}, Point2d{x:0,y:1}, Point2d{x:-1,y:0}, Point2d{x:0,y:-1} ];
-static DIRS_8 : [Point2d; 4] = [ Point2d{x:1,y:0}, Point2d{x:0,y:1}, Point2d{x:-1,y:0}, Point2d{x:0,y:-1} ];
+static DIRS_8 : [Point2d; 8] = [ Point2d{x:1,y:0}, Point2d{x:1,y:1}, Point2d{x:0,y:1}, Point2d{x:-1,y:1},
+                                 Point2d{x:-1,y:0}, Point2d{x:-1,y:-1}, Point2d{x:0,y:-1}, Point2d{x:1,y:-1} ];
 
 impl Point2d {
     pub fn new(x:i32,y:i32) -> Self {
@@ -34,6 +35,158 @@ impl Point2d {
     pub fn neighbors8(p:Point2d) -> impl Iterator<Item=Point2d> {
         Point2d::dirs8().map(move|d|p+d)
     }
+
+    // componentwise signum, ie the "king-move" step toward self
+    pub fn signum(&self) -> Point2d {
+        Point2d::new(self.x.signum(), self.y.signum())
+    }
+
+    pub fn abs(&self) -> Point2d {
+        Point2d::new(self.x.abs(), self.y.abs())
+    }
+
+    // distance metrics
+
+    // king-move distance
+    pub fn chebyshev(&self, other:Point2d) -> i32 {
+        let d = (*self - other).abs();
+        d.x.max(d.y)
+    }
+
+    // rook-move distance
+    pub fn manhattan(&self, other:Point2d) -> i32 {
+        let d = (*self - other).abs();
+        d.x + d.y
+    }
+
+    pub fn euclidean2(&self, other:Point2d) -> i32 {
+        let d = *self - other;
+        d.x*d.x + d.y*d.y
+    }
+
+    pub fn euclidean(&self, other:Point2d) -> f64 {
+        (self.euclidean2(other) as f64).sqrt()
+    }
+
+    // king-move distance with diagonal steps costing sqrt(2)
+    pub fn octile(&self, other:Point2d) -> f64 {
+        let d = (*self - other).abs();
+        let (lo, hi) = (d.x.min(d.y), d.x.max(d.y));
+        hi as f64 + (std::f64::consts::SQRT_2 - 1.0) * lo as f64
+    }
+
+    // rotations about the origin by quarter-turns, +x toward +y
+    // (nb: that is clockwise on screen, the opposite sense to Dir::turn)
+
+    pub fn rot90(&self) -> Point2d {
+        Point2d::new(-self.y, self.x)
+    }
+
+    pub fn rot180(&self) -> Point2d {
+        -*self
+    }
+
+    pub fn rot270(&self) -> Point2d {
+        Point2d::new(self.y, -self.x)
+    }
+
+    pub fn rotate(&self, quarter_turns:i32) -> Point2d {
+        match quarter_turns.rem_euclid(4) {
+            0 => *self,
+            1 => self.rot90(),
+            2 => self.rot180(),
+            _ => self.rot270(),
+        }
+    }
+
+    pub fn rotate_about(&self, center:Point2d, quarter_turns:i32) -> Point2d {
+        center + (*self - center).rotate(quarter_turns)
+    }
+
+    pub fn flip_x(&self) -> Point2d {
+        Point2d::new(-self.x, self.y)
+    }
+
+    pub fn flip_y(&self) -> Point2d {
+        Point2d::new(self.x, -self.y)
+    }
+
+    pub fn transpose(&self) -> Point2d {
+        Point2d::new(self.y, self.x)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// compass directions, in screen orientation (ie N is -y, toward the top of the terminal)
+// ordered counter-clockwise (on screen) from E, so that +1 is a 45 degree turn
+#[derive(Clone,Copy,Debug,Eq,PartialEq,Ord,PartialOrd,Hash,serde::Serialize,serde::Deserialize)]
+pub enum Dir {
+    E, NE, N, NW, W, SW, S, SE,
+}
+
+static DIRS : [Dir; 8] = [ Dir::E, Dir::NE, Dir::N, Dir::NW, Dir::W, Dir::SW, Dir::S, Dir::SE ];
+static CARDINALS : [Dir; 4] = [ Dir::E, Dir::N, Dir::W, Dir::S ];
+
+impl Dir {
+    pub fn all() -> impl Iterator<Item=Dir> {
+        DIRS.iter().cloned()
+    }
+
+    pub fn cardinals() -> impl Iterator<Item=Dir> {
+        CARDINALS.iter().cloned()
+    }
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn from_index(i:isize) -> Dir {
+        DIRS[i.rem_euclid(8) as usize]
+    }
+
+    pub fn is_cardinal(&self) -> bool {
+        self.index().is_multiple_of(2)
+    }
+
+    pub fn delta(&self) -> Point2d {
+        match self {
+            Dir::E  => Point2d::new( 1, 0),
+            Dir::NE => Point2d::new( 1,-1),
+            Dir::N  => Point2d::new( 0,-1),
+            Dir::NW => Point2d::new(-1,-1),
+            Dir::W  => Point2d::new(-1, 0),
+            Dir::SW => Point2d::new(-1, 1),
+            Dir::S  => Point2d::new( 0, 1),
+            Dir::SE => Point2d::new( 1, 1),
+        }
+    }
+
+    // nearest direction (in angle) of the given offset, None for (0,0)
+    pub fn from_delta(d:Point2d) -> Option<Dir> {
+        if d == Point2d::default() { return None; }
+        let theta = (-d.y as f64).atan2(d.x as f64);
+        let octant = (theta / std::f64::consts::FRAC_PI_4).round() as isize;
+        Some(Dir::from_index(octant))
+    }
+
+    // turns by 45 degree steps (positive is counter-clockwise on screen)
+    pub fn turn(&self, steps:isize) -> Dir {
+        Dir::from_index(self.index() as isize + steps)
+    }
+
+    pub fn left(&self) -> Dir { self.turn(2) }
+    pub fn right(&self) -> Dir { self.turn(-2) }
+    pub fn opposite(&self) -> Dir { self.turn(4) }
+}
+
+impl Add<Dir> for Point2d { type Output=Point2d; fn add(self, other:Dir) -> Point2d { self + other.delta() } }
+impl AddAssign<Dir> for Point2d { fn add_assign(&mut self, other:Dir) { *self = *self + other; } }
+
+impl std::fmt::Display for Dir {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
 }
 
 impl Add<Point2d> for Point2d { type Output=Point2d; fn add(self, other:Point2d) -> Point2d { Point2d{x:self.x+other.x, y:self.y+other.y} } }
@@ -61,3 +214,62 @@ impl std::fmt::Display for Point2d {
 }
 
 ////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::{Generator, Rng};
+
+    fn rand_point(rng:&mut Rng) -> Point2d {
+        Point2d::new(rng.gen_range(-50,50), rng.gen_range(-50,50))
+    }
+
+    #[test]
+    fn metrics_are_ordered() {
+        let mut rng = Rng::from_seed([1,2,3,4]);
+        for _ in 0 .. 1000 {
+            let (a, b, c) = (rand_point(&mut rng), rand_point(&mut rng), rand_point(&mut rng));
+            let (ch, mh, eu, oc) = (a.chebyshev(b), a.manhattan(b), a.euclidean(b), a.octile(b));
+            assert!(ch as f64 <= eu + 1e-9 && eu <= oc + 1e-9 && oc <= mh as f64 + 1e-9);
+            assert_eq!(ch, b.chebyshev(a));
+            assert_eq!(mh, b.manhattan(a));
+            assert!(a.chebyshev(c) <= ch + b.chebyshev(c));
+            assert!(a.manhattan(c) <= mh + b.manhattan(c));
+            assert_eq!(a.euclidean2(b), (eu*eu).round() as i32);
+        }
+    }
+
+    #[test]
+    fn rotations_compose() {
+        let mut rng = Rng::from_seed([5,6,7,8]);
+        for _ in 0 .. 1000 {
+            let p = rand_point(&mut rng);
+            let c = rand_point(&mut rng);
+            let n = rng.gen_range(-8,8);
+            assert_eq!(p.rot90().rot90(), p.rot180());
+            assert_eq!(p.rot90().rot270(), p);
+            assert_eq!(p.rotate(n).rotate(-n), p);
+            assert_eq!(p.rotate(n+4), p.rotate(n));
+            assert_eq!(p.rotate_about(c, n).chebyshev(c), p.chebyshev(c));
+            assert_eq!(p.transpose().transpose(), p);
+        }
+    }
+
+    #[test]
+    fn dirs_roundtrip() {
+        for d in Dir::all() {
+            assert_eq!(Dir::from_delta(d.delta()), Some(d));
+            assert_eq!(d.opposite().delta(), -d.delta());
+            assert_eq!(d.left().right(), d);
+            assert_eq!(d.delta().chebyshev(Point2d::default()), 1);
+        }
+        assert_eq!(Dir::cardinals().count(), 4);
+        assert!(Dir::cardinals().all(|d| d.is_cardinal()));
+        assert_eq!(Dir::from_delta(Point2d::new(7,-1)), Some(Dir::E));
+        assert_eq!(Dir::from_delta(Point2d::default()), None);
+        let all : Vec<Point2d> = Point2d::dirs8().collect();
+        assert!(Dir::all().all(|d| all.contains(&d.delta())));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
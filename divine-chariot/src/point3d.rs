@@ -0,0 +1,140 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use crate::point2d::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+// (x,y) as in Point2d within a deck, z is the height layer (up is +z)
+#[derive(Clone,Copy,Default,Eq,PartialEq,Ord,PartialOrd,Hash,serde::Serialize,serde::Deserialize)]
+pub struct Point3d {
+    pub x : i32,
+    pub y : i32,
+    pub z : i32,
+}
+
+static DIRS_6 : [Point3d; 6] = [
+    Point3d{x:1,y:0,z:0}, Point3d{x:0,y:1,z:0}, Point3d{x:-1,y:0,z:0},
+    Point3d{x:0,y:-1,z:0}, Point3d{x:0,y:0,z:1}, Point3d{x:0,y:0,z:-1},
+];
+
+impl Point3d {
+    pub fn new(x:i32, y:i32, z:i32) -> Self {
+        Point3d{x,y,z}
+    }
+
+    pub fn from_2d(p:Point2d, z:i32) -> Self {
+        Point3d{x:p.x, y:p.y, z}
+    }
+
+    // drops the height
+    pub fn xy(&self) -> Point2d {
+        Point2d::new(self.x, self.y)
+    }
+
+    pub fn up(&self) -> Point3d {
+        Point3d{z:self.z+1, ..*self}
+    }
+
+    pub fn down(&self) -> Point3d {
+        Point3d{z:self.z-1, ..*self}
+    }
+
+    // face-adjacent directions
+    pub fn dirs6() -> impl Iterator<Item=Point3d> {
+        DIRS_6.iter().cloned()
+    }
+
+    // all 26 adjacent directions (face, edge & corner)
+    pub fn dirs26() -> impl Iterator<Item=Point3d> {
+        (-1..=1).flat_map(|z| (-1..=1).flat_map(move|y| (-1..=1).map(move|x| Point3d::new(x,y,z))))
+            .filter(|d| *d != Point3d::default())
+    }
+
+    pub fn neighbors6(p:Point3d) -> impl Iterator<Item=Point3d> {
+        Point3d::dirs6().map(move|d|p+d)
+    }
+
+    pub fn neighbors26(p:Point3d) -> impl Iterator<Item=Point3d> {
+        Point3d::dirs26().map(move|d|p+d)
+    }
+
+    // same-layer neighbors plus directly above & below
+    // (ie what a flying creature can reach without diagonal climbs)
+    pub fn neighbors10(p:Point3d) -> impl Iterator<Item=Point3d> {
+        Point2d::neighbors8(p.xy()).map(move|q| Point3d::from_2d(q, p.z))
+            .chain([p.up(), p.down()])
+    }
+
+    pub fn abs(&self) -> Point3d {
+        Point3d::new(self.x.abs(), self.y.abs(), self.z.abs())
+    }
+
+    pub fn chebyshev(&self, other:Point3d) -> i32 {
+        let d = (*self - other).abs();
+        d.x.max(d.y).max(d.z)
+    }
+
+    pub fn manhattan(&self, other:Point3d) -> i32 {
+        let d = (*self - other).abs();
+        d.x + d.y + d.z
+    }
+
+    pub fn euclidean2(&self, other:Point3d) -> i32 {
+        let d = *self - other;
+        d.x*d.x + d.y*d.y + d.z*d.z
+    }
+
+    pub fn euclidean(&self, other:Point3d) -> f64 {
+        (self.euclidean2(other) as f64).sqrt()
+    }
+}
+
+impl From<Point2d> for Point3d { fn from(p:Point2d) -> Point3d { Point3d::from_2d(p, 0) } }
+
+impl Add<Point3d> for Point3d { type Output=Point3d; fn add(self, other:Point3d) -> Point3d { Point3d{x:self.x+other.x, y:self.y+other.y, z:self.z+other.z} } }
+impl Sub<Point3d> for Point3d { type Output=Point3d; fn sub(self, other:Point3d) -> Point3d { Point3d{x:self.x-other.x, y:self.y-other.y, z:self.z-other.z} } }
+impl Mul<i32    > for Point3d { type Output=Point3d; fn mul(self, other:i32    ) -> Point3d { Point3d{x:self.x*other  , y:self.y*other  , z:self.z*other  } } }
+impl Div<i32    > for Point3d { type Output=Point3d; fn div(self, other:i32    ) -> Point3d { Point3d{x:self.x/other  , y:self.y/other  , z:self.z/other  } } }
+impl Neg for Point3d { type Output=Point3d; fn neg(self) -> Point3d { Point3d{x:-self.x, y:-self.y, z:-self.z} } }
+impl AddAssign<Point3d> for Point3d { fn add_assign(&mut self, other:Point3d) { *self = *self + other; } }
+impl SubAssign<Point3d> for Point3d { fn sub_assign(&mut self, other:Point3d) { *self = *self - other; } }
+impl MulAssign<i32    > for Point3d { fn mul_assign(&mut self, other:i32    ) { *self = *self * other; } }
+impl DivAssign<i32    > for Point3d { fn div_assign(&mut self, other:i32    ) { *self = *self / other; } }
+
+impl std::fmt::Debug for Point3d {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "({},{},{})", self.x, self.y, self.z)
+    }
+}
+
+impl std::fmt::Display for Point3d {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "({},{},{})", self.x, self.y, self.z)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighborhoods() {
+        let dirs : Vec<Point3d> = Point3d::dirs26().collect();
+        assert_eq!(dirs.len(), 26);
+        assert!(dirs.iter().all(|d| d.chebyshev(Point3d::default()) == 1));
+        assert!(Point3d::dirs6().all(|d| dirs.contains(&d)));
+        let p = Point3d::new(4,-2,1);
+        let n : Vec<Point3d> = Point3d::neighbors10(p).collect();
+        assert_eq!(n.len(), 10);
+        assert_eq!(n.iter().filter(|q| q.z == p.z).count(), 8);
+        assert!(n.contains(&p.up()) && n.contains(&p.down()) && !n.contains(&p));
+        assert!(n.iter().all(|q| Point3d::neighbors26(p).any(|r| r == *q)));
+        assert_eq!(Point3d::from_2d(p.xy(), p.z), p);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
@@ -0,0 +1,369 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use bear_lib_terminal::terminal::{KeyCode};
+
+use crate::ansi::{self, ColorMode};
+use crate::core_systems::*;
+use crate::grid::*;
+use crate::point2d::*;
+use crate::rect2d::*;
+use crate::renderer::{flatten_ext, InputEvent, Renderer};
+use crate::window::{WindowHandle};
+
+////////////////////////////////////////////////////////////////////////////////
+
+// only the cells changed since the previous frame (all cells for the first,
+// and for any after the screen is resized)
+#[derive(Clone,Debug,serde::Serialize,serde::Deserialize)]
+pub struct RecordedFrame {
+    pub t: f64,                         // seconds since recording started
+    pub cells: Vec<(Point2d,Glyph)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<Point2d>,          // new screen size, if resized since the previous frame
+}
+
+#[derive(Clone,Debug,Default,serde::Serialize,serde::Deserialize)]
+pub struct Recording {
+    pub size: Point2d,                  // at the start; frames note any resizes
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl Recording {
+    pub fn new(size:Point2d) -> Self {
+        Recording { size, frames:Vec::new() }
+    }
+
+    // adds screen as a frame at time t, given the previous frame pushed (if any)
+    pub fn push(&mut self, t:Duration, screen:&Grid<Glyph>, prev:Option<&Grid<Glyph>>) {
+        let size = screen.span().size();
+        let resized = match prev {
+            Some(pr) => pr.span() != screen.span(),
+            None => size != self.size,
+        };
+        let prev = if resized { None } else { prev };
+        let cells = screen.span().into_iter()
+            .map(|p| (p, *screen.get(p).unwrap()))
+            .filter(|(p, g)| prev.is_none_or(|pr| pr.get(*p) != Some(g)))
+            .collect();
+        self.frames.push(RecordedFrame { t:t.as_secs_f64(), cells, size:resized.then_some(size) });
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.frames.last().map(|f| f.t).unwrap_or(0.0)
+    }
+
+    // full screens, rebuilt from the diffs
+    pub fn screens(&self) -> impl Iterator<Item=(f64,Grid<Glyph>)> + '_ {
+        let mut screen = Grid::new(Rect2d::new(Point2d::new(0,0), self.size), Glyph::default());
+        self.frames.iter().map(move |f| {
+            if let Some(size) = f.size {
+                screen = Grid::new(Rect2d::new(Point2d::new(0,0), size), Glyph::default());
+            }
+            for (p, g) in f.cells.iter() { screen.set(*p, *g); }
+            (f.t, screen.clone())
+        })
+    }
+
+    pub fn save(&self, path:&Path) -> io::Result<()> {
+        let f = io::BufWriter::new(std::fs::File::create(path)?);
+        serde_cbor::to_writer(f, self).map_err(io::Error::other)
+    }
+
+    pub fn load(path:&Path) -> io::Result<Recording> {
+        let f = io::BufReader::new(std::fs::File::open(path)?);
+        serde_cbor::from_reader(f).map_err(io::Error::other)
+    }
+
+    // asciicast v2: a header line, then one [time, "o", output] line per frame
+    pub fn write_asciicast<W:Write>(&self, out:&mut W) -> io::Result<()> {
+        let header = serde_json::json!({
+            "version": 2,
+            "width": self.size.x,
+            "height": self.size.y,
+            "env": { "TERM": "xterm-256color" },
+        });
+        writeln!(out, "{}", header)?;
+        for (i, f) in self.frames.iter().enumerate() {
+            if let Some(size) = f.size {
+                writeln!(out, "{}", serde_json::json!([f.t, "r", format!("{}x{}", size.x, size.y)]))?;
+            }
+            let mut data = if i == 0 || f.size.is_some() { b"\x1b[?25l\x1b[2J".to_vec() } else { Vec::new() };
+            data.extend(ansi::cell_escapes(f.cells.iter().cloned(), ColorMode::TrueColor));
+            if data.is_empty() { continue; }
+            let data = String::from_utf8(data).map_err(io::Error::other)?;
+            writeln!(out, "{}", serde_json::json!([f.t, "o", data]))?;
+        }
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// passes everything through to another renderer, recording each refreshed frame
+// (as if flat: layered cells are recorded where they'd land on one layer)
+pub struct RecordingRenderer {
+    inner: Box<dyn Renderer>,
+    screen: Grid<Glyph>,
+    last: Option<Grid<Glyph>>,
+    recording: Recording,
+    t: Duration,
+    save_to: Option<PathBuf>,
+}
+
+impl RecordingRenderer {
+    pub fn new(inner:Box<dyn Renderer>) -> Self {
+        let size = inner.size();
+        let screen = Grid::new(Rect2d::new(Point2d::new(0,0), size), Glyph::default());
+        RecordingRenderer { inner, screen, last:None, recording:Recording::new(size), t:Duration::default(), save_to:None }
+    }
+
+    // saves the recording to path when dropped
+    pub fn saving_to(mut self, path:&Path) -> Self {
+        self.save_to = Some(path.to_path_buf());
+        self
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+}
+
+impl Renderer for RecordingRenderer {
+    fn size(&self) -> Point2d {
+        self.inner.size()
+    }
+
+    fn put(&mut self, p:Point2d, g:Glyph) {
+        self.screen.set(p, g);
+        self.inner.put(p, g);
+    }
+
+    fn print(&mut self, p:Point2d, s:&str, fg:Color, bg:Color) {
+        for (i, ch) in s.chars().enumerate() {
+            self.screen.set(p + Point2d::new(i as i32, 0), Glyph::new(ch, fg, bg));
+        }
+        self.inner.print(p, s, fg, bg);
+    }
+
+    fn clear(&mut self) {
+        self.screen.fill(Glyph::default());
+        self.inner.clear();
+    }
+
+    fn refresh(&mut self) {
+        self.recording.push(self.t, &self.screen, self.last.as_ref());
+        self.last = Some(self.screen.clone());
+        self.inner.refresh();
+    }
+
+    // (a resize starts the next frame afresh, at the new size)
+    fn poll_event(&mut self) -> Option<InputEvent> {
+        let ev = self.inner.poll_event();
+        if let Some(InputEvent::Resize(size)) = ev {
+            self.screen = Grid::new(Rect2d::new(Point2d::new(0,0), size), Glyph::default());
+        }
+        ev
+    }
+
+    fn set_frame_time(&mut self, t:Duration) {
+        self.t = t;
+        self.inner.set_frame_time(t);
+    }
+
+    // (the regions to record are noted before the inner renderer takes them)
+    fn draw_window(&mut self, window:&WindowHandle<Glyph>) {
+        window.update_data();
+        let dirty = window.dirty();
+        self.inner.draw_window(window);
+        for r in dirty {
+            for p in r.intersection(&self.screen.span()).into_iter().flatten() {
+                if let Some(g) = window.data(p) { self.screen.set(p, g); }
+            }
+        }
+    }
+
+    fn has_layers(&self) -> bool {
+        self.inner.has_layers()
+    }
+
+    fn cell_pixels(&self) -> Point2d {
+        self.inner.cell_pixels()
+    }
+
+    fn put_ext(&mut self, layer:i32, p:Point2d, offset:Point2d, g:Glyph, corners:Option<[Color; 4]>) {
+        let (q, flat) = flatten_ext(self.inner.cell_pixels(), p, offset, g, corners);
+        self.screen.set(q, flat);
+        self.inner.put_ext(layer, p, offset, g, corners);
+    }
+
+    fn clear_layer(&mut self, layer:i32, area:Rect2d) {
+        self.inner.clear_layer(layer, area);
+    }
+}
+
+impl Drop for RecordingRenderer {
+    fn drop(&mut self) {
+        if let Some(path) = self.save_to.take() {
+            if let Err(e) = self.recording.save(&path) {
+                eprintln!("cannot save recording to {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// replays a recording at the given speed; returns false if stopped early (escape or q)
+pub fn play(rec:&Recording, renderer:&mut dyn Renderer, speed:f64) -> bool {
+    let start = Instant::now();
+    for f in rec.frames.iter() {
+        let due = Duration::from_secs_f64(f.t / speed.max(1e-3));
+        loop {
+            while let Some(ev) = renderer.poll_event() {
+                match ev {
+                    InputEvent::Key { key:KeyCode::Escape, .. } |
+                    InputEvent::Key { key:KeyCode::Q, .. } |
+                    InputEvent::Close => { return false; }
+                    _ => {}
+                }
+            }
+            let now = start.elapsed();
+            if now >= due { break; }
+            sleep((due - now).min(Duration::from_millis(20)));
+        }
+        if f.size.is_some() { renderer.clear(); }
+        for (p, g) in f.cells.iter() {
+            renderer.put(*p, *g);
+        }
+        renderer.refresh();
+    }
+    true
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{RefCell};
+    use std::rc::{Rc};
+    use crate::renderer::{HeadlessRenderer};
+
+    fn record() -> Recording {
+        let mut r = RecordingRenderer::new(Box::new(HeadlessRenderer::new(Point2d::new(6,2))));
+        for i in 0..4 {
+            r.set_frame_time(Duration::from_millis(100 * i as u64));
+            r.put(Point2d::new(i, 1), Glyph::new('@', Color::gold(), Color::black()));
+            r.refresh();
+        }
+        r.recording().clone()
+    }
+
+    #[test]
+    fn diffs_roundtrip_through_playback() {
+        let rec = record();
+        assert_eq!(rec.frames.len(), 4);
+        assert_eq!(rec.frames[0].cells.len(), 12);
+        assert_eq!(rec.frames[3].cells, vec![(Point2d::new(3,1), Glyph::new('@', Color::gold(), Color::black()))]);
+        let bytes = serde_cbor::to_vec(&rec).unwrap();
+        let rec2 : Recording = serde_cbor::from_slice(&bytes).unwrap();
+        let mut h = HeadlessRenderer::new(rec2.size);
+        assert!(play(&rec2, &mut h, 100.0));
+        let (_, last) = rec.screens().last().unwrap();
+        assert_eq!(h.frames().len(), 4);
+        assert_eq!(h.last_frame().unwrap().cells(), last.cells());
+        assert_eq!(h.row_text(1), "@@@@\0\0");
+    }
+
+    #[test]
+    fn asciicast_export() {
+        let rec = record();
+        let mut out = Vec::new();
+        rec.write_asciicast(&mut out).unwrap();
+        let lines : Vec<serde_json::Value> = String::from_utf8(out).unwrap().lines()
+            .map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["width"], 6);
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[2][0], 0.1);
+        assert_eq!(lines[2][1], "o");
+        assert_eq!(lines[2][2], "\x1b[2;2H\x1b[38;2;165;124;0m\x1b[48;2;0;0;0m@");
+    }
+
+    // a pretend layered backend, logging what it's asked to draw
+    struct Layered(Rc<RefCell<Vec<String>>>);
+
+    impl Renderer for Layered {
+        fn size(&self) -> Point2d { Point2d::new(6,3) }
+        fn put(&mut self, _p:Point2d, _g:Glyph) {}
+        fn clear(&mut self) {}
+        fn refresh(&mut self) {}
+        fn poll_event(&mut self) -> Option<InputEvent> { None }
+        fn draw_window(&mut self, window:&WindowHandle<Glyph>) {
+            self.0.borrow_mut().push(format!("draw {:?}", window.take_dirty()));
+        }
+        fn has_layers(&self) -> bool { true }
+        fn cell_pixels(&self) -> Point2d { Point2d::new(8,16) }
+        fn put_ext(&mut self, layer:i32, p:Point2d, offset:Point2d, g:Glyph, _corners:Option<[Color; 4]>) {
+            self.0.borrow_mut().push(format!("{} at {}{} on {}", g.ch, p, offset, layer));
+        }
+        fn clear_layer(&mut self, layer:i32, _area:Rect2d) {
+            self.0.borrow_mut().push(format!("clear {}", layer));
+        }
+    }
+
+    #[test]
+    fn layers_forwarded_and_flattened() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut r = RecordingRenderer::new(Box::new(Layered(log.clone())));
+        assert!(r.has_layers());
+        assert_eq!(r.cell_pixels(), Point2d::new(8,16));
+        let root = WindowHandle::new("Root".into(), Rect2d::new(Point2d::new(0,0), Point2d::new(6,3)));
+        let child = root.new_child("Child".into(), Point2d::new(1,1), Rect2d::new(Point2d::new(0,0), Point2d::new(2,1)));
+        root.fill(Glyph::new('.', Color::grey(), Color::black()));
+        child.fill(Glyph::new('#', Color::gold(), Color::black()));
+        r.draw_window(&root);
+        // (nearest the cell below & left of (4,0), in the corners' average)
+        let star = Glyph::new('*', Color::gold(), Color::transparent());
+        r.put_ext(2, Point2d::new(4,0), Point2d::new(-5,20), star, Some([Color::white(), Color::black(), Color::white(), Color::black()]));
+        r.clear_layer(2, Rect2d::new(Point2d::new(0,0), Point2d::new(1,1)));
+        r.refresh();
+        let (_, screen) = r.recording().screens().last().unwrap();
+        let row = |y| (0..6).map(|x| screen.get(Point2d::new(x, y)).unwrap().ch).collect::<String>();
+        assert_eq!([row(0), row(1), row(2)], ["......", ".##*..", "......"]);
+        assert_eq!(screen.get(Point2d::new(3,1)).unwrap().fg, Color::rgb(128,128,128));
+        assert_eq!(*log.borrow(), ["draw [[(0,0):(6,3)]]", "* at (4,0)(-5,20) on 2", "clear 2"]);
+    }
+
+    #[test]
+    fn resizes_recorded() {
+        let mut h = HeadlessRenderer::new(Point2d::new(4,2));
+        h.push_input(InputEvent::Resize(Point2d::new(3,3)));
+        let mut r = RecordingRenderer::new(Box::new(h));
+        r.put(Point2d::new(3,1), Glyph::new('@', Color::gold(), Color::black()));
+        r.refresh();
+        assert_eq!(r.poll_event(), Some(InputEvent::Resize(Point2d::new(3,3))));
+        assert_eq!(r.size(), Point2d::new(3,3));
+        r.put(Point2d::new(1,2), Glyph::new('#', Color::gold(), Color::black()));
+        r.refresh();
+        r.refresh();
+        let rec = r.recording().clone();
+        assert_eq!(rec.size, Point2d::new(4,2));
+        assert_eq!(rec.frames.iter().map(|f| (f.size, f.cells.len())).collect::<Vec<_>>(),
+            vec![(None, 8), (Some(Point2d::new(3,3)), 9), (None, 0)]);
+        let rec : Recording = serde_cbor::from_slice(&serde_cbor::to_vec(&rec).unwrap()).unwrap();
+        let (_, last) = rec.screens().last().unwrap();
+        assert_eq!(last.span().size(), Point2d::new(3,3));
+        assert_eq!(last.get(Point2d::new(1,2)).unwrap().ch, '#');
+        let mut out = Vec::new();
+        rec.write_asciicast(&mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().lines().nth(2).unwrap().ends_with(",\"r\",\"3x3\"]"));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
@@ -51,16 +51,16 @@ impl Rect2d {
     pub fn intersects(&self, other:&Rect2d) -> bool {
         let bl_x = self.bl.x.max(other.bl.x);
         let bl_y = self.bl.y.max(other.bl.y);
-        let tr_x = self.tr.x.max(other.tr.x);
-        let tr_y = self.tr.y.max(other.tr.y);
+        let tr_x = self.tr.x.min(other.tr.x);
+        let tr_y = self.tr.y.min(other.tr.y);
         (bl_x < tr_x) && (bl_y < tr_y)
     }
 
     pub fn intersection(&self, other:&Rect2d) -> Option<Rect2d> {
         let bl_x = self.bl.x.max(other.bl.x);
         let bl_y = self.bl.y.max(other.bl.y);
-        let tr_x = self.tr.x.max(other.tr.x);
-        let tr_y = self.tr.y.max(other.tr.y);
+        let tr_x = self.tr.x.min(other.tr.x);
+        let tr_y = self.tr.y.min(other.tr.y);
         if (bl_x < tr_x) && (bl_y < tr_y) {
             Some(Rect2d{bl:Point2d{x:bl_x,y:bl_y}, tr:Point2d{x:tr_x,y:tr_y}})
         } else {
@@ -89,6 +89,137 @@ impl Rect2d {
         }
     }
 
+    #[inline]
+    pub fn area(&self) -> i32 {
+        let size = self.size();
+        size.x * size.y
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bl.x >= self.tr.x || self.bl.y >= self.tr.y
+    }
+
+    // (rounded toward bl)
+    pub fn center(&self) -> Point2d {
+        self.bl + self.size()/2
+    }
+
+    pub fn contains_rect(&self, other:&Rect2d) -> bool {
+        self.bl << other.bl && other.tr << self.tr
+    }
+
+    // nearest point in rect (assumes non-empty)
+    pub fn clamp(&self, p:Point2d) -> Point2d {
+        Point2d::new(p.x.max(self.bl.x).min(self.tr.x-1), p.y.max(self.bl.y).min(self.tr.y-1))
+    }
+
+    // smallest rect containing both (empty rects are ignored)
+    pub fn union(&self, other:&Rect2d) -> Rect2d {
+        if self.is_empty() { return *other; }
+        if other.is_empty() { return *self; }
+        Rect2d {
+            bl:Point2d::new(self.bl.x.min(other.bl.x), self.bl.y.min(other.bl.y)),
+            tr:Point2d::new(self.tr.x.max(other.tr.x), self.tr.y.max(other.tr.y)),
+        }
+    }
+
+    // smallest rect containing all the (non-empty) rects given
+    pub fn bounding<I:IntoIterator<Item=Rect2d>>(rects:I) -> Option<Rect2d> {
+        rects.into_iter()
+            .filter(|r| !r.is_empty())
+            .fold(None, |acc:Option<Rect2d>, r| Some(acc.map_or(r, |a| a.union(&r))))
+    }
+
+    // smallest rect containing all the points given
+    pub fn bounding_points<I:IntoIterator<Item=Point2d>>(points:I) -> Option<Rect2d> {
+        Rect2d::bounding(points.into_iter().map(|p| Rect2d{bl:p, tr:p+Point2d::new(1,1)}))
+    }
+
+    // shrinks each side by d (grows, for negative d);
+    // collapses to an empty rect at the center rather than inverting
+    pub fn inset(&self, d:i32) -> Rect2d {
+        self.inset_xy(d, d)
+    }
+
+    pub fn inset_xy(&self, dx:i32, dy:i32) -> Rect2d {
+        let c = self.center();
+        let (bl_x, tr_x) =
+            if self.size().x <= 2*dx { (c.x, c.x) } else { (self.bl.x+dx, self.tr.x-dx) };
+        let (bl_y, tr_y) =
+            if self.size().y <= 2*dy { (c.y, c.y) } else { (self.bl.y+dy, self.tr.y-dy) };
+        Rect2d { bl:Point2d::new(bl_x, bl_y), tr:Point2d::new(tr_x, tr_y) }
+    }
+
+    pub fn outset(&self, d:i32) -> Rect2d {
+        self.inset(-d)
+    }
+
+    // splits into [bl.x,x) and [x,tr.x); None unless x is strictly inside
+    pub fn split_x(&self, x:i32) -> Option<(Rect2d,Rect2d)> {
+        if x <= self.bl.x || x >= self.tr.x { return None; }
+        Some((Rect2d{bl:self.bl, tr:Point2d::new(x, self.tr.y)},
+              Rect2d{bl:Point2d::new(x, self.bl.y), tr:self.tr}))
+    }
+
+    // splits into [bl.y,y) and [y,tr.y); None unless y is strictly inside
+    pub fn split_y(&self, y:i32) -> Option<(Rect2d,Rect2d)> {
+        if y <= self.bl.y || y >= self.tr.y { return None; }
+        Some((Rect2d{bl:self.bl, tr:Point2d::new(self.tr.x, y)},
+              Rect2d{bl:Point2d::new(self.bl.x, y), tr:self.tr}))
+    }
+
+    // cuts into n columns (n rows) of near-equal size, wider (taller) ones first
+    pub fn split_columns(&self, n:usize) -> Vec<Rect2d> {
+        Rect2d::split_extent(self.bl.x, self.size().x, n).into_iter()
+            .map(|(a,b)| Rect2d{bl:Point2d::new(a, self.bl.y), tr:Point2d::new(b, self.tr.y)})
+            .collect()
+    }
+
+    pub fn split_rows(&self, n:usize) -> Vec<Rect2d> {
+        Rect2d::split_extent(self.bl.y, self.size().y, n).into_iter()
+            .map(|(a,b)| Rect2d{bl:Point2d::new(self.bl.x, a), tr:Point2d::new(self.tr.x, b)})
+            .collect()
+    }
+
+    fn split_extent(start:i32, len:i32, n:usize) -> Vec<(i32,i32)> {
+        if n == 0 { return vec![]; }
+        let n = n as i32;
+        let (q, r) = (len / n, len % n);
+        let mut res = Vec::new();
+        let mut a = start;
+        for i in 0 .. n {
+            let b = a + q + if i < r {1} else {0};
+            res.push((a, b));
+            a = b;
+        }
+        res
+    }
+
+    // self minus other, as (at most 4) disjoint rects:
+    // full-width strips below & above other, then the pieces left & right of it
+    pub fn subtract(&self, other:&Rect2d) -> Vec<Rect2d> {
+        let mut res = Vec::new();
+        if self.is_empty() { return res; }
+        let cut = match self.intersection(other) {
+            Some(cut) => cut,
+            None => { res.push(*self); return res; }
+        };
+        if self.bl.y < cut.bl.y {
+            res.push(Rect2d{bl:self.bl, tr:Point2d::new(self.tr.x, cut.bl.y)});
+        }
+        if cut.tr.y < self.tr.y {
+            res.push(Rect2d{bl:Point2d::new(self.bl.x, cut.tr.y), tr:self.tr});
+        }
+        if self.bl.x < cut.bl.x {
+            res.push(Rect2d{bl:Point2d::new(self.bl.x, cut.bl.y), tr:Point2d::new(cut.bl.x, cut.tr.y)});
+        }
+        if cut.tr.x < self.tr.x {
+            res.push(Rect2d{bl:Point2d::new(cut.tr.x, cut.bl.y), tr:Point2d::new(self.tr.x, cut.tr.y)});
+        }
+        res
+    }
+
     pub fn iter(&self) -> impl Iterator<Item=Point2d> {
         self.into_iter()
     }
@@ -203,3 +334,102 @@ impl Iterator for RectBoundaryIterator {
 }
 
 ////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::{Generator, Rng};
+
+    fn rand_rect(rng:&mut Rng) -> Rect2d {
+        let p1 = Point2d::new(rng.gen_range(-10,10), rng.gen_range(-10,10));
+        let p2 = Point2d::new(rng.gen_range(-10,10), rng.gen_range(-10,10));
+        Rect2d::new(p1, p2)
+    }
+
+    fn rand_point(rng:&mut Rng) -> Point2d {
+        Point2d::new(rng.gen_range(-12,12), rng.gen_range(-12,12))
+    }
+
+    #[test]
+    fn intersection_is_pointwise_and() {
+        let mut rng = Rng::from_seed([1,2,3,4]);
+        for _ in 0 .. 500 {
+            let (a, b) = (rand_rect(&mut rng), rand_rect(&mut rng));
+            let i = a.intersection(&b);
+            assert_eq!(i.is_some(), a.intersects(&b));
+            for _ in 0 .. 20 {
+                let p = rand_point(&mut rng);
+                let in_both = a.contains(p) && b.contains(p);
+                assert_eq!(in_both, i.is_some_and(|i| i.contains(p)), "{} {} {}", a, b, p);
+            }
+        }
+    }
+
+    #[test]
+    fn union_and_bounding_contain_inputs() {
+        let mut rng = Rng::from_seed([5,6,7,8]);
+        for _ in 0 .. 500 {
+            let rs : Vec<Rect2d> = (0..4).map(|_| rand_rect(&mut rng)).collect();
+            let bb = Rect2d::bounding(rs.iter().cloned());
+            for r in rs.iter().filter(|r| !r.is_empty()) {
+                assert!(bb.unwrap().contains_rect(r));
+                assert!(r.union(&rs[0]).contains_rect(r));
+            }
+            let ps : Vec<Point2d> = (0..6).map(|_| rand_point(&mut rng)).collect();
+            let pb = Rect2d::bounding_points(ps.iter().cloned()).unwrap();
+            assert!(ps.iter().all(|&p| pb.contains(p)));
+            assert_eq!(pb.area(), (pb.size().x)*(pb.size().y));
+        }
+    }
+
+    #[test]
+    fn inset_outset_roundtrip() {
+        let mut rng = Rng::from_seed([9,10,11,12]);
+        for _ in 0 .. 500 {
+            let r = rand_rect(&mut rng);
+            let d = rng.gen_range(0,5);
+            assert_eq!(r.outset(d).inset(d), r);
+            let i = r.inset(d);
+            assert!(i.is_empty() || r.contains_rect(&i));
+        }
+    }
+
+    #[test]
+    fn splits_partition() {
+        let mut rng = Rng::from_seed([13,14,15,16]);
+        for _ in 0 .. 500 {
+            let r = rand_rect(&mut rng);
+            let x = rng.gen_range(-11,11);
+            if let Some((a, b)) = r.split_x(x) {
+                assert_eq!(a.area() + b.area(), r.area());
+                assert!(!a.intersects(&b));
+            }
+            let n = rng.gen_range(1usize,6);
+            let rows = r.split_rows(n);
+            assert_eq!(rows.len(), n);
+            assert_eq!(rows.iter().map(|q| q.area()).sum::<i32>(), r.area());
+            let sizes : Vec<i32> = rows.iter().map(|q| q.size().y).collect();
+            assert!(sizes.iter().max().unwrap() - sizes.iter().min().unwrap() <= 1);
+        }
+    }
+
+    #[test]
+    fn subtract_is_pointwise_difference() {
+        let mut rng = Rng::from_seed([17,18,19,20]);
+        for _ in 0 .. 500 {
+            let (a, b) = (rand_rect(&mut rng), rand_rect(&mut rng));
+            let d = a.subtract(&b);
+            for (i, r) in d.iter().enumerate() {
+                assert!(!r.is_empty());
+                for s in d[i+1..].iter() { assert!(!r.intersects(s)); }
+            }
+            for _ in 0 .. 20 {
+                let p = rand_point(&mut rng);
+                let in_diff = a.contains(p) && !b.contains(p);
+                assert_eq!(in_diff, d.iter().any(|r| r.contains(p)), "{} - {} @ {}", a, b, p);
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
@@ -0,0 +1,116 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::grid::*;
+use crate::point2d::*;
+use crate::rect2d::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+// a single connected component, as found by label_components
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub struct Region {
+    pub label: usize,       // matches value in label grid (never 0)
+    pub size: usize,        // number of cells
+    pub bounds: Rect2d,     // smallest rect containing all cells
+}
+
+// labels connected cells satisfying pred:
+//  - returned grid has the same span as the input, 0 for cells not satisfying pred,
+//    otherwise the (1-based) label of the containing region
+//  - regions are returned in label order (ie regions[i].label == i+1)
+pub fn label_components<T:Clone, Pred:FnMut(&T)->bool>(
+    grid:&Grid<T>, pred:&mut Pred, diagonal:bool) -> (Grid<usize>, Vec<Region>)
+{
+    let span = grid.span();
+    let mut labels = Grid::new(span, 0usize);
+    let mut regions = Vec::new();
+    let mut stack = Vec::new();
+    for p in span {
+        if *labels.get(p).unwrap() != 0 || !pred(grid.get(p).unwrap()) { continue; }
+        let label = regions.len() + 1;
+        let mut size = 0;
+        let mut lo = p;
+        let mut hi = p;
+        labels.set(p, label);
+        stack.push(p);
+        while let Some(q) = stack.pop() {
+            size += 1;
+            lo = Point2d::new(lo.x.min(q.x), lo.y.min(q.y));
+            hi = Point2d::new(hi.x.max(q.x), hi.y.max(q.y));
+            let ns : Vec<Point2d> =
+                if diagonal { Point2d::neighbors8(q).collect() }
+                else { Point2d::neighbors4(q).collect() };
+            for n in ns {
+                if labels.get(n) == Some(&0) && pred(grid.get(n).unwrap()) {
+                    labels.set(n, label);
+                    stack.push(n);
+                }
+            }
+        }
+        let bounds = Rect2d::new(lo, hi + Point2d::new(1,1));
+        regions.push(Region { label, size, bounds });
+    }
+    (labels, regions)
+}
+
+// largest region (ties go to the lowest label), if any
+pub fn largest_region(regions:&[Region]) -> Option<Region> {
+    regions.iter().fold(None, |best:Option<Region>, r|
+        match best {
+            Some(b) if b.size >= r.size => Some(b),
+            _ => Some(*r),
+        })
+}
+
+// overwrites every cell of grid whose label is not in keep with fill,
+// eg to wall-off disconnected pockets after generation
+pub fn retain_regions<T:Clone>(grid:&mut Grid<T>, labels:&Grid<usize>, keep:&[usize], fill:T) -> usize {
+    let mut count = 0;
+    for p in labels.span() {
+        let l = *labels.get(p).unwrap();
+        if l != 0 && !keep.contains(&l) {
+            grid.set(p, fill.clone());
+            count += 1;
+        }
+    }
+    count
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_of(rows:&[&str]) -> Grid<char> {
+        let span = Rect2d::new(Point2d::new(0,0), Point2d::new(rows[0].len() as i32, rows.len() as i32));
+        let mut grid = Grid::new(span, '.');
+        for p in span {
+            grid.set(p, rows[p.y as usize].as_bytes()[p.x as usize] as char);
+        }
+        grid
+    }
+
+    #[test]
+    fn components() {
+        let mut grid = grid_of(&["##...", "##...", "..#..", "...##"]);
+        let rect = |x0, y0, x1, y1| Rect2d::new(Point2d::new(x0,y0), Point2d::new(x1,y1));
+        let (labels, regions) = label_components(&grid, &mut |c| *c == '#', false);
+        assert_eq!(regions, vec![
+            Region { label:1, size:4, bounds:rect(0,0,2,2) },
+            Region { label:2, size:1, bounds:rect(2,2,3,3) },
+            Region { label:3, size:2, bounds:rect(3,3,5,4) }]);
+        assert_eq!(labels.get(Point2d::new(4,3)), Some(&3));
+        assert_eq!(labels.get(Point2d::new(2,0)), Some(&0));
+        assert_eq!(largest_region(&regions).map(|r| r.label), Some(1));
+        assert_eq!(largest_region(&[]), None);
+        // diagonally it's all one (all 8 neighbours are looked at)
+        assert_eq!(Point2d::neighbors8(Point2d::new(0,0)).collect::<std::collections::HashSet<_>>().len(), 8);
+        let (_, joined) = label_components(&grid, &mut |c| *c == '#', true);
+        assert_eq!(joined, vec![Region { label:1, size:7, bounds:rect(0,0,5,4) }]);
+        assert_eq!(retain_regions(&mut grid, &labels, &[1], '.'), 3);
+        assert_eq!(grid.cells(), grid_of(&["##...", "##...", ".....", "....."]).cells());
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
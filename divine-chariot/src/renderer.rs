@@ -0,0 +1,426 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use std::collections::{VecDeque};
+use std::time::{Duration};
+
+use bear_lib_terminal::{geometry, terminal};
+use bear_lib_terminal::terminal::{KeyCode};
+use bear_lib_terminal::terminal::config::{ConfigError};
+
+use crate::core_systems::*;
+use crate::grid::*;
+use crate::mouse::{MouseInput};
+use crate::point2d::*;
+use crate::rect2d::*;
+use crate::window::{WindowHandle, WindowWeakHandle};
+
+////////////////////////////////////////////////////////////////////////////////
+
+// backend-independent input
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum InputEvent {
+    Key { key:KeyCode, ctrl:bool, shift:bool, alt:bool },
+    Text(char),         // printable char typed (after its Key, where there is one)
+    Mouse(MouseInput),
+    Resize(Point2d),
+    Close,
+}
+
+impl InputEvent {
+    pub fn key(key:KeyCode) -> InputEvent {
+        InputEvent::Key { key, ctrl:false, shift:false, alt:false }
+    }
+}
+
+// something glyphs can be drawn to & input read from;
+// (0,0) is the top-left cell, as for the root window
+pub trait Renderer {
+    fn size(&self) -> Point2d;
+
+    fn put(&mut self, p:Point2d, g:Glyph);
+
+    fn print(&mut self, p:Point2d, s:&str, fg:Color, bg:Color) {
+        for (i, ch) in s.chars().enumerate() {
+            self.put(p + Point2d::new(i as i32, 0), Glyph::new(ch, fg, bg));
+        }
+    }
+
+    fn clear(&mut self);
+
+    // presents everything drawn since the last refresh
+    fn refresh(&mut self);
+
+    // next pending input, without blocking
+    fn poll_event(&mut self) -> Option<InputEvent>;
+
+    // timestamp of the frame about to be refreshed (only of interest when recording)
+    fn set_frame_time(&mut self, _t:Duration) {}
+
+    // draws the regions of a root window changed since last drawn;
+    // backends with layers may draw its children on layers of their own
+    fn draw_window(&mut self, window:&WindowHandle<Glyph>) {
+        draw_flat(self, window);
+    }
+
+    // whether put_ext() really lands on layers, at pixel offsets, in gradients
+    fn has_layers(&self) -> bool { false }
+
+    // size of a cell in pixels, what put_ext() offsets are measured in
+    fn cell_pixels(&self) -> Point2d { Point2d::new(1,1) }
+
+    // draws g shifted by a pixel offset onto a layer (0 being the bottom),
+    // its foreground shaded between corner colours (top-left, bottom-left,
+    // bottom-right, top-right); the flat fallback puts it in the nearest cell,
+    // in the corners' average, straight onto the screen
+    fn put_ext(&mut self, _layer:i32, p:Point2d, offset:Point2d, g:Glyph, corners:Option<[Color; 4]>) {
+        put_ext_flat(self, p, offset, g, corners);
+    }
+
+    // clears an area of a layer; without layers whatever put_ext() drew over
+    // stays until the window beneath redraws it (ie after mark_dirty())
+    fn clear_layer(&mut self, _layer:i32, _area:Rect2d) {}
+}
+
+// draws the root window's composited cells
+pub fn draw_flat<R:Renderer+?Sized>(r:&mut R, window:&WindowHandle<Glyph>) {
+    window.update_data();
+    for dirty in window.take_dirty() {
+        for p in dirty {
+            r.put(p, window.data(p).unwrap());
+        }
+    }
+}
+
+pub fn put_ext_flat<R:Renderer+?Sized>(r:&mut R, p:Point2d, offset:Point2d, g:Glyph, corners:Option<[Color; 4]>) {
+    let (q, g) = flatten_ext(r.cell_pixels(), p, offset, g, corners);
+    r.put(q, g);
+}
+
+// the cell & glyph a put_ext() comes nearest to, given the cell size in pixels
+pub fn flatten_ext(cell:Point2d, p:Point2d, offset:Point2d, g:Glyph, corners:Option<[Color; 4]>) -> (Point2d,Glyph) {
+    let nearest = |o:i32, c:i32| (o as f64 / c.max(1) as f64).round() as i32;
+    let q = p + Point2d::new(nearest(offset.x, cell.x), nearest(offset.y, cell.y));
+    let fg = corners.map(|c| average_color(&c)).unwrap_or(g.fg);
+    (q, Glyph::new(g.ch, fg, g.bg))
+}
+
+pub fn average_color(cs:&[Color]) -> Color {
+    let n = cs.len().max(1) as u32;
+    let avg = |f:fn(&Color)->u8| ((cs.iter().map(|c| f(c) as u32).sum::<u32>() + n/2) / n) as u8;
+    Color::rgba(avg(Color::r), avg(Color::g), avg(Color::b), avg(Color::a))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// BearLibTerminal has 256 layers
+const MAX_LAYERS : usize = 256;
+
+// the real terminal, closed again when dropped
+pub struct BltRenderer {
+    size: Point2d,
+    pending: VecDeque<InputEvent>,
+    layered: bool,                          // root window's children drawn on layers of their own
+    stack: Vec<WindowWeakHandle<Glyph>>,    // what went on layers 1.. last frame
+}
+
+impl BltRenderer {
+    // (closed again if the window or input can't be set up)
+    pub fn open(title:&str, size:Point2d) -> Result<Self, ConfigError> {
+        terminal::open(title, size.x as u32, size.y as u32);
+        let r = BltRenderer { size, pending:VecDeque::new(), layered:false, stack:Vec::new() };
+        terminal::try_set(terminal::config::Window::empty()
+            .title(title.to_string())
+            .size(geometry::Size::new(size.x, size.y))
+            .cellsize(terminal::config::Cellsize::Auto)
+            .resizeable(true))?;
+        terminal::try_set(terminal::config::InputFilterSet::all())?;
+        Ok(r)
+    }
+
+    pub fn with_layers(mut self, layered:bool) -> Self {
+        // (so a cell's background block & glyph stack)
+        terminal::composition(layered);
+        self.layered = layered;
+        self
+    }
+
+    // the root window's own cells on layer 0, & each child on the layer above
+    // the one beneath it, shifted by its pixel offset; layers above 0 have no
+    // backgrounds, so those are blocks beneath the glyphs, blended by the
+    // terminal (any BlendMode but Replace being taken as Normal)
+    fn draw_layers(&mut self, root:&WindowHandle<Glyph>) {
+        root.update_data();
+        let span = root.span();
+        let children : Vec<_> = root.children().into_iter().take(MAX_LAYERS - 1).collect();
+        let n = children.len() + 1;
+        let mut dirty = root.take_dirty();
+        // (a window opened, closed or restacked moves the others to other layers)
+        let same = self.stack.len() == children.len() && self.stack.iter().zip(children.iter())
+            .all(|(w, ch)| w.upgrade().is_some_and(|w| WindowHandle::ptr_eq(&w, ch)));
+        if !same {
+            for z in 1 .. self.stack.len().max(children.len()) + 1 {
+                self.clear_layer(z as i32, span);
+            }
+            self.stack = children.iter().map(|ch| ch.weak()).collect();
+            dirty = vec![span];
+        }
+        for r in dirty {
+            let r = match r.intersection(&span) { Some(r) => r, None => { continue; } };
+            for z in 0 .. n {
+                self.clear_layer(z as i32, r);
+            }
+            terminal::layer(0);
+            for p in r {
+                BltRenderer::put_cell(p, root.get(p).unwrap());
+            }
+            for (z, ch) in children.iter().enumerate() {
+                if ch.get_hidden() { continue; }
+                let shift = ch.offset() - ch.span().bl;
+                let area = match (ch.span() + shift).intersection(&r) { Some(a) => a, None => { continue; } };
+                let (offset, opaque) = (ch.get_pixel_offset(), ch.get_blend() == BlendMode::Replace);
+                terminal::layer(z as i32 + 1);
+                for p in area {
+                    let mut g = ch.data(p - shift).unwrap();
+                    if opaque { g = Glyph::new(g.ch, g.fg.with_alpha(255), g.bg.with_alpha(255)); }
+                    BltRenderer::put_layered(p, offset, g, None);
+                }
+            }
+        }
+        terminal::layer(0);
+    }
+
+    fn put_cell(p:Point2d, g:Glyph) {
+        terminal::set_background(g.bg.blt());
+        terminal::set_foreground(g.fg.blt());
+        terminal::put_xy(p.x, p.y, g.ch);
+    }
+
+    // a background block (where not transparent) then the glyph
+    fn put_layered(p:Point2d, offset:Point2d, g:Glyph, corners:Option<[Color; 4]>) {
+        let (p, offset) = (geometry::Point::new(p.x, p.y), geometry::Point::new(offset.x, offset.y));
+        if g.bg.a() > 0 {
+            terminal::put_ext(p, offset, '█', &vec![g.bg.blt(); 4]);
+        }
+        if g.ch != '\0' && (g.fg.a() > 0 || corners.is_some()) {
+            let corners = corners.unwrap_or([g.fg; 4]);
+            terminal::put_ext(p, offset, g.ch, &corners.iter().map(Color::blt).collect());
+        }
+    }
+
+    fn translate(ev:&terminal::Event) -> Option<InputEvent> {
+        if let Some(mi) = MouseInput::from_blt(ev) {
+            return Some(InputEvent::Mouse(mi));
+        }
+        match *ev {
+            terminal::Event::KeyPressed{key, ctrl, shift, alt, ..} => Some(InputEvent::Key { key, ctrl, shift, alt }),
+            terminal::Event::Resize{width, height} => Some(InputEvent::Resize(Point2d::new(width, height))),
+            terminal::Event::Close => Some(InputEvent::Close),
+            _ => None,
+        }
+    }
+}
+
+impl Renderer for BltRenderer {
+    fn size(&self) -> Point2d {
+        self.size
+    }
+
+    fn put(&mut self, p:Point2d, g:Glyph) {
+        // (composited, a put adds to what's there)
+        if self.layered { self.clear_layer(0, Rect2d::new(p, p + Point2d::new(1,1))); }
+        BltRenderer::put_cell(p, g);
+    }
+
+    fn print(&mut self, p:Point2d, s:&str, fg:Color, bg:Color) {
+        if self.layered { self.clear_layer(0, Rect2d::new(p, p + Point2d::new(s.chars().count() as i32, 1))); }
+        terminal::set_background(bg.blt());
+        terminal::set_foreground(fg.blt());
+        terminal::print_xy(p.x, p.y, s);
+    }
+
+    fn clear(&mut self) {
+        terminal::clear(None);
+    }
+
+    fn refresh(&mut self) {
+        terminal::refresh();
+    }
+
+    fn draw_window(&mut self, window:&WindowHandle<Glyph>) {
+        if self.layered { self.draw_layers(window); } else { draw_flat(self, window); }
+    }
+
+    fn has_layers(&self) -> bool {
+        self.layered
+    }
+
+    fn cell_pixels(&self) -> Point2d {
+        let size = terminal::state::cell_size();
+        Point2d::new(size.width, size.height)
+    }
+
+    fn put_ext(&mut self, layer:i32, p:Point2d, offset:Point2d, g:Glyph, corners:Option<[Color; 4]>) {
+        if !self.layered { return put_ext_flat(self, p, offset, g, corners); }
+        terminal::layer(layer);
+        BltRenderer::put_layered(p, offset, g, corners);
+        terminal::layer(0);
+    }
+
+    fn clear_layer(&mut self, layer:i32, area:Rect2d) {
+        if !self.layered { return; }
+        terminal::layer(layer);
+        let size = area.size();
+        terminal::clear(Some(geometry::Rect::from_values(area.bl.x, area.bl.y, size.x, size.y)));
+        terminal::layer(0);
+    }
+
+    fn poll_event(&mut self) -> Option<InputEvent> {
+        if let Some(ev) = self.pending.pop_front() { return Some(ev); }
+        while terminal::has_input() {
+            let ev = terminal::wait_event();
+            // (the char typed, in whatever layout, follows the key)
+            if let Some(terminal::Event::KeyPressed{ctrl:false, alt:false, text:Some(ch), ..}) = ev {
+                self.pending.push_back(InputEvent::Text(ch));
+            }
+            if let Some(ev) = ev.as_ref().and_then(BltRenderer::translate) {
+                if let InputEvent::Resize(size) = ev { self.size = size; }
+                return Some(ev);
+            }
+        }
+        None
+    }
+}
+
+impl Drop for BltRenderer {
+    fn drop(&mut self) {
+        terminal::close();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// in-memory renderer for tests: records each refreshed frame & plays back scripted input
+pub struct HeadlessRenderer {
+    screen: Grid<Glyph>,
+    frames: Vec<Grid<Glyph>>,
+    script: VecDeque<InputEvent>,
+}
+
+impl HeadlessRenderer {
+    pub fn new(size:Point2d) -> Self {
+        let span = Rect2d::new(Point2d::new(0,0), size);
+        HeadlessRenderer { screen:Grid::new(span, Glyph::default()), frames:Vec::new(), script:VecDeque::new() }
+    }
+
+    // what has been drawn so far (refreshed or not)
+    pub fn screen(&self) -> &Grid<Glyph> {
+        &self.screen
+    }
+
+    pub fn frames(&self) -> &[Grid<Glyph>] {
+        &self.frames
+    }
+
+    pub fn last_frame(&self) -> Option<&Grid<Glyph>> {
+        self.frames.last()
+    }
+
+    // chars of one row of the screen, ie for asserting on rendered text
+    pub fn row_text(&self, y:i32) -> String {
+        (0 .. self.screen.span().tr.x)
+            .map(|x| self.screen.get(Point2d::new(x, y)).map(|g| g.ch).unwrap_or(' '))
+            .collect()
+    }
+
+    pub fn push_input(&mut self, ev:InputEvent) {
+        self.script.push_back(ev);
+    }
+
+    pub fn push_keys(&mut self, keys:&[KeyCode]) {
+        self.script.extend(keys.iter().map(|&k| InputEvent::key(k)));
+    }
+
+    pub fn pending_input(&self) -> usize {
+        self.script.len()
+    }
+}
+
+impl Renderer for HeadlessRenderer {
+    fn size(&self) -> Point2d {
+        self.screen.span().size()
+    }
+
+    fn put(&mut self, p:Point2d, g:Glyph) {
+        self.screen.set(p, g);
+    }
+
+    fn clear(&mut self) {
+        self.screen.fill(Glyph::default());
+    }
+
+    fn refresh(&mut self) {
+        self.frames.push(self.screen.clone());
+    }
+
+    fn poll_event(&mut self) -> Option<InputEvent> {
+        let ev = self.script.pop_front();
+        if let Some(InputEvent::Resize(size)) = ev {
+            self.screen.resize(size, Anchor::Min, Anchor::Min, Glyph::default());
+        }
+        ev
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::{ListMenu, MenuEvent};
+
+    #[test]
+    fn menu_driven_by_script() {
+        let mut r = HeadlessRenderer::new(Point2d::new(12,4));
+        let root = WindowHandle::new("Root".into(), Rect2d::new(Point2d::new(0,0), r.size()));
+        let items = ["north", "south", "east"].iter().map(|s| s.to_string()).collect();
+        let mut menu = ListMenu::new(&root, "Menu", Point2d::new(1,1), Point2d::new(10,3), items, false);
+        r.push_keys(&[KeyCode::Down, KeyCode::Down, KeyCode::Enter, KeyCode::Escape]);
+        let mut chosen = None;
+        while let Some(ev) = r.poll_event() {
+            if let InputEvent::Key { key, .. } = ev {
+                if let MenuEvent::Chosen(i) = menu.handle_key(key) {
+                    chosen = Some(i);
+                    break;
+                }
+            }
+            r.draw_window(&root);
+            r.refresh();
+        }
+        assert_eq!(chosen, Some(2));
+        assert_eq!(r.pending_input(), 1);
+        assert_eq!(r.frames().len(), 2);
+        assert_eq!(r.row_text(3).trim_matches('\0'), "east      ");
+        let sel = r.last_frame().unwrap().get(Point2d::new(1,3)).unwrap();
+        assert_eq!(sel.bg, Color::gold());
+        // only changed cells are redrawn once the first frame is out
+        let before = r.screen().clone();
+        r.draw_window(&root);
+        assert!(root.take_dirty().is_empty());
+        assert_eq!(r.screen().cells(), before.cells());
+    }
+
+    #[test]
+    fn flat_put_ext() {
+        let mut r = HeadlessRenderer::new(Point2d::new(4,2));
+        assert!(!r.has_layers());
+        let g = Glyph::new('*', Color::gold(), Color::black());
+        // (offsets in pixels, a cell being one pixel here)
+        r.put_ext(3, Point2d::new(1,0), Point2d::new(1,1), g, None);
+        assert_eq!(r.screen().get(Point2d::new(2,1)), Some(&g));
+        let corners = [Color::rgb(0,0,0), Color::rgb(100,0,0), Color::rgb(100,200,0), Color::rgb(0,200,255)];
+        r.put_ext(1, Point2d::new(0,0), Point2d::new(0,0), g, Some(corners));
+        assert_eq!(r.screen().get(Point2d::new(0,0)).unwrap().fg, Color::rgb(50,100,64));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
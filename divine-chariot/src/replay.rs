@@ -0,0 +1,195 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use std::io;
+use std::path::{Path};
+
+use crate::entity::{EntityId};
+use crate::point2d::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+// a player decision; everything that changes the game goes through one of these,
+// so a session is its starting seed plus the commands given
+#[derive(Clone,Copy,Debug,Eq,PartialEq,serde::Serialize,serde::Deserialize)]
+pub enum GameCommand {
+    Move(Dir),
+    Wait,
+    Pickup(EntityId),
+    Drop(EntityId),
+}
+
+// something commands can be replayed into
+pub trait Simulation {
+    // (turns taken so far)
+    fn turn(&self) -> u64;
+
+    // false if the command was refused (ie walking into a wall), and changed nothing
+    fn apply(&mut self, cmd:GameCommand) -> bool;
+
+    // hash of everything a later turn could depend on (see hash::XXHash)
+    fn state_hash(&self) -> u32;
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq,serde::Serialize,serde::Deserialize)]
+pub struct LoggedCommand {
+    pub turn: u64,          // turn the command was given on
+    pub command: GameCommand,
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum ReplayError {
+    // the i'th command came on another turn than logged
+    OutOfStep { index:usize, logged:u64, actual:u64 },
+    // the i'th command was refused, where it had been accepted
+    Refused { index:usize },
+    // all went through, but ended somewhere else
+    HashMismatch { logged:u32, actual:u32 },
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ReplayError::OutOfStep { index, logged, actual } =>
+                write!(f, "command {} logged on turn {}, replayed on turn {}", index, logged, actual),
+            ReplayError::Refused { index } => write!(f, "command {} refused on replay", index),
+            ReplayError::HashMismatch { logged, actual } =>
+                write!(f, "final state {:08x}, logged {:08x}", actual, logged),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+// the accepted commands of a session, with what's needed to replay them:
+// the game's starting Rng::seed() & (once finished) its final state hash
+#[derive(Clone,Debug,Default,Eq,PartialEq,serde::Serialize,serde::Deserialize)]
+pub struct CommandLog {
+    pub seed: [u32; 4],
+    pub commands: Vec<LoggedCommand>,
+    pub final_hash: Option<u32>,
+}
+
+impl CommandLog {
+    pub fn new(seed:[u32; 4]) -> Self {
+        CommandLog { seed, commands:Vec::new(), final_hash:None }
+    }
+
+    pub fn push(&mut self, turn:u64, command:GameCommand) {
+        self.commands.push(LoggedCommand { turn, command });
+        self.final_hash = None;
+    }
+
+    pub fn finish<S:Simulation>(&mut self, sim:&S) {
+        self.final_hash = Some(sim.state_hash());
+    }
+
+    pub fn save(&self, path:&Path) -> io::Result<()> {
+        let f = io::BufWriter::new(std::fs::File::create(path)?);
+        serde_cbor::to_writer(f, self).map_err(io::Error::other)
+    }
+
+    pub fn load(path:&Path) -> io::Result<CommandLog> {
+        let f = io::BufReader::new(std::fs::File::open(path)?);
+        serde_cbor::from_reader(f).map_err(io::Error::other)
+    }
+
+    // feeds the i'th command to sim, checking it stays in step
+    pub fn replay_one<S:Simulation>(&self, i:usize, sim:&mut S) -> Result<(), ReplayError> {
+        let c = &self.commands[i];
+        if sim.turn() != c.turn {
+            return Err(ReplayError::OutOfStep { index:i, logged:c.turn, actual:sim.turn() });
+        }
+        if !sim.apply(c.command) {
+            return Err(ReplayError::Refused { index:i });
+        }
+        Ok(())
+    }
+
+    // replays everything into sim (freshly made from self.seed), then checks it
+    // ended as logged; returns the final state hash
+    pub fn verify<S:Simulation>(&self, sim:&mut S) -> Result<u32, ReplayError> {
+        for i in 0 .. self.commands.len() {
+            self.replay_one(i, sim)?;
+        }
+        let actual = sim.state_hash();
+        match self.final_hash {
+            Some(logged) if logged != actual => Err(ReplayError::HashMismatch { logged, actual }),
+            _ => Ok(actual),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a walker on a line, walled in at x = 2
+    #[derive(Default)]
+    struct Walk { x:i32, turn:u64 }
+
+    impl Simulation for Walk {
+        fn turn(&self) -> u64 {
+            self.turn
+        }
+
+        fn apply(&mut self, cmd:GameCommand) -> bool {
+            match cmd {
+                GameCommand::Move(Dir::E) if self.x < 2 => { self.x += 1; }
+                GameCommand::Move(Dir::W) => { self.x -= 1; }
+                GameCommand::Wait => {}
+                _ => { return false; }
+            }
+            self.turn += 1;
+            true
+        }
+
+        fn state_hash(&self) -> u32 {
+            (self.x as u32).wrapping_mul(31) ^ self.turn as u32
+        }
+    }
+
+    fn log(cmds:&[(u64,GameCommand)]) -> CommandLog {
+        let mut res = CommandLog::new([1,2,3,4]);
+        for (turn, cmd) in cmds { res.push(*turn, *cmd); }
+        res
+    }
+
+    #[test]
+    fn verify_errors() {
+        use GameCommand::*;
+        let mut ok = log(&[(0, Move(Dir::E)), (1, Wait), (2, Move(Dir::E))]);
+        let mut sim = Walk::default();
+        ok.verify(&mut sim).unwrap();
+        ok.finish(&sim);
+        assert_eq!(ok.verify(&mut Walk::default()), Ok(sim.state_hash()));
+        // a command on another turn than logged
+        let bad = log(&[(0, Wait), (2, Wait)]);
+        assert_eq!(bad.verify(&mut Walk::default()), Err(ReplayError::OutOfStep { index:1, logged:2, actual:1 }));
+        // one the simulation refuses (into the wall)
+        let bad = log(&[(0, Move(Dir::E)), (1, Move(Dir::E)), (2, Move(Dir::E))]);
+        assert_eq!(bad.verify(&mut Walk::default()), Err(ReplayError::Refused { index:2 }));
+        // all accepted, but not ending as logged
+        let mut bad = ok.clone();
+        bad.commands[1].command = Move(Dir::W);
+        bad.final_hash = ok.final_hash;
+        let mut sim = Walk::default();
+        assert_eq!(bad.verify(&mut sim), Err(ReplayError::HashMismatch { logged:ok.final_hash.unwrap(), actual:sim.state_hash() }));
+        assert_ne!(ok.final_hash, Some(sim.state_hash()));
+    }
+
+    #[test]
+    fn save_load_roundtrip() {
+        let mut l = log(&[(0, GameCommand::Move(Dir::SW)), (1, GameCommand::Pickup(EntityId::null())), (5, GameCommand::Wait)]);
+        l.final_hash = Some(0xdeadbeef);
+        let path = std::env::temp_dir().join(format!("divine-chariot-replay-{}.cbor", std::process::id()));
+        l.save(&path).unwrap();
+        let loaded = CommandLog::load(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.unwrap(), l);
+        assert!(CommandLog::load(&path).is_err());
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
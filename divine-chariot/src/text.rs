@@ -0,0 +1,244 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::core_systems::*;
+use crate::point2d::*;
+use crate::widgets::{Align};
+use crate::window::{WindowHandle};
+
+////////////////////////////////////////////////////////////////////////////////
+
+// chars taking no cell of their own (combining marks, zero-width spaces & joiners)
+pub fn is_zero_width(ch:char) -> bool {
+    matches!(ch,
+        '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}' |
+        '\u{20D0}'..='\u{20FF}' | '\u{FE20}'..='\u{FE2F}' | '\u{200B}'..='\u{200D}' | '\u{FEFF}')
+}
+
+// cells needed for s (one per char, less the zero-width ones)
+pub fn text_width(s:&str) -> usize {
+    s.chars().filter(|ch| !is_zero_width(*ch)).count()
+}
+
+// precomposed form of base + combining mark, for the marks of Latin transliterations
+// (ie Akkadian: ā â š ṣ ṭ ḫ ḥ); with no such form the mark is lost, and base
+// returned as is, since a cell only holds the one char
+pub fn compose(base:char, mark:char) -> char {
+    let (from, to) = match mark {
+        '\u{0300}' => ("aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+        '\u{0301}' => ("aeiouAEIOU", "áéíóúÁÉÍÓÚ"),
+        '\u{0302}' => ("aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+        '\u{0303}' => ("anoANO", "ãñõÃÑÕ"),
+        '\u{0304}' => ("aeiouAEIOU", "āēīōūĀĒĪŌŪ"),
+        '\u{0308}' => ("aeiouAEIOU", "äëïöüÄËÏÖÜ"),
+        '\u{030C}' => ("csgzCSGZ", "čšǧžČŠǦŽ"),
+        '\u{0323}' => ("hstHST", "ḥṣṭḤṢṬ"),
+        '\u{032E}' => ("hH", "ḫḪ"),
+        _ => { return base; }
+    };
+    from.chars().position(|c| c == base).and_then(|i| to.chars().nth(i)).unwrap_or(base)
+}
+
+// the chars of s, one per cell, with combining marks composed into the char before
+pub fn cell_chars(s:&str) -> Vec<char> {
+    let mut res : Vec<char> = Vec::new();
+    for ch in s.chars() {
+        if !is_zero_width(ch) {
+            res.push(ch);
+        } else if let Some(prev) = res.last_mut() {
+            *prev = compose(*prev, ch);
+        }
+    }
+    res
+}
+
+// splits cells into lines of at most w, breaking at (the cells whose char is) spaces
+// where possible; '\n' cells end a line, and are dropped
+pub fn wrap_cells<T:Copy>(cells:&[T], w:usize, ch:impl Fn(&T)->char) -> Vec<Vec<T>> {
+    let mut lines = Vec::new();
+    if w == 0 { return lines; }
+    for para in cells.split(|c| ch(c) == '\n') {
+        let mut line : Vec<T> = Vec::new();
+        let mut i = 0;
+        while i <= para.len() {
+            let end = para[i..].iter().position(|c| ch(c) == ' ').map_or(para.len(), |n| i + n);
+            let word = &para[i..end];
+            if !line.is_empty() && line.len() + 1 + word.len() > w {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() { line.push(para[i-1]); }
+            // hard-break words too long for a line on their own
+            for c in word {
+                if line.len() == w {
+                    lines.push(std::mem::take(&mut line));
+                }
+                line.push(*c);
+            }
+            i = end + 1;
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// text with per-char colours, from markup:
+//  - [fg=NAME] ... [/fg] and [bg=NAME] ... [/bg], nesting, with NAME a palette
+//    colour (see Color::named) or #RRGGBB,
+//  - [[ for a literal '[', and anything else in brackets is kept as written,
+//  - combining marks folded into the char before (see compose), so each glyph
+//    is one cell
+#[derive(Clone,Debug,Default,Eq,PartialEq)]
+pub struct RichText {
+    glyphs: Vec<Glyph>,                 // '\n' glyphs for line breaks
+}
+
+impl RichText {
+    pub fn parse(markup:&str, fg:Color, bg:Color) -> Self {
+        let mut fgs = vec![fg];
+        let mut bgs = vec![bg];
+        let mut glyphs : Vec<Glyph> = Vec::new();
+        let mut rest = markup;
+        while let Some(ch) = rest.chars().next() {
+            if ch == '[' {
+                if let Some(r) = rest.strip_prefix("[[") {
+                    glyphs.push(Glyph::new('[', *fgs.last().unwrap(), *bgs.last().unwrap()));
+                    rest = r;
+                    continue;
+                }
+                if let Some(end) = rest.find(']') {
+                    let handled = match &rest[1..end] {
+                        "/fg" => { if fgs.len() > 1 { fgs.pop(); } true }
+                        "/bg" => { if bgs.len() > 1 { bgs.pop(); } true }
+                        tag => match tag.split_once('=') {
+                            Some(("fg", c)) => Color::named(c).map(|c| fgs.push(c)).is_some(),
+                            Some(("bg", c)) => Color::named(c).map(|c| bgs.push(c)).is_some(),
+                            _ => false,
+                        }
+                    };
+                    if handled {
+                        rest = &rest[end+1..];
+                        continue;
+                    }
+                }
+            }
+            if is_zero_width(ch) {
+                if let Some(prev) = glyphs.last_mut() {
+                    prev.ch = compose(prev.ch, ch);
+                }
+            } else {
+                glyphs.push(Glyph::new(ch, *fgs.last().unwrap(), *bgs.last().unwrap()));
+            }
+            rest = &rest[ch.len_utf8()..];
+        }
+        RichText { glyphs }
+    }
+
+    pub fn plain(s:&str, fg:Color, bg:Color) -> Self {
+        RichText::parse(&s.replace('[', "[["), fg, bg)
+    }
+
+    pub fn glyphs(&self) -> &[Glyph] {
+        &self.glyphs
+    }
+
+    // widest line, unwrapped
+    pub fn width(&self) -> i32 {
+        self.glyphs.split(|g| g.ch == '\n').map(|l| l.len()).max().unwrap_or(0) as i32
+    }
+
+    // lines of at most w cells, breaking at spaces where possible;
+    // beyond max_lines (if any) the text is cut short, the last line ending in '…'
+    pub fn lines(&self, w:i32, max_lines:Option<i32>) -> Vec<Vec<Glyph>> {
+        let mut lines = wrap_cells(&self.glyphs, w.max(0) as usize, |g| g.ch);
+        if let Some(n) = max_lines {
+            let n = n.max(0) as usize;
+            if lines.len() > n {
+                lines.truncate(n);
+                if let Some(last) = lines.last_mut() {
+                    last.truncate((w - 1).max(0) as usize);
+                    let style = last.last().copied().unwrap_or_else(|| self.glyphs[0]);
+                    last.push(Glyph::new('…', style.fg, style.bg));
+                }
+            }
+        }
+        lines
+    }
+
+    // draws the lines (as above) from p within w columns, each aligned;
+    // returns the number of lines drawn, so following content can go beneath
+    pub fn draw(&self, window:&WindowHandle<Glyph>, p:Point2d, w:i32, align:Align, max_lines:Option<i32>) -> i32 {
+        let lines = self.lines(w, max_lines);
+        for (y, line) in lines.iter().enumerate() {
+            let len = line.len() as i32;
+            let lead = match align {
+                Align::Left => 0,
+                Align::Center => (w - len)/2,
+                Align::Right => w - len,
+            };
+            for (x, g) in line.iter().enumerate() {
+                window.set(p + Point2d::new(lead + x as i32, y as i32), *g);
+            }
+        }
+        lines.len() as i32
+    }
+}
+
+// parses & draws markup (see RichText), wrapped to w; returns the number of lines drawn
+pub fn put_markup(window:&WindowHandle<Glyph>, markup:&str, p:Point2d, w:i32, align:Align, fg:Color, bg:Color) -> i32 {
+    RichText::parse(markup, fg, bg).draw(window, p, w, align, None)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rect2d::*;
+
+    fn chars(line:&[Glyph]) -> String {
+        line.iter().map(|g| g.ch).collect()
+    }
+
+    #[test]
+    fn markup_and_diacritics() {
+        let t = RichText::parse("a [fg=gold]ḫur[bg=#102030]ā[/bg]ṣum[/fg] [[x] [fg=nope]", Color::white(), Color::black());
+        assert_eq!(chars(t.glyphs()), "a ḫurāṣum [x] [fg=nope]");
+        let g = t.glyphs();
+        assert_eq!((g[0].fg, g[2].fg, g[8].fg, g[9].fg), (Color::white(), Color::gold(), Color::gold(), Color::white()));
+        assert_eq!((g[4].bg, g[5].bg, g[6].bg), (Color::black(), Color::rgb(0x10,0x20,0x30), Color::black()));
+        // decomposed input takes the same cells as precomposed
+        let decomposed = "h\u{032E}aia\u{0304}t\u{0323}um";
+        assert_eq!(text_width(decomposed), 7);
+        assert_eq!(chars(RichText::parse(decomposed, Color::white(), Color::black()).glyphs()), "ḫaiāṭum");
+        assert_eq!(RichText::parse("x\u{0310}", Color::white(), Color::black()).width(), 1);
+        assert_eq!(chars(RichText::plain("[fg=red]", Color::white(), Color::black()).glyphs()), "[fg=red]");
+        assert_eq!(Color::named("misty_rose"), Some(Color::misty_rose()));
+        assert_eq!(Color::named("#12345"), None);
+    }
+
+    #[test]
+    fn wrap_align_and_ellipsis() {
+        let t = RichText::parse("kaspum: [fg=iron]silver[/fg], price\nṣarpum", Color::white(), Color::black());
+        let lines : Vec<String> = t.lines(10, None).iter().map(|l| chars(l)).collect();
+        assert_eq!(lines, vec!["kaspum:", "silver,", "price", "ṣarpum"]);
+        let lines : Vec<String> = t.lines(10, Some(2)).iter().map(|l| chars(l)).collect();
+        assert_eq!(lines, vec!["kaspum:", "silver,…"]);
+        let lines : Vec<String> = RichText::plain("abcdefghijkl", Color::white(), Color::black())
+            .lines(5, Some(1)).iter().map(|l| chars(l)).collect();
+        assert_eq!(lines, vec!["abcd…"]);
+        let root = WindowHandle::new("Root".into(), Rect2d::new(Point2d::new(0,0), Point2d::new(12,6)));
+        root.fill(Glyph::new('.', Color::white(), Color::black()));
+        let n = t.draw(&root, Point2d::new(1,1), 10, Align::Right, Some(3));
+        assert_eq!(n, 3);
+        let row = |y| (0..12).map(|x| root.get(Point2d::new(x,y)).unwrap().ch).collect::<String>();
+        assert_eq!(row(1), "....kaspum:.");
+        assert_eq!(row(2), "....silver,.");
+        assert_eq!(row(3), ".....price….");
+        assert_eq!(root.get(Point2d::new(5,2)).unwrap().fg, Color::iron());
+        assert_eq!(put_markup(&root, "a b", Point2d::new(0,5), 12, Align::Center, Color::white(), Color::black()), 1);
+        assert_eq!(row(5), "....a b.....");
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
@@ -0,0 +1,206 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use std::path::{Path, PathBuf};
+
+use bear_lib_terminal::geometry::{Size};
+use bear_lib_terminal::terminal;
+use bear_lib_terminal::terminal::config::{ConfigError, ConfigPart};
+use bear_lib_terminal::terminal::config::font::{self, Bitmap, Origin, TrueType};
+
+use crate::point2d::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+// the private use area, which tilesets are loaded into
+pub const PUA_START : u32 = 0xE000;
+pub const PUA_END : u32 = 0xF900;     // (exclusive)
+
+#[derive(Debug)]
+pub enum TilesetError {
+    Config(ConfigError),                // refused by BearLibTerminal (its log says why)
+    Missing(PathBuf),                   // no such font or image
+    Unknown(String),                    // no tileset of that name
+    Duplicate(String),                  // already a tileset of that name
+    Full { name:String, count:u32 },    // not that much of the private use area left
+}
+
+impl std::fmt::Display for TilesetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TilesetError::Config(e) => write!(f, "{}", e),
+            TilesetError::Missing(path) => write!(f, "cannot find {}", path.display()),
+            TilesetError::Unknown(name) => write!(f, "no tileset '{}'", name),
+            TilesetError::Duplicate(name) => write!(f, "tileset '{}' already added", name),
+            TilesetError::Full { name, count } =>
+                write!(f, "no room for the {} tiles of '{}' in the private use area", count, name),
+        }
+    }
+}
+
+impl std::error::Error for TilesetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TilesetError::Config(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ConfigError> for TilesetError {
+    fn from(e:ConfigError) -> Self {
+        TilesetError::Config(e)
+    }
+}
+
+////////////////////////////////////////
+
+// a bitmap tileset: `count` tiles of tile_size pixels, shown as the chars from `base` on
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub struct Tileset {
+    pub name: String,
+    pub path: PathBuf,
+    pub tile_size: Point2d,
+    pub count: u32,
+    pub base: char,
+}
+
+impl Tileset {
+    // the char for tile i
+    pub fn tile(&self, i:u32) -> Option<char> {
+        if i >= self.count { return None; }
+        char::from_u32(self.base as u32 + i)
+    }
+
+    pub fn contains(&self, ch:char) -> bool {
+        (self.base as u32 .. self.base as u32 + self.count).contains(&(ch as u32))
+    }
+
+    fn config(&self) -> Bitmap {
+        font::bitmap(Origin::Offset(self.base), &self.path)
+            .size(Size::new(self.tile_size.x, self.tile_size.y))
+    }
+}
+
+////////////////////////////////////////
+
+// the TrueType font text is drawn in, plus bitmap tilesets, each given its own
+// stretch of the private use area; either can be swapped while running, and as
+// the terminal keeps chars rather than pixels, what's on screen changes with them
+#[derive(Clone,Debug)]
+pub struct TilesetManager {
+    font: PathBuf,
+    font_size: i32,
+    tilesets: Vec<Tileset>,
+    next: u32,
+}
+
+impl TilesetManager {
+    pub fn new(font:&Path, font_size:i32) -> Self {
+        TilesetManager { font:font.to_path_buf(), font_size, tilesets:Vec::new(), next:PUA_START }
+    }
+
+    pub fn font(&self) -> &Path {
+        &self.font
+    }
+
+    pub fn font_size(&self) -> i32 {
+        self.font_size
+    }
+
+    pub fn tilesets(&self) -> &[Tileset] {
+        &self.tilesets
+    }
+
+    pub fn tileset(&self, name:&str) -> Option<&Tileset> {
+        self.tilesets.iter().find(|t| t.name == name)
+    }
+
+    // reserves chars for the tileset's tiles; loaded by apply()
+    pub fn add(&mut self, name:&str, path:&Path, tile_size:Point2d, count:u32) -> Result<&Tileset, TilesetError> {
+        if self.tileset(name).is_some() { return Err(TilesetError::Duplicate(name.into())); }
+        let base = match char::from_u32(self.next) {
+            Some(base) if count <= PUA_END - self.next => base,
+            _ => { return Err(TilesetError::Full { name:name.into(), count }); }
+        };
+        self.next += count;
+        self.tilesets.push(Tileset { name:name.into(), path:path.to_path_buf(), tile_size, count, base });
+        Ok(self.tilesets.last().unwrap())
+    }
+
+    // loads the font & all the tilesets, carrying on past any that fail
+    pub fn apply(&self) -> Result<(), Vec<TilesetError>> {
+        let mut errors = Vec::new();
+        errors.extend(load(&self.font, self.font_config()).err());
+        for t in &self.tilesets {
+            errors.extend(load(&t.path, t.config()).err());
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    // switches the text font (or just its size); on failure the old one stays
+    pub fn set_font(&mut self, path:&Path, size:i32) -> Result<(), TilesetError> {
+        check_exists(path)?;
+        let old = std::mem::replace(&mut self.font, path.to_path_buf());
+        let old_size = std::mem::replace(&mut self.font_size, size);
+        if let Err(e) = terminal::try_set(self.font_config()) {
+            self.font = old;
+            self.font_size = old_size;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    // swaps another image into a tileset's chars (ie alternate art, laid out alike)
+    pub fn switch(&mut self, name:&str, path:&Path, tile_size:Point2d) -> Result<(), TilesetError> {
+        check_exists(path)?;
+        let t = match self.tilesets.iter_mut().find(|t| t.name == name) {
+            Some(t) => t,
+            None => { return Err(TilesetError::Unknown(name.into())); }
+        };
+        let mut swapped = t.clone();
+        swapped.path = path.to_path_buf();
+        swapped.tile_size = tile_size;
+        terminal::try_set(swapped.config())?;
+        *t = swapped;
+        Ok(())
+    }
+
+    fn font_config(&self) -> TrueType {
+        font::true_type(Origin::Root, &self.font, Size::new(0, self.font_size))
+    }
+}
+
+fn check_exists(path:&Path) -> Result<(), TilesetError> {
+    if path.exists() { Ok(()) } else { Err(TilesetError::Missing(path.to_path_buf())) }
+}
+
+fn load<T:ConfigPart>(path:&Path, config:T) -> Result<(), TilesetError> {
+    check_exists(path)?;
+    Ok(terminal::try_set(config)?)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_use_ranges() {
+        let mut tm = TilesetManager::new(Path::new("./DejaVuSansMono.ttf"), 14);
+        assert_eq!(tm.font_config().to_config_str(), "font: './DejaVuSansMono.ttf', size=14;");
+        assert_eq!(tm.add("terrain", Path::new("terrain.png"), Point2d::new(16,16), 256).unwrap().base, '\u{E000}');
+        assert_eq!(tm.add("items", Path::new("items.png"), Point2d::new(12,12), 64).unwrap().base, '\u{E100}');
+        let items = tm.tileset("items").unwrap();
+        assert_eq!(items.tile(3), Some('\u{E103}'));
+        assert_eq!(items.tile(64), None);
+        assert!(items.contains('\u{E13F}') && !items.contains('\u{E0FF}'));
+        assert_eq!(items.config().to_config_str(), "0xE100: 'items.png', size=12x12;");
+        assert!(matches!(tm.add("items", Path::new("x.png"), Point2d::new(8,8), 1), Err(TilesetError::Duplicate(_))));
+        assert!(matches!(tm.add("huge", Path::new("x.png"), Point2d::new(8,8), 0x1800), Err(TilesetError::Full { .. })));
+        // (a refusal leaves the range free)
+        assert_eq!(tm.add("rest", Path::new("x.png"), Point2d::new(8,8), 0x17C0).unwrap().base, '\u{E140}');
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
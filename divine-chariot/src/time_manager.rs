@@ -9,6 +9,7 @@ use std::time::{Duration,Instant};
 pub struct TimeManager {
     target_fps : usize,
     target_spf : Duration,
+    start_t    : Instant,
     last_t     : Instant,
     total_t    : Duration,
     frame_count: usize,
@@ -21,27 +22,29 @@ impl TimeManager {
         let spf_sec = spf.floor() as u64;
         let spf_subnanos = ((spf - spf_sec as f64) * 1e9).round() as u32;
         let target_spf = Duration::new(spf_sec, spf_subnanos);
-        let last_t = Instant::now();
+        let start_t = Instant::now();
+        let last_t = start_t;
         let total_t = Duration::default();
         let frame_count = 0;
         let pause_count = 1;
         TimeManager {
-            target_fps, target_spf, last_t, total_t,
+            target_fps, target_spf, start_t, last_t, total_t,
             frame_count, pause_count,
         }
     }
 
+    // paused frames (ie under a dialog) are still paced, but not counted
     pub fn tick(&mut self) {
-        if self.pause_count > 0 { return; }
         let t = Instant::now();
         let tdiff = t - self.last_t;
         if tdiff < self.target_spf {
             sleep(self.target_spf - tdiff);
         }
-        self.frame_count += 1;
         let t = Instant::now();
         let tdiff = t - self.last_t;
         self.last_t = t;
+        if self.pause_count > 0 { return; }
+        self.frame_count += 1;
         self.total_t += tdiff;
     }
 
@@ -56,6 +59,10 @@ impl TimeManager {
         }
     }
 
+    pub fn paused(&self) -> bool {
+        self.pause_count > 0
+    }
+
     pub fn target_fps(&self) -> usize {
         self.target_fps
     }
@@ -69,6 +76,16 @@ impl TimeManager {
     pub fn frame_count(&self) -> usize {
         self.frame_count
     }
+
+    // total unpaused time over all ticks so far
+    pub fn elapsed(&self) -> Duration {
+        self.total_t
+    }
+
+    // wall-clock time since created, paused or not (ie for timestamping recorded frames)
+    pub fn since_start(&self) -> Duration {
+        self.start_t.elapsed()
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -0,0 +1,978 @@
+////////////////////////////////////////////////////////////////////////////////
+
+use std::collections::{VecDeque};
+
+use bear_lib_terminal::terminal::{KeyCode};
+
+use crate::core_systems::*;
+use crate::handle::{Handle};
+use crate::keymap::{Command};
+use crate::point2d::*;
+use crate::rect2d::*;
+use crate::renderer::{InputEvent};
+use crate::text::{cell_chars, compose, is_zero_width, text_width, wrap_cells};
+use crate::window::{WindowHandle};
+
+////////////////////////////////////////////////////////////////////////////////
+
+// Widgets each own a child window of the given parent, rerender themselves
+// whenever their state changes, and demolish their window when dropped.
+// All take a local origin of (0,0) at the top-left of their window.
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub struct Style {
+    pub fg: Color,
+    pub bg: Color,
+    pub hi_fg: Color,       // highlighted (ie selected) entries
+    pub hi_bg: Color,
+    pub dim_fg: Color,      // borders, headers, scroll markers, etc.
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            fg: Color::pearl(),
+            bg: Color::black(),
+            hi_fg: Color::black(),
+            hi_bg: Color::gold(),
+            dim_fg: Color::granite_gray(),
+        }
+    }
+}
+
+#[derive(Clone,Copy,Debug,Default,Eq,PartialEq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+// writes s from p onward, clipped to max_w cells; returns number of cells written
+// (combining marks are composed into the char before, see text::cell_chars)
+pub fn put_str(window:&WindowHandle<Glyph>, s:&str, p:Point2d, max_w:i32, fg:Color, bg:Color) -> i32 {
+    let mut n = 0;
+    for ch in cell_chars(s) {
+        if n >= max_w { break; }
+        window.set(p + Point2d::new(n, 0), Glyph::new(ch, fg, bg));
+        n += 1;
+    }
+    n
+}
+
+// as put_str, but aligned within (and padded out to) a field of width w
+pub fn put_str_aligned(window:&WindowHandle<Glyph>, s:&str, p:Point2d, w:i32, align:Align, fg:Color, bg:Color) {
+    let len = (text_width(s) as i32).min(w);
+    let lead = match align {
+        Align::Left => 0,
+        Align::Center => (w - len)/2,
+        Align::Right => w - len,
+    };
+    window.fill_rect(Rect2d::new(p, p + Point2d::new(w, 1)), Glyph::new(' ', fg, bg));
+    put_str(window, s, p + Point2d::new(lead, 0), len, fg, bg);
+}
+
+// splits text into lines of at most w cells, breaking at spaces where possible
+pub fn wrap_chars(text:&str, w:usize) -> Vec<String> {
+    wrap_cells(&cell_chars(text), w, |ch| *ch).into_iter()
+        .map(|l| l.into_iter().collect())
+        .collect()
+}
+
+// maps letter keys to 0..26 (for menu hotkeys)
+pub fn key_letter(k:KeyCode) -> Option<usize> {
+    use KeyCode::*;
+    let letters = [A,B,C,D,E,F,G,H,I,J,K,L,M,N,O,P,Q,R,S,T,U,V,W,X,Y,Z];
+    letters.iter().position(|&l| l == k)
+}
+
+fn local_span(size:Point2d) -> Rect2d {
+    Rect2d::new(Point2d::new(0,0), size)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub const SINGLE_BORDER : [char; 6] = ['┌', '─', '┐', '│', '└', '┘'];
+pub const DOUBLE_BORDER : [char; 6] = ['╔', '═', '╗', '║', '╚', '╝'];
+
+// bordered box with a title set into the top edge;
+// other widgets can be placed inside it, as children of window() within inner()
+pub struct Frame {
+    window: WindowHandle<Glyph>,
+    title: String,
+    border: [char; 6],
+    style: Style,
+}
+
+impl Frame {
+    pub fn new(parent:&WindowHandle<Glyph>, id:&str, offset:Point2d, size:Point2d, title:&str) -> Self {
+        let window = parent.new_child(id.to_string(), offset, local_span(size));
+        let mut res = Frame { window, title:title.to_string(), border:SINGLE_BORDER, style:Style::default() };
+        res.render();
+        res
+    }
+
+    pub fn window(&self) -> &WindowHandle<Glyph> {
+        &self.window
+    }
+
+    // area within the border
+    pub fn inner(&self) -> Rect2d {
+        self.window.span().inset(1)
+    }
+
+    pub fn set_title(&mut self, title:&str) {
+        self.title = title.to_string();
+        self.render();
+    }
+
+    pub fn set_border(&mut self, border:[char; 6]) {
+        self.border = border;
+        self.render();
+    }
+
+    pub fn set_style(&mut self, style:Style) {
+        self.style = style;
+        self.render();
+    }
+
+    fn render(&mut self) {
+        let span = self.window.span();
+        let (l, r) = (span.bl.x, span.tr.x-1);
+        let (t, b) = (span.bl.y, span.tr.y-1);
+        let [tl, h, tr, v, bl, br] = self.border;
+        let edge = |ch| Glyph::new(ch, self.style.dim_fg, self.style.bg);
+        self.window.fill(Glyph::new(' ', self.style.fg, self.style.bg));
+        for x in l ..= r {
+            self.window.set(Point2d::new(x, t), edge(h));
+            self.window.set(Point2d::new(x, b), edge(h));
+        }
+        for y in t ..= b {
+            self.window.set(Point2d::new(l, y), edge(v));
+            self.window.set(Point2d::new(r, y), edge(v));
+        }
+        self.window.set(Point2d::new(l, t), edge(tl));
+        self.window.set(Point2d::new(r, t), edge(tr));
+        self.window.set(Point2d::new(l, b), edge(bl));
+        self.window.set(Point2d::new(r, b), edge(br));
+        if !self.title.is_empty() && r - l > 4 {
+            let p = Point2d::new(l+2, t);
+            put_str(&self.window, &format!(" {} ", self.title), p, r - l - 3, self.style.fg, self.style.bg);
+        }
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        self.window.demolish();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// scrolling log of messages, newest at the bottom, keeping up to capacity messages
+pub struct MessageLog {
+    window: WindowHandle<Glyph>,
+    history: VecDeque<(String,Color)>,
+    capacity: usize,
+    scroll: usize,          // lines scrolled back from the newest
+    style: Style,
+}
+
+impl MessageLog {
+    pub fn new(parent:&WindowHandle<Glyph>, id:&str, offset:Point2d, size:Point2d, capacity:usize) -> Self {
+        let window = parent.new_child(id.to_string(), offset, local_span(size));
+        let mut res = MessageLog { window, history:VecDeque::new(), capacity, scroll:0, style:Style::default() };
+        res.render();
+        res
+    }
+
+    pub fn window(&self) -> &WindowHandle<Glyph> {
+        &self.window
+    }
+
+    // new messages snap the view back to the newest
+    pub fn push(&mut self, text:&str, color:Color) {
+        self.history.push_back((text.to_string(), color));
+        while self.history.len() > self.capacity {
+            self.history.pop_front();
+        }
+        self.scroll = 0;
+        self.render();
+    }
+
+    pub fn history(&self) -> impl Iterator<Item=&(String,Color)> {
+        self.history.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.scroll = 0;
+        self.render();
+    }
+
+    // positive scrolls back toward older messages
+    pub fn scroll_by(&mut self, delta:i32) {
+        let max = self.lines().len().saturating_sub(self.window.size().y.max(0) as usize);
+        self.scroll = (self.scroll as i64 + delta as i64).max(0).min(max as i64) as usize;
+        self.render();
+    }
+
+    pub fn scroll_to_end(&mut self) {
+        self.scroll = 0;
+        self.render();
+    }
+
+    // rerenders at the window's current size (ie after a Layout has resized it)
+    pub fn redraw(&mut self) {
+        self.scroll_by(0);
+    }
+
+    pub fn handle_key(&mut self, key:KeyCode) -> bool {
+        let page = self.window.size().y.max(1);
+        match key {
+            KeyCode::PageUp => { self.scroll_by(page); }
+            KeyCode::PageDown => { self.scroll_by(-page); }
+            KeyCode::End => { self.scroll_to_end(); }
+            _ => { return false; }
+        }
+        true
+    }
+
+    fn lines(&self) -> Vec<(String,Color)> {
+        let w = self.window.size().x.max(1) as usize;
+        self.history.iter()
+            .flat_map(|(text, color)| wrap_chars(text, w).into_iter().map(move|l| (l, *color)))
+            .collect()
+    }
+
+    fn render(&mut self) {
+        let size = self.window.size();
+        self.window.fill(Glyph::new(' ', self.style.fg, self.style.bg));
+        let lines = self.lines();
+        let end = lines.len() - self.scroll.min(lines.len());
+        let start = end.saturating_sub(size.y.max(0) as usize);
+        let rows = (end - start) as i32;
+        for (i, (line, color)) in lines[start..end].iter().enumerate() {
+            let y = size.y - rows + i as i32;
+            put_str(&self.window, line, Point2d::new(0, y), size.x, *color, self.style.bg);
+        }
+        if self.scroll > 0 {
+            self.window.set(Point2d::new(size.x-1, size.y-1), Glyph::new('↓', self.style.dim_fg, self.style.bg));
+        }
+        if start > 0 {
+            self.window.set(Point2d::new(size.x-1, 0), Glyph::new('↑', self.style.dim_fg, self.style.bg));
+        }
+    }
+}
+
+impl Drop for MessageLog {
+    fn drop(&mut self) {
+        self.window.demolish();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum MenuEvent {
+    Ignored,            // key not handled
+    Moved(usize),       // selection moved to given item
+    Chosen(usize),      // item picked (enter or hotkey)
+    Cancelled,          // escape
+}
+
+// vertical list with a highlighted selection, scrolling to keep it visible;
+// optionally with a) b) c) ... letter hotkeys
+pub struct ListMenu {
+    window: WindowHandle<Glyph>,
+    items: Vec<String>,
+    selected: usize,
+    top: usize,
+    hotkeys: bool,
+    style: Style,
+}
+
+impl ListMenu {
+    pub fn new(parent:&WindowHandle<Glyph>, id:&str, offset:Point2d, size:Point2d, items:Vec<String>, hotkeys:bool) -> Self {
+        let window = parent.new_child(id.to_string(), offset, local_span(size));
+        let mut res = ListMenu { window, items, selected:0, top:0, hotkeys, style:Style::default() };
+        res.render();
+        res
+    }
+
+    pub fn window(&self) -> &WindowHandle<Glyph> {
+        &self.window
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    pub fn set_items(&mut self, items:Vec<String>) {
+        self.items = items;
+        self.selected = self.selected.min(self.items.len().saturating_sub(1));
+        self.render();
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        if self.items.is_empty() { None } else { Some(self.selected) }
+    }
+
+    pub fn set_selected(&mut self, i:usize) {
+        self.selected = i.min(self.items.len().saturating_sub(1));
+        self.render();
+    }
+
+    pub fn set_style(&mut self, style:Style) {
+        self.style = style;
+        self.render();
+    }
+
+    pub fn handle_key(&mut self, key:KeyCode) -> MenuEvent {
+        if self.items.is_empty() {
+            return if key == KeyCode::Escape { MenuEvent::Cancelled } else { MenuEvent::Ignored };
+        }
+        let n = self.items.len() as i64;
+        let page = self.window.size().y.max(1) as i64;
+        let cur = self.selected as i64;
+        let to = match key {
+            KeyCode::Up | KeyCode::Num8 => (cur - 1).rem_euclid(n),
+            KeyCode::Down | KeyCode::Num2 => (cur + 1).rem_euclid(n),
+            KeyCode::PageUp | KeyCode::Num9 => (cur - page).max(0),
+            KeyCode::PageDown | KeyCode::Num3 => (cur + page).min(n-1),
+            KeyCode::Home | KeyCode::Num7 => 0,
+            KeyCode::End | KeyCode::Num1 => n-1,
+            KeyCode::Enter | KeyCode::NumEnter => { return MenuEvent::Chosen(self.selected); }
+            KeyCode::Escape => { return MenuEvent::Cancelled; }
+            k => {
+                match key_letter(k) {
+                    Some(i) if self.hotkeys && (i as i64) < n => {
+                        self.set_selected(i);
+                        return MenuEvent::Chosen(i);
+                    }
+                    _ => { return MenuEvent::Ignored; }
+                }
+            }
+        };
+        self.set_selected(to as usize);
+        MenuEvent::Moved(self.selected)
+    }
+
+    // as handle_key, for a command bound in the menu context (see keymap)
+    pub fn handle_command(&mut self, cmd:Command) -> MenuEvent {
+        let key = match cmd {
+            Command::Up => KeyCode::Up,
+            Command::Down => KeyCode::Down,
+            Command::PageUp => KeyCode::PageUp,
+            Command::PageDown => KeyCode::PageDown,
+            Command::First => KeyCode::Home,
+            Command::Last => KeyCode::End,
+            Command::Select => KeyCode::Enter,
+            Command::Cancel => KeyCode::Escape,
+            _ => { return MenuEvent::Ignored; }
+        };
+        self.handle_key(key)
+    }
+
+    fn render(&mut self) {
+        let size = self.window.size();
+        let h = size.y.max(1) as usize;
+        if self.selected < self.top { self.top = self.selected; }
+        if self.selected >= self.top + h { self.top = self.selected + 1 - h; }
+        self.window.fill(Glyph::new(' ', self.style.fg, self.style.bg));
+        for (row, i) in (self.top .. self.items.len().min(self.top + h)).enumerate() {
+            let (fg, bg) =
+                if i == self.selected { (self.style.hi_fg, self.style.hi_bg) }
+                else { (self.style.fg, self.style.bg) };
+            let p = Point2d::new(0, row as i32);
+            let text =
+                if self.hotkeys && i < 26 { format!("{}) {}", (b'a' + i as u8) as char, self.items[i]) }
+                else { self.items[i].clone() };
+            put_str_aligned(&self.window, &text, p, size.x, Align::Left, fg, bg);
+        }
+        if self.top > 0 {
+            self.window.set(Point2d::new(size.x-1, 0), Glyph::new('↑', self.style.dim_fg, self.style.bg));
+        }
+        if self.top + h < self.items.len() {
+            self.window.set(Point2d::new(size.x-1, size.y-1), Glyph::new('↓', self.style.dim_fg, self.style.bg));
+        }
+    }
+}
+
+impl Drop for ListMenu {
+    fn drop(&mut self) {
+        self.window.demolish();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// partial blocks, in eighths of a cell
+const BAR_BLOCKS : [char; 9] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+
+// horizontal progress/stat bar (eg hit points), with a centred label
+pub struct Bar {
+    window: WindowHandle<Glyph>,
+    label: String,
+    value: f64,
+    max: f64,
+    fill: Color,
+    empty: Color,
+    style: Style,
+}
+
+impl Bar {
+    pub fn new(parent:&WindowHandle<Glyph>, id:&str, offset:Point2d, width:i32, fill:Color) -> Self {
+        let window = parent.new_child(id.to_string(), offset, local_span(Point2d::new(width, 1)));
+        let mut res = Bar { window, label:String::new(), value:0.0, max:1.0, fill, empty:fill.scale(0.25), style:Style::default() };
+        res.render();
+        res
+    }
+
+    pub fn window(&self) -> &WindowHandle<Glyph> {
+        &self.window
+    }
+
+    pub fn set_value(&mut self, value:f64, max:f64) {
+        self.value = value;
+        self.max = max;
+        self.render();
+    }
+
+    pub fn set_label(&mut self, label:&str) {
+        self.label = label.to_string();
+        self.render();
+    }
+
+    pub fn set_colors(&mut self, fill:Color, empty:Color) {
+        self.fill = fill;
+        self.empty = empty;
+        self.render();
+    }
+
+    pub fn fraction(&self) -> f64 {
+        if self.max <= 0.0 { 0.0 } else { (self.value / self.max).clamp(0.0, 1.0) }
+    }
+
+    fn render(&mut self) {
+        let w = self.window.size().x;
+        let eighths = (self.fraction() * (w * 8) as f64).round() as i32;
+        let label : Vec<char> = self.label.chars().collect();
+        let lead = (w - label.len() as i32).max(0) / 2;
+        for x in 0 .. w {
+            let e = (eighths - 8*x).clamp(0, 8);
+            let g = match label.get((x - lead) as usize) {
+                // label text sits on whichever of fill/empty is mostly under it
+                Some(&ch) if x >= lead => Glyph::new(ch, self.style.fg, if e >= 4 { self.fill } else { self.empty }),
+                _ => Glyph::new(BAR_BLOCKS[e as usize], self.fill, self.empty),
+            };
+            self.window.set(Point2d::new(x, 0), g);
+        }
+    }
+}
+
+impl Drop for Bar {
+    fn drop(&mut self) {
+        self.window.demolish();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub struct Column {
+    pub title: String,
+    pub width: i32,         // 0 to share out whatever width is left over
+    pub align: Align,
+}
+
+impl Column {
+    pub fn new(title:&str, width:i32, align:Align) -> Self {
+        Column { title:title.to_string(), width, align }
+    }
+}
+
+// multi-column table with a header row, scrollable, with optional row highlight
+pub struct Table {
+    window: WindowHandle<Glyph>,
+    columns: Vec<Column>,
+    rows: Vec<Vec<String>>,
+    top: usize,
+    selected: Option<usize>,
+    style: Style,
+}
+
+impl Table {
+    pub fn new(parent:&WindowHandle<Glyph>, id:&str, offset:Point2d, size:Point2d, columns:Vec<Column>) -> Self {
+        let window = parent.new_child(id.to_string(), offset, local_span(size));
+        let mut res = Table { window, columns, rows:Vec::new(), top:0, selected:None, style:Style::default() };
+        res.render();
+        res
+    }
+
+    pub fn window(&self) -> &WindowHandle<Glyph> {
+        &self.window
+    }
+
+    pub fn set_rows(&mut self, rows:Vec<Vec<String>>) {
+        self.rows = rows;
+        self.top = self.top.min(self.rows.len().saturating_sub(1));
+        self.render();
+    }
+
+    pub fn push_row(&mut self, row:Vec<String>) {
+        self.rows.push(row);
+        self.render();
+    }
+
+    pub fn rows(&self) -> &[Vec<String>] {
+        &self.rows
+    }
+
+    pub fn set_selected(&mut self, sel:Option<usize>) {
+        self.selected = sel.filter(|&i| i < self.rows.len());
+        self.render();
+    }
+
+    pub fn scroll_by(&mut self, delta:i32) {
+        let max = self.rows.len().saturating_sub(self.body_height());
+        self.top = (self.top as i64 + delta as i64).max(0).min(max as i64) as usize;
+        self.render();
+    }
+
+    fn body_height(&self) -> usize {
+        (self.window.size().y - 1).max(0) as usize
+    }
+
+    // resolved column widths (fixed ones as given, the rest sharing what's left)
+    fn widths(&self) -> Vec<i32> {
+        let total = self.window.size().x;
+        let n = self.columns.len() as i32;
+        let fixed : i32 = self.columns.iter().map(|c| c.width).sum::<i32>() + (n - 1).max(0);
+        let flex = self.columns.iter().filter(|c| c.width == 0).count() as i32;
+        let spare = (total - fixed).max(0);
+        let mut k = 0;
+        self.columns.iter().map(|c| {
+            if c.width > 0 { c.width } else {
+                k += 1;
+                spare/flex + if k <= spare%flex {1} else {0}
+            }
+        }).collect()
+    }
+
+    fn render(&mut self) {
+        let size = self.window.size();
+        let widths = self.widths();
+        self.window.fill(Glyph::new(' ', self.style.fg, self.style.bg));
+        let mut x = 0;
+        for (c, w) in self.columns.iter().zip(widths.iter()) {
+            put_str_aligned(&self.window, &c.title, Point2d::new(x, 0), *w, c.align, self.style.dim_fg, self.style.bg);
+            x += w + 1;
+        }
+        let h = self.body_height();
+        if let Some(sel) = self.selected {
+            if sel < self.top { self.top = sel; }
+            if sel >= self.top + h { self.top = sel + 1 - h; }
+        }
+        for (row, i) in (self.top .. self.rows.len().min(self.top + h)).enumerate() {
+            let (fg, bg) =
+                if Some(i) == self.selected { (self.style.hi_fg, self.style.hi_bg) }
+                else { (self.style.fg, self.style.bg) };
+            let y = row as i32 + 1;
+            self.window.fill_rect(Rect2d::new(Point2d::new(0, y), Point2d::new(size.x, y+1)), Glyph::new(' ', fg, bg));
+            let mut x = 0;
+            for (j, (c, w)) in self.columns.iter().zip(widths.iter()).enumerate() {
+                let text = self.rows[i].get(j).map(|s| s.as_str()).unwrap_or("");
+                put_str_aligned(&self.window, text, Point2d::new(x, y), *w, c.align, fg, bg);
+                x += w + 1;
+            }
+        }
+    }
+}
+
+impl Drop for Table {
+    fn drop(&mut self) {
+        self.window.demolish();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub enum EditEvent {
+    Ignored,            // not an editing key
+    Edited,             // text or cursor changed
+    Submitted(String),  // enter (the line is cleared, & added to the history)
+    Cancelled,          // escape
+}
+
+// candidate completions for the word ending at the cursor
+pub type CompleteFn = Box<dyn FnMut(&str)->Vec<String>>;
+
+// lines entered so far, oldest first (shareable, to keep between edits)
+pub type History = Handle<Vec<String>>;
+
+const MAX_HISTORY : usize = 100;
+
+// one-line text input, scrolling sideways to keep the cursor in view:
+//  - left/right by char (by word with ctrl or alt), home/end (or ctrl+a/ctrl+e),
+//  - backspace/delete, ctrl+w (or ctrl+backspace) a word back, ctrl+u/ctrl+k to start/end,
+//  - up/down through the history, tab to complete (again to cycle the candidates);
+// chars come from InputEvent::Text, so anything the backend can deliver, with
+// combining marks composed into the char before where possible
+pub struct LineEdit {
+    window: WindowHandle<Glyph>,
+    prompt: String,
+    text: Vec<char>,
+    cursor: usize,
+    scroll: usize,                              // first char shown
+    history: History,
+    browsing: Option<(usize,Vec<char>)>,        // history entry shown, & the line as it was
+    completer: Option<CompleteFn>,
+    completing: Option<(usize,Vec<String>,usize)>,  // word start, candidates, next to cycle to
+    style: Style,
+}
+
+impl LineEdit {
+    pub fn new(parent:&WindowHandle<Glyph>, id:&str, offset:Point2d, width:i32, prompt:&str) -> Self {
+        let window = parent.new_child(id.to_string(), offset, local_span(Point2d::new(width, 1)));
+        let mut res = LineEdit { window, prompt:prompt.to_string(), text:Vec::new(), cursor:0, scroll:0,
+            history:Handle::new_from(Vec::new()), browsing:None, completer:None, completing:None, style:Style::default() };
+        res.render();
+        res
+    }
+
+    pub fn window(&self) -> &WindowHandle<Glyph> {
+        &self.window
+    }
+
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    // (cursor at the end)
+    pub fn set_text(&mut self, s:&str) {
+        self.text = s.chars().collect();
+        self.cursor = self.text.len();
+        self.browsing = None;
+        self.completing = None;
+        self.render();
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn set_history(&mut self, history:History) {
+        self.history = history;
+        self.browsing = None;
+    }
+
+    pub fn set_completer(&mut self, f:CompleteFn) {
+        self.completer = Some(f);
+    }
+
+    pub fn set_style(&mut self, style:Style) {
+        self.style = style;
+        self.render();
+    }
+
+    pub fn handle(&mut self, ev:&InputEvent) -> EditEvent {
+        let (key, ctrl) = match *ev {
+            InputEvent::Text(ch) => {
+                self.insert(ch);
+                return self.edited();
+            }
+            InputEvent::Key { key, ctrl, alt, .. } => (key, ctrl || alt),
+            _ => { return EditEvent::Ignored; }
+        };
+        if key != KeyCode::Tab { self.completing = None; }
+        let len = self.text.len();
+        match (key, ctrl) {
+            (KeyCode::Left, false) => { self.cursor = self.cursor.saturating_sub(1); }
+            (KeyCode::Left, true) => { self.cursor = self.word_start(self.cursor); }
+            (KeyCode::Right, false) => { self.cursor = (self.cursor + 1).min(len); }
+            (KeyCode::Right, true) => { self.cursor = self.word_end(self.cursor); }
+            (KeyCode::Home, _) | (KeyCode::A, true) => { self.cursor = 0; }
+            (KeyCode::End, _) | (KeyCode::E, true) => { self.cursor = len; }
+            (KeyCode::Backspace, false) => { if self.cursor > 0 { self.delete(self.cursor - 1 .. self.cursor); } }
+            (KeyCode::Backspace, true) | (KeyCode::W, true) => { self.delete(self.word_start(self.cursor) .. self.cursor); }
+            (KeyCode::Delete, _) => { if self.cursor < len { self.delete(self.cursor .. self.cursor + 1); } }
+            (KeyCode::U, true) => { self.delete(0 .. self.cursor); }
+            (KeyCode::K, true) => { self.delete(self.cursor .. len); }
+            (KeyCode::Up, _) => { self.browse(-1); }
+            (KeyCode::Down, _) => { self.browse(1); }
+            (KeyCode::Tab, _) => { self.complete(); }
+            (KeyCode::Enter, _) | (KeyCode::NumEnter, _) => { return self.submit(); }
+            (KeyCode::Escape, _) => { return EditEvent::Cancelled; }
+            _ => { return EditEvent::Ignored; }
+        }
+        self.edited()
+    }
+
+    fn edited(&mut self) -> EditEvent {
+        self.render();
+        EditEvent::Edited
+    }
+
+    fn insert(&mut self, ch:char) {
+        self.browsing = None;
+        self.completing = None;
+        if is_zero_width(ch) {
+            if let Some(i) = self.cursor.checked_sub(1) {
+                self.text[i] = compose(self.text[i], ch);
+            }
+            return;
+        }
+        self.text.insert(self.cursor, ch);
+        self.cursor += 1;
+    }
+
+    fn delete(&mut self, r:std::ops::Range<usize>) {
+        self.browsing = None;
+        self.cursor = r.start;
+        self.text.drain(r);
+    }
+
+    // start of the word before i (skipping spaces first)
+    fn word_start(&self, i:usize) -> usize {
+        let mut i = i;
+        while i > 0 && self.text[i-1] == ' ' { i -= 1; }
+        while i > 0 && self.text[i-1] != ' ' { i -= 1; }
+        i
+    }
+
+    // end of the word after i (skipping spaces first)
+    fn word_end(&self, i:usize) -> usize {
+        let mut i = i;
+        while i < self.text.len() && self.text[i] == ' ' { i += 1; }
+        while i < self.text.len() && self.text[i] != ' ' { i += 1; }
+        i
+    }
+
+    // back (-1) or forward (+1) through the history; forward past the newest
+    // entry comes back to the line being typed
+    fn browse(&mut self, d:i32) {
+        let n = self.history.borrow().len();
+        let to = match (&self.browsing, d < 0) {
+            (None, true) if n > 0 => n - 1,
+            (Some((i, _)), true) => i.saturating_sub(1),
+            (Some((i, _)), false) if i + 1 < n => i + 1,
+            (Some((_, line)), false) => {
+                self.text = line.clone();
+                self.cursor = self.text.len();
+                self.browsing = None;
+                return;
+            }
+            _ => { return; }
+        };
+        let line = match self.browsing.take() { Some((_, line)) => line, None => self.text.clone() };
+        self.text = self.history.borrow()[to].chars().collect();
+        self.cursor = self.text.len();
+        self.browsing = Some((to, line));
+    }
+
+    // completes the word at the cursor as far as all candidates agree, or if
+    // that gets no further, to each candidate in turn
+    fn complete(&mut self) {
+        let (start, cands, next) = match self.completing.take() {
+            Some(c) => c,
+            None => {
+                let f = match self.completer.as_mut() { Some(f) => f, None => { return; } };
+                let start = self.text[..self.cursor].iter().rposition(|c| *c == ' ').map_or(0, |i| i + 1);
+                let word : String = self.text[start..self.cursor].iter().collect();
+                let cands = f(&word);
+                let first = match cands.first() { Some(c) => c.clone(), None => { return; } };
+                let common : String = cands.iter().fold(first, |acc, c|
+                    acc.chars().zip(c.chars()).take_while(|(a, b)| a == b).map(|(a, _)| a).collect());
+                if common.chars().count() > word.chars().count() || cands.len() == 1 {
+                    self.replace_word(start, &common);
+                    if cands.len() > 1 { self.completing = Some((start, cands, 0)); }
+                    return;
+                }
+                (start, cands, 0)
+            }
+        };
+        self.replace_word(start, &cands[next]);
+        let next = (next + 1) % cands.len();
+        self.completing = Some((start, cands, next));
+    }
+
+    fn replace_word(&mut self, start:usize, s:&str) {
+        let cs : Vec<char> = s.chars().collect();
+        self.cursor = start + cs.len();
+        self.text.splice(start .. self.word_end_from(start), cs);
+    }
+
+    // (end of the word starting at i, without skipping spaces)
+    fn word_end_from(&self, i:usize) -> usize {
+        self.text[i..].iter().position(|c| *c == ' ').map_or(self.text.len(), |n| i + n)
+    }
+
+    fn submit(&mut self) -> EditEvent {
+        let line = self.text();
+        {
+            let mut h = self.history.borrow_mut();
+            if !line.trim().is_empty() && h.last() != Some(&line) { h.push(line.clone()); }
+            let excess = h.len().saturating_sub(MAX_HISTORY);
+            h.drain(..excess);
+        }
+        self.text.clear();
+        self.cursor = 0;
+        self.browsing = None;
+        self.render();
+        EditEvent::Submitted(line)
+    }
+
+    fn render(&mut self) {
+        let w = self.window.size().x;
+        self.window.fill(Glyph::new(' ', self.style.fg, self.style.bg));
+        let lead = put_str(&self.window, &self.prompt, Point2d::new(0,0), w, self.style.dim_fg, self.style.bg);
+        // (the cursor may sit just past the end)
+        let avail = (w - lead).max(1) as usize;
+        if self.cursor < self.scroll { self.scroll = self.cursor; }
+        if self.cursor >= self.scroll + avail { self.scroll = self.cursor + 1 - avail; }
+        for (x, i) in (self.scroll .. (self.text.len() + 1).min(self.scroll + avail)).enumerate() {
+            let ch = self.text.get(i).copied().unwrap_or(' ');
+            let (fg, bg) =
+                if i == self.cursor { (self.style.hi_fg, self.style.hi_bg) }
+                else { (self.style.fg, self.style.bg) };
+            self.window.set(Point2d::new(lead + x as i32, 0), Glyph::new(ch, fg, bg));
+        }
+    }
+}
+
+impl Drop for LineEdit {
+    fn drop(&mut self) {
+        self.window.demolish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root() -> WindowHandle<Glyph> {
+        WindowHandle::new("Root".into(), Rect2d::new(Point2d::new(0,0), Point2d::new(40,20)))
+    }
+
+    fn row(w:&WindowHandle<Glyph>, y:i32) -> String {
+        (0 .. w.size().x).map(|x| w.get(Point2d::new(x,y)).unwrap().ch).collect()
+    }
+
+    #[test]
+    fn wrapping() {
+        assert_eq!(wrap_chars("the quick brown fox", 9), vec!["the quick", "brown fox"]);
+        assert_eq!(wrap_chars("abcdefghij k", 4), vec!["abcd", "efgh", "ij k"]);
+        assert_eq!(wrap_chars("a\nb", 4), vec!["a", "b"]);
+        // combining marks take no cell of their own
+        assert_eq!(wrap_chars("s\u{030C}arrum a\u{0304}lum", 6), vec!["šarrum", "ālum"]);
+        let r = root();
+        r.fill(Glyph::new('.', Color::white(), Color::black()));
+        assert_eq!(put_str(&r, "t\u{0323}e\u{0310}x", Point2d::new(0,0), 10, Color::white(), Color::black()), 3);
+        assert!(row(&r, 0).starts_with("ṭex."));
+    }
+
+    #[test]
+    fn menu_navigation() {
+        let r = root();
+        let items = (0..6).map(|i| format!("item{}", i)).collect();
+        let mut m = ListMenu::new(&r, "Menu", Point2d::new(1,1), Point2d::new(10,3), items, true);
+        assert_eq!(m.handle_key(KeyCode::Up), MenuEvent::Moved(5));
+        assert_eq!(row(m.window(), 2), "f) item5  ");
+        assert_eq!(m.handle_key(KeyCode::Down), MenuEvent::Moved(0));
+        assert_eq!(row(m.window(), 0), "a) item0  ");
+        assert_eq!(m.handle_key(KeyCode::PageDown), MenuEvent::Moved(3));
+        assert_eq!(m.handle_key(KeyCode::Enter), MenuEvent::Chosen(3));
+        assert_eq!(m.handle_key(KeyCode::B), MenuEvent::Chosen(1));
+        assert_eq!(m.handle_key(KeyCode::Z), MenuEvent::Ignored);
+        assert_eq!(m.handle_key(KeyCode::Escape), MenuEvent::Cancelled);
+    }
+
+    #[test]
+    fn log_scrolls_and_drops() {
+        let r = root();
+        {
+            let mut log = MessageLog::new(&r, "Log", Point2d::new(0,10), Point2d::new(8,2), 3);
+            for i in 0..5 { log.push(&format!("m{}", i), Color::white()); }
+            assert_eq!(log.history().count(), 3);
+            assert!(row(log.window(), 1).starts_with("m4"));
+            log.scroll_by(10);
+            assert!(row(log.window(), 0).starts_with("m2"));
+            assert!(r.find_by_id("Log").is_some());
+        }
+        assert!(r.find_by_id("Log").is_none());
+    }
+
+    #[test]
+    fn bar_and_table() {
+        let r = root();
+        let mut b = Bar::new(&r, "Bar", Point2d::new(0,0), 4, Color::rose_garnet());
+        b.set_value(5.0, 16.0);
+        assert_eq!(row(b.window(), 0), "█▎  ");
+        let mut t = Table::new(&r, "Table", Point2d::new(0,2), Point2d::new(12,3),
+            vec![Column::new("Name", 0, Align::Left), Column::new("Hp", 3, Align::Right)]);
+        t.push_row(vec!["rat".into(), "4".into()]);
+        assert_eq!(row(t.window(), 0), "Name      Hp");
+        assert_eq!(row(t.window(), 1), "rat        4");
+    }
+
+    #[test]
+    fn line_editing() {
+        let r = root();
+        let mut e = LineEdit::new(&r, "Edit", Point2d::new(0,0), 10, "> ");
+        let typed = |e:&mut LineEdit, s:&str| for ch in s.chars() { e.handle(&InputEvent::Text(ch)); };
+        let key = |e:&mut LineEdit, k:KeyCode, ctrl:bool| e.handle(&InputEvent::Key { key:k, ctrl, shift:false, alt:false });
+        typed(&mut e, "h\u{032E}aia\u{0304}t\u{0323}um ok");
+        assert_eq!(e.text(), "ḫaiāṭum ok");
+        key(&mut e, KeyCode::Left, true);
+        key(&mut e, KeyCode::Left, true);
+        assert_eq!(e.cursor(), 0);
+        key(&mut e, KeyCode::Right, true);
+        typed(&mut e, "!");
+        key(&mut e, KeyCode::Delete, false);
+        assert_eq!(e.text(), "ḫaiāṭum!ok");
+        // scrolled to keep the cursor (at the end, on a blank) in view
+        key(&mut e, KeyCode::End, false);
+        assert_eq!(row(e.window(), 0), "> āṭum!ok ");
+        assert_eq!(e.window().get(Point2d::new(9,0)).unwrap().bg, Style::default().hi_bg);
+        key(&mut e, KeyCode::W, true);
+        assert_eq!(e.text(), "");
+        assert_eq!(key(&mut e, KeyCode::A, false), EditEvent::Ignored);
+        // history, back & forth to the line being typed
+        typed(&mut e, "first");
+        assert_eq!(key(&mut e, KeyCode::Enter, false), EditEvent::Submitted("first".into()));
+        typed(&mut e, "second");
+        key(&mut e, KeyCode::Enter, false);
+        typed(&mut e, "dra");
+        key(&mut e, KeyCode::Up, false);
+        key(&mut e, KeyCode::Up, false);
+        key(&mut e, KeyCode::Up, false);
+        assert_eq!(e.text(), "first");
+        key(&mut e, KeyCode::Down, false);
+        key(&mut e, KeyCode::Down, false);
+        assert_eq!(e.text(), "dra");
+        // completion: as far as the candidates agree, then cycling them
+        e.set_completer(Box::new(|w| ["draw", "drawl", "drop"].iter().filter(|c| c.starts_with(w)).map(|c| c.to_string()).collect()));
+        key(&mut e, KeyCode::Tab, false);
+        assert_eq!(e.text(), "draw");
+        key(&mut e, KeyCode::Tab, false);
+        key(&mut e, KeyCode::Tab, false);
+        assert_eq!(e.text(), "drawl");
+        key(&mut e, KeyCode::Tab, false);
+        assert_eq!(e.text(), "draw");
+        typed(&mut e, " d");
+        key(&mut e, KeyCode::Tab, false);
+        key(&mut e, KeyCode::Tab, false);
+        key(&mut e, KeyCode::Tab, false);
+        assert_eq!(e.text(), "draw drawl");
+        assert_eq!(key(&mut e, KeyCode::Escape, false), EditEvent::Cancelled);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
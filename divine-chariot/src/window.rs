@@ -1,5 +1,6 @@
 ////////////////////////////////////////////////////////////////////////////////
 
+use crate::core_systems::{BlendMode, Composite};
 use crate::point2d::*;
 use crate::rect2d::*;
 use crate::grid::*;
@@ -13,31 +14,34 @@ pub struct Window<Cell:Clone> {
     parent: WindowWeakHandle<Cell>,     // weak ref
     children: Vec<WindowHandle<Cell>>,  // strong refs
     offset: Point2d,                    // offset into parent local coordinates
+    pixel_offset: Point2d,              // sub-cell shift (pixels), only where drawn on a layer of its own
     size: Point2d,                      // ie local coordinates
     span: Rect2d,                       // (0,0) -- (sizex,sizey)
     hidden: bool,                       // hides all subchildren also!
+    blend: BlendMode,                   // how composited onto parent
     default: Cell,                      // default value
     self_data: Grid<Cell>,              // self data, no children
     data: Grid<Cell>,                   // merged with children
-    //dirty: Grid<bool>,                  // has anything changed in given cell
+    dirty: Vec<Rect2d>,                 // regions changed since last taken (disjoint-ish, local coords)
 }
 
-// TODO: track "dirty" flags to minimize copying (& screen updates)...
+// beyond this many separate dirty rects, just track their bounding rect
+const MAX_DIRTY_RECTS : usize = 8;
 
-impl<Cell:Clone+Copy+Default+Eq> Window<Cell> {
+impl<Cell:Clone+Copy+Default+Eq+Composite> Window<Cell> {
     fn new(id:String, span:Rect2d) -> Self {
         let parent = WeakHandle::new();
         let children = Vec::new();
         let offset = Point2d::default();
+        let pixel_offset = Point2d::default();
         let size = span.size();
         let hidden = false;
+        let blend = BlendMode::default();
         let default = Cell::default();
         let self_data = Grid::new(span, default);
         let data = Grid::new(span, default);
-        //let dirty = Grid::new(span, false);
-        Window { id, parent, children, offset, size, span, hidden, default, self_data, data
-        //, dirty
-        }
+        let dirty = vec![span];
+        Window { id, parent, children, offset, pixel_offset, size, span, hidden, blend, default, self_data, data, dirty }
     }
 
     fn get_self_data(&self, p:Point2d) -> Option<Cell> {
@@ -49,23 +53,74 @@ impl<Cell:Clone+Copy+Default+Eq> Window<Cell> {
     }
 
     fn set_self_data(&mut self, p:Point2d, cell:Cell) -> bool {
-        //if !self.span.contains(p) { return false; }
-        //if cell != self.self_data.get(p).unwrap() {
-            //self.dirty.set(p, true);
-            self.self_data.set(p, cell)
-        //}
+        match self.self_data.get_mut(p) {
+            Some(c) => {
+                if *c != cell {
+                    *c = cell;
+                    self.add_dirty(Rect2d{bl:p, tr:p+Point2d::new(1,1)});
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    // area covered in parent's local coordinates
+    fn area_in_parent(&self) -> Rect2d {
+        self.span - self.span.bl + self.offset
     }
 
+    fn add_dirty(&mut self, r:Rect2d) {
+        let mut r = match r.intersection(&self.span) {
+            Some(r) => r,
+            None => { return; }
+        };
+        if self.dirty.iter().any(|d| d.contains_rect(&r)) { return; }
+        // absorb anything touching r, so runs of single-cell writes coalesce
+        let grown = r.outset(1);
+        let mut i = 0;
+        while i < self.dirty.len() {
+            if self.dirty[i].intersects(&grown) {
+                r = r.union(&self.dirty.swap_remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        self.dirty.push(r);
+        if self.dirty.len() > MAX_DIRTY_RECTS {
+            let bb = Rect2d::bounding(self.dirty.drain(..)).unwrap();
+            self.dirty.push(bb);
+        }
+    }
+
+    // recomposites only the regions dirtied (here or in any visible descendant)
+    // since the last take_dirty(), leaving them in self.dirty for the parent
     fn update_data(&mut self) {
-        self.data.copy_from(&self.self_data);
-        for n in 0 .. self.children.len() {
-            let mut ch = self.children[n].borrow_mut();
+        let mut child_dirty = Vec::new();
+        for ch in self.children.iter() {
+            let mut ch = ch.borrow_mut();
             if ch.hidden { continue; }
             ch.update_data();
-            for p in ch.span {
-                if let Some(chdata) = ch.get_data(p) {
-                    let relp = ch.offset + (p - ch.span.bl);
-                    self.data.set(relp, chdata);
+            let shift = ch.offset - ch.span.bl;
+            child_dirty.extend(ch.dirty.drain(..).map(|r| r + shift));
+        }
+        for r in child_dirty {
+            self.add_dirty(r);
+        }
+        for r in self.dirty.iter() {
+            self.data.blit(&self.self_data, *r, r.bl);
+            for ch in self.children.iter() {
+                let ch = ch.borrow();
+                if ch.hidden { continue; }
+                let shift = ch.offset - ch.span.bl;
+                if let Some(cr) = (*r - shift).intersection(&ch.span) {
+                    if ch.blend == BlendMode::Replace {
+                        self.data.blit(&ch.data, cr, cr.bl + shift);
+                    } else {
+                        let mode = ch.blend;
+                        self.data.blit_with(&ch.data, cr, cr.bl + shift,
+                            &mut |below:&mut Cell, above:&Cell| *below = above.composite(below, mode));
+                    }
                 }
             }
         }
@@ -84,7 +139,7 @@ pub type WindowWeakHandle<Cell> = WeakHandle<Window<Cell>>;
 
 pub type WindowHandle<Cell> = Handle<Window<Cell>>;
 
-impl<Cell:Clone+Copy+Default+::std::fmt::Debug+Eq> WindowHandle<Cell> {
+impl<Cell:Clone+Copy+Default+::std::fmt::Debug+Eq+Composite> WindowHandle<Cell> {
     pub fn new(id:String, span:Rect2d) -> Self {
         WindowHandle::new_from(Window::new(id, span))
     }
@@ -96,16 +151,61 @@ impl<Cell:Clone+Copy+Default+::std::fmt::Debug+Eq> WindowHandle<Cell> {
         chw.borrow_mut().parent = self.weak();
         //chw.0.borrow_mut().z = self.0.borrow().children.len();
         self.borrow_mut().children.push(chw.clone());
+        chw.dirty_in_parent();
 
         chw
     }
 
+    // marks the area this window covers in its parent as needing recompositing
+    // (for changes of position, size, visibility or stacking)
+    fn dirty_in_parent(&self) {
+        if let Some(par) = self.parent() {
+            let area = self.borrow().area_in_parent();
+            par.borrow_mut().add_dirty(area);
+        }
+    }
+
+    pub fn mark_dirty(&self, rect:Rect2d) {
+        self.borrow_mut().add_dirty(rect);
+    }
+
+    pub fn mark_all_dirty(&self) {
+        let mut w = self.borrow_mut();
+        let span = w.span;
+        w.add_dirty(span);
+    }
+
+    // regions (local coordinates) changed since last call;
+    // call after update_data() on the root to find which cells need redrawing
+    pub fn take_dirty(&self) -> Vec<Rect2d> {
+        std::mem::take(&mut self.borrow_mut().dirty)
+    }
+
+    // (without taking them)
+    pub fn dirty(&self) -> Vec<Rect2d> {
+        self.borrow().dirty.clone()
+    }
+
     pub fn get_offset(&self) -> Point2d {
         self.borrow().offset
     }
 
     pub fn set_offset(&self, offset:Point2d) {
+        if offset == self.get_offset() { return; }
+        self.dirty_in_parent();
         self.borrow_mut().offset = offset;
+        self.dirty_in_parent();
+    }
+
+    pub fn get_pixel_offset(&self) -> Point2d {
+        self.borrow().pixel_offset
+    }
+
+    // (see Renderer::draw_window; flattened, windows stay on the cell grid)
+    pub fn set_pixel_offset(&self, offset:Point2d) {
+        if offset == self.get_pixel_offset() { return; }
+        self.borrow_mut().pixel_offset = offset;
+        self.dirty_in_parent();
     }
 
     pub fn get_size(&self) -> Point2d {
@@ -113,24 +213,20 @@ impl<Cell:Clone+Copy+Default+::std::fmt::Debug+Eq> WindowHandle<Cell> {
     }
 
     pub fn set_size(&self, size:Point2d) {
-        let mut w = self.borrow_mut();
-        if size == w.size { return; }
-        let span = Rect2d::new(self.span().bl, self.span().bl+size);
-
-        let mut self_data = Grid::new(span, Cell::default());
-        let data = Grid::new(span, Cell::default());
-
-        // TODO: blit
-        for p in w.span {
-            if let Some(cell) = w.get_self_data(p) {
-                self_data.set(p, cell);
-            } else {
-                self_data.set(p, w.default.clone());
-            }
+        if size == self.get_size() { return; }
+        self.dirty_in_parent();
+        {
+            let mut w = self.borrow_mut();
+            let default = w.default;
+            let self_data = w.self_data.resized(size, Anchor::Min, Anchor::Min, default);
+            let span = self_data.span();
+            w.data = Grid::new(span, default);
+            w.self_data = self_data;
+            w.span = span;
+            w.size = size;
+            w.dirty = vec![span];
         }
-        w.self_data = self_data;
-        w.data = data;
-        w.span = span;
+        self.dirty_in_parent();
     }
 
     pub fn get_default(&self) -> Cell {
@@ -146,26 +242,37 @@ impl<Cell:Clone+Copy+Default+::std::fmt::Debug+Eq> WindowHandle<Cell> {
     }
 
     pub fn set_hidden(&self, h:bool) {
+        if h == self.get_hidden() { return; }
         self.borrow_mut().hidden = h;
+        self.dirty_in_parent();
     }
 
 
+    pub fn get_blend(&self) -> BlendMode {
+        self.borrow().blend
+    }
+
+    pub fn set_blend(&self, mode:BlendMode) {
+        if mode == self.get_blend() { return; }
+        self.borrow_mut().blend = mode;
+        self.dirty_in_parent();
+    }
+
     pub fn span(&self) -> Rect2d {
         self.borrow().span
     }
 
     pub fn fill(&self, cell:Cell) {
         let mut w = self.borrow_mut();
-        for p in w.span {
-            w.set_self_data(p, cell);
-        }
+        w.self_data.fill(cell);
+        let span = w.span;
+        w.add_dirty(span);
     }
 
     pub fn fill_rect(&self, rect:Rect2d, cell:Cell) {
         let mut w = self.borrow_mut();
-        for p in rect {
-            w.set_self_data(p, cell);
-        }
+        w.self_data.fill_rect(rect, cell);
+        w.add_dirty(rect);
     }
 
     pub fn set(&self, p:Point2d, cell:Cell) -> bool {
@@ -199,22 +306,28 @@ impl<Cell:Clone+Copy+Default+::std::fmt::Debug+Eq> WindowHandle<Cell> {
 
     pub fn lower(&self) {
         if let Some(idx) = self.index_in_parent() {
-            let par = self.parent().unwrap();
-            let mut par = par.borrow_mut();
-            for oth in 0..idx {
-                par.children.swap(oth, idx);
+            {
+                let par = self.parent().unwrap();
+                let mut par = par.borrow_mut();
+                for oth in 0..idx {
+                    par.children.swap(oth, idx);
+                }
             }
+            self.dirty_in_parent();
         }
     }
 
     pub fn upper(&self) {
         if let Some(idx) = self.index_in_parent() {
-            let par = self.parent().unwrap();
-            let mut par = par.borrow_mut();
-            let n = par.children.len();
-            for oth in idx..(n-1) {
-                par.children.swap(oth, oth+1);
+            {
+                let par = self.parent().unwrap();
+                let mut par = par.borrow_mut();
+                let n = par.children.len();
+                for oth in idx..(n-1) {
+                    par.children.swap(oth, oth+1);
+                }
             }
+            self.dirty_in_parent();
         }
     }
 
@@ -222,6 +335,11 @@ impl<Cell:Clone+Copy+Default+::std::fmt::Debug+Eq> WindowHandle<Cell> {
         self.borrow().parent.upgrade()
     }
 
+    // bottom-most first
+    pub fn children(&self) -> Vec<WindowHandle<Cell>> {
+        self.borrow().children.clone()
+    }
+
     pub fn is_orphan(&self) -> bool {
         self.parent().is_none()
     }
@@ -229,9 +347,10 @@ impl<Cell:Clone+Copy+Default+::std::fmt::Debug+Eq> WindowHandle<Cell> {
     // delinks from parent, but keeps subtree intact
     pub fn orphan(&self) {
         if let Some(idx) = self.index_in_parent() {
+            self.dirty_in_parent();
             let p = self.parent().unwrap();
-            let mut p = p.borrow_mut();
-            p.children.remove(idx);
+            p.borrow_mut().children.remove(idx);
+            self.borrow_mut().parent = WeakHandle::new();
         }
     }
 
@@ -241,6 +360,8 @@ impl<Cell:Clone+Copy+Default+::std::fmt::Debug+Eq> WindowHandle<Cell> {
         w.parent = WeakHandle::new();
         w.children.iter().for_each(|ch|ch.internal_demolish());
         w.children.clear();
+        let span = w.span;
+        w.add_dirty(span);
         w.update_data();
     }
 
@@ -268,7 +389,12 @@ impl<Cell:Clone+Copy+Default+::std::fmt::Debug+Eq> WindowHandle<Cell> {
         let w = self.borrow();
         if w.span.contains(p) {
             for ch in w.children.iter().rev() {
-                let o = ch.select(p - ch.borrow().offset);
+                let (hidden, q) = {
+                    let c = ch.borrow();
+                    (c.hidden, p - c.offset + c.span.bl)
+                };
+                if hidden { continue; }
+                let o = ch.select(q);
                 if !o.is_none() { return o; }
             }
             Some((self.clone(),p))
@@ -277,6 +403,39 @@ impl<Cell:Clone+Copy+Default+::std::fmt::Debug+Eq> WindowHandle<Cell> {
         }
     }
 
+    // root (ie screen) coordinates to this window's local coordinates
+    pub fn to_local(&self, p:Point2d) -> Point2d {
+        match self.parent() {
+            Some(par) => {
+                let q = par.to_local(p);
+                let w = self.borrow();
+                q - w.offset + w.span.bl
+            }
+            None => p,
+        }
+    }
+
+    // this window's local coordinates to root (ie screen) coordinates
+    pub fn to_screen(&self, p:Point2d) -> Point2d {
+        let q = {
+            let w = self.borrow();
+            p - w.span.bl + w.offset
+        };
+        match self.parent() {
+            Some(par) => par.to_screen(q),
+            None => p,
+        }
+    }
+
+    // self, then parent, grandparent, ... up to the root
+    pub fn ancestry(&self) -> Vec<WindowHandle<Cell>> {
+        let mut res = vec![self.clone()];
+        while let Some(par) = res.last().unwrap().parent() {
+            res.push(par);
+        }
+        res
+    }
+
     pub fn update_data(&self) {
         self.borrow_mut().update_data();
     }
@@ -339,3 +498,135 @@ impl<Cell:Clone+Copy+Default+::std::fmt::Debug+Eq> WindowHandle<Cell> {
 }
 
 ////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::{Generator, Rng, Sampler};
+    use std::time::{Instant};
+
+    fn p(x:i32, y:i32) -> Point2d { Point2d::new(x, y) }
+
+    impl Composite for u8 {
+        fn composite(&self, below:&u8, mode:BlendMode) -> u8 {
+            if mode == BlendMode::Replace || *self != 0 { *self } else { *below }
+        }
+    }
+
+    fn tree() -> (WindowHandle<u8>, Vec<WindowHandle<u8>>) {
+        let root = WindowHandle::new("root".into(), Rect2d::new(p(0,0), p(40,20)));
+        let a = root.new_child("a".into(), p(2,2), Rect2d::new(p(-5,-5), p(5,5)));
+        let b = root.new_child("b".into(), p(8,4), Rect2d::new(p(0,0), p(20,12)));
+        let c = a.new_child("c".into(), p(-3,-1), Rect2d::new(p(0,0), p(4,4)));
+        let all = vec![root.clone(), a, b, c];
+        (root, all)
+    }
+
+    // reference composite: topmost visible window covering each point
+    fn naive(w:&WindowHandle<u8>, q:Point2d) -> u8 {
+        let b = w.borrow();
+        for ch in b.children.iter().rev() {
+            let c = ch.borrow();
+            let cq = q - c.offset + c.span.bl;
+            if !c.hidden && c.span.contains(cq) {
+                drop(c);
+                return naive(ch, cq);
+            }
+        }
+        *b.self_data.get(q).unwrap()
+    }
+
+    #[test]
+    fn incremental_matches_full() {
+        let mut rng = Rng::from_seed([3,1,4,1]);
+        let (root, ws) = tree();
+        let mut screen = Grid::new(root.span(), 0u8);
+        for round in 0 .. 300 {
+            for _ in 0 .. rng.gen_range(0,6) {
+                let w = rng.sample(&ws);
+                match rng.gen_range(0,10) {
+                    0 => { if !WindowHandle::ptr_eq(w, &root) { w.set_offset(w.get_offset() + p(rng.gen_range(-2,3), rng.gen_range(-2,3))); } }
+                    1 => { if !WindowHandle::ptr_eq(w, &root) { w.set_hidden(!w.get_hidden()); } }
+                    2 => { w.upper(); }
+                    3 => { let r = Rect2d::new(rng.sample(w.span()), rng.sample(w.span())); w.fill_rect(r, rng.gen()); }
+                    4 => { if !WindowHandle::ptr_eq(w, &root) { w.set_size((w.get_size() + p(rng.gen_range(-1,2), rng.gen_range(-1,2))).abs()); } }
+                    _ => { w.set(rng.sample(w.span()), rng.gen()); }
+                }
+            }
+            root.update_data();
+            for r in root.take_dirty() {
+                screen.blit(&root.borrow().data, r, r.bl);
+            }
+            for q in root.span() {
+                assert_eq!(*screen.get(q).unwrap(), naive(&root, q), "round {} at {}", round, q);
+            }
+        }
+    }
+
+    #[test]
+    fn idle_frame_is_clean() {
+        let (root, ws) = tree();
+        root.update_data();
+        assert!(!root.take_dirty().is_empty());
+        root.update_data();
+        assert!(root.take_dirty().is_empty());
+        ws[3].set(p(1,1), 7);
+        root.update_data();
+        let d = root.take_dirty();
+        assert_eq!(d.iter().map(|r| r.area()).sum::<i32>(), 1);
+    }
+
+    #[test]
+    fn blended_child_composites_onto_parent() {
+        use crate::core_systems::{Color, Glyph};
+        let root : WindowHandle<Glyph> = WindowHandle::new("root".into(), Rect2d::new(p(0,0), p(4,1)));
+        root.fill(Glyph::new('#', Color::white(), Color::rgb(0,0,200)));
+        let over = root.new_child("over".into(), p(0,0), Rect2d::new(p(0,0), p(4,1)));
+        over.set_blend(BlendMode::Normal);
+        over.set(p(0,0), Glyph::transparent());
+        over.set(p(1,0), Glyph::tint(Color::rgba(200,0,0,128)));
+        over.set(p(2,0), Glyph::new('@', Color::green(), Color::black()));
+        over.set(p(3,0), Glyph::tint(Color::rgb(10,10,10)));
+        root.update_data();
+        assert_eq!(root.data(p(0,0)), root.get(p(0,0)));
+        let tinted = root.data(p(1,0)).unwrap();
+        assert_eq!(tinted.ch, '#');
+        assert!(tinted.bg.r() > 90 && tinted.bg.b() > 90);
+        assert_eq!(root.data(p(2,0)), over.get(p(2,0)));
+        let hidden = root.data(p(3,0)).unwrap();
+        assert_eq!(hidden.fg, hidden.bg);
+        over.set_blend(BlendMode::Multiply);
+        root.update_data();
+        assert_eq!(root.data(p(3,0)).unwrap().bg.b(), 8);
+    }
+
+    // cargo test bench_idle_frames -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_idle_frames() {
+        let root : WindowHandle<u8> = WindowHandle::new("root".into(), Rect2d::new(p(0,0), p(100,40)));
+        let map = root.new_child("map".into(), p(1,1), Rect2d::new(p(-40,-15), p(41,16)));
+        let side = root.new_child("side".into(), p(83,1), Rect2d::new(p(0,0), p(16,38)));
+        let ws = [root.clone(), map, side];
+        let frames = 2000;
+        let mut sink = 0usize;
+        let mut frame = |full:bool| {
+            if full { ws.iter().for_each(|w| w.mark_all_dirty()); }
+            root.update_data();
+            for r in root.take_dirty() {
+                for q in r { sink += *root.borrow().data.get(q).unwrap() as usize + 1; }
+            }
+        };
+        let t = Instant::now();
+        for _ in 0 .. frames { frame(true); }
+        let full = t.elapsed();
+        let t = Instant::now();
+        for _ in 0 .. frames { frame(false); }
+        let incr = t.elapsed();
+        println!("idle frame: full {:?}/frame, incremental {:?}/frame ({} cells emitted)",
+            full / frames, incr / frames, sink);
+        assert!(incr < full);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////